@@ -1,6 +1,6 @@
 "use strict";
 
-const { Database: NativeDb, databasePrepareSync, databaseSyncSync, databaseExecSync, statementRunSync, statementGetSync, statementIterateSync, iteratorNextSync } = require("./index.js");
+const { Database: NativeDb, queryTimeoutStats, databasePrepareSync, databaseSyncSync, databasePushSync, databasePullSync, databaseCheckpointSync, databaseRekeySync, databaseAttachSync, databaseDetachSync, databaseMigrateSync, databaseSchemaSync, databaseDatabasesSync, databaseExecMultiSync, databaseExecSync, statementRunSync, statementGetSync, statementIterateSync, iteratorNextSync } = require("./index.js");
 const SqliteError = require("./sqlite-error.js");
 const { Authorization, Action } = require("./auth");
 
@@ -12,13 +12,19 @@ function convertError(err) {
       if (data && data.libsqlError) {
         if (data.code === "SQLITE_AUTH") {
           // For SQLITE_AUTH, preserve the JSON string for the test
-          return new SqliteError(err.message, data.code, data.rawCode);
+          return new SqliteError(err.message, data.code, data.rawCode, data);
         } else if (data.code === "SQLITE_NOTOPEN") {
           // Convert SQLITE_NOTOPEN to TypeError with expected message
           return new TypeError("The database connection is not open");
+        } else if (data.code === "SQLITE_RANGE") {
+          // Convert parameter binding errors to RangeError, as better-sqlite3 does
+          return new RangeError(data.message);
+        } else if (data.code === "SQLITE_MISUSE") {
+          // Convert misuse of bound parameters to TypeError, as better-sqlite3 does
+          return new TypeError(data.message);
         } else {
           // For all other errors, use the plain message string
-          return new SqliteError(data.message, data.code, data.rawCode);
+          return new SqliteError(data.message, data.code, data.rawCode, data);
         }
       }
     } catch (_) {
@@ -32,7 +38,15 @@ function isQueryOptions(value) {
   return value != null
     && typeof value === "object"
     && !Array.isArray(value)
-    && Object.prototype.hasOwnProperty.call(value, "queryTimeout");
+    && (Object.prototype.hasOwnProperty.call(value, "queryTimeout")
+      || Object.prototype.hasOwnProperty.call(value, "maxSteps"));
+}
+
+function joinBindParameters(bindParameters) {
+  if (bindParameters.length === 0) {
+    return undefined;
+  }
+  return bindParameters.length === 1 ? bindParameters[0] : bindParameters;
 }
 
 function splitBindParameters(bindParameters) {
@@ -45,7 +59,7 @@ function splitBindParameters(bindParameters) {
       queryOptions: bindParameters[bindParameters.length - 1],
     };
   }
-  return { params: bindParameters.length === 1 ? bindParameters[0] : bindParameters, queryOptions: undefined };
+  return { params: joinBindParameters(bindParameters), queryOptions: undefined };
 }
 
 /**
@@ -59,30 +73,166 @@ class Database {
    * @param {string} path - Path to the database file.
    */
   constructor(path, opts) {
-    this.db = new NativeDb(path, opts);
+    try {
+      this.db = new NativeDb(path, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+    if (opts?.busyHandler != null) {
+      this.busyHandler(opts.busyHandler);
+    }
     this.memory = this.db.memory
+    this.readonly = this.db.readonly
+    this.name = this.db.name
     const db = this.db;
     Object.defineProperties(this, {
       inTransaction: {
         get() {
-          return db.inTransaction();
+          return db.inTransaction;
+        }
+      },
+      open: {
+        get() {
+          return db.open;
         }
       },
     });
   }
 
-  sync() {
+  /**
+   * Syncs the database with its remote primary.
+   *
+   * @param {{ timeout?: number, onProgress?: (progress: { framesSynced: number, bytes: number, generation?: number }) => void }} [opts] -
+   *   Sync options. If the sync takes longer than `timeout` milliseconds, it
+   *   is abandoned with a `SQLITE_SYNC_TIMEOUT` error. `onProgress` is called
+   *   when the sync starts and as it applies frames.
+   */
+  sync(opts) {
+    const onProgress = opts?.onProgress;
+    if (onProgress != null && typeof onProgress !== "function") {
+      throw new TypeError("Expected onProgress to be a function");
+    }
+    const progress = onProgress == null ? null : (progress) => {
+      try {
+        onProgress(progress);
+      } catch (_) {
+        // A failing progress callback does not abort the sync.
+      }
+    };
+    try {
+      const result = databaseSyncSync(this.db, opts, progress);
+      return {
+        frames_synced: result.framesSynced,
+        replication_index: result.replicationIndex,
+        duration: result.duration,
+        bootstrapped: result.bootstrapped,
+        generation: result.generation
+      };
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the status of the syncs of the database.
+   *
+   * @returns {{ lastSuccess?: number, lastError?: Error, framesSynced: number, nextSync?: number, paused: boolean }}
+   *   Times are in milliseconds since the Unix epoch.
+   */
+  syncStatus() {
+    const status = this.db.syncStatus();
+    if (status.lastError !== undefined) {
+      status.lastError = convertError(new Error(status.lastError));
+    }
+    return status;
+  }
+
+  /**
+   * Pauses the background syncs enabled with the `syncPeriod` option.
+   */
+  pauseSync() {
+    this.db.pauseSync();
+    return this;
+  }
+
+  /**
+   * Resumes background syncs, starting with one right away.
+   */
+  resumeSync() {
+    this.db.resumeSync();
+    return this;
+  }
+
+  /**
+   * Sets a function to be notified of failed background syncs, or removes it
+   * with `null`.
+   *
+   * @param {((err: Error) => void) | null} callback - A function that is
+   *   called with the error of every failed background sync.
+   */
+  onSyncError(callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected first argument to be a function");
+    }
+    this.db.onSyncError(callback == null ? null : (message) => {
+      try {
+        callback(convertError(new Error(message)));
+      } catch (_) {
+        // A failing callback does not stop background syncs.
+      }
+    });
+    return this;
+  }
+
+  /**
+   * Pushes the local changes of an offline replica to its remote primary.
+   *
+   * @param {{ timeout?: number }} [opts] - Sync options.
+   * @returns {{ frames: number, transactions: number }} The changes pushed.
+   */
+  push(opts) {
+    try {
+      return databasePushSync(this.db, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Pulls the changes of the remote primary into an offline replica, which
+   * must not have local changes waiting to be pushed.
+   *
+   * @param {{ timeout?: number }} [opts] - Sync options.
+   */
+  pull(opts) {
     try {
-      const result = databaseSyncSync(this.db);
+      const result = databasePullSync(this.db, opts);
       return {
-        frames_synced: result.frames_synced,
-        replication_index: result.replication_index
+        frames_synced: result.framesSynced,
+        replication_index: result.replicationIndex,
+        duration: result.duration,
+        bootstrapped: result.bootstrapped,
+        generation: result.generation
       };
     } catch (err) {
       throw convertError(err);
     }
   }
 
+  /**
+   * Returns the local changes of an offline replica that have not been
+   * pushed yet.
+   *
+   * @returns {{ frames: number, transactions: number }}
+   */
+  pendingChanges() {
+    try {
+      return this.db.pendingChanges();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
   syncUntil(replicationIndex) {
     throw new Error("not implemented");
   }
@@ -91,10 +241,11 @@ class Database {
    * Prepares a SQL statement for execution.
    *
    * @param {string} sql - The SQL statement string to prepare.
+   * @param {object} [queryOptions] - Optional per-call overrides, such as `queryTimeout`.
    */
-  prepare(sql) {
+  prepare(sql, queryOptions) {
     try {
-      const stmt = databasePrepareSync(this.db, sql);
+      const stmt = databasePrepareSync(this.db, sql, queryOptions);
       return new Statement(stmt);
     } catch (err) {
       throw convertError(err);
@@ -196,6 +347,25 @@ class Database {
     }
   }
 
+  /**
+   * Executes a SQL script statement by statement, returning one result object
+   * per statement.
+   *
+   * Execution stops at the first statement that fails. The error has the
+   * `statementIndex` of the statement and its `offset` in the script.
+   *
+   * @param {string} sql - The SQL script to execute.
+   * @param {object} [queryOptions] - Optional per-query overrides, for the whole script.
+   * @returns {Array<{ sql: string, start: number, end: number, columns: string[], rows: Array<Record<string, any>>, changes: number, lastInsertRowid: number }>}
+   */
+  execMulti(sql, queryOptions) {
+    try {
+      return databaseExecMultiSync(this.db, sql, queryOptions);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
   /**
    * Executes a batch of SQL statements sequentially, returning one
    * result object per input statement.
@@ -236,7 +406,7 @@ class Database {
         const columnNames = cols.map((c) => c.name);
         const columnTypes = cols.map((c) => c.type ?? "");
 
-        if (columnNames.length > 0) {
+        if (stmt.reader) {
           // Reader statement: collect the returned rows.
           if (raw) {
             stmt.raw(true);
@@ -274,6 +444,205 @@ class Database {
     this.db.interrupt();
   }
 
+  /**
+   * Registers a callback notified with the connection's running count of
+   * virtual machine instructions, roughly every `period` instructions.
+   * Call without a callback to remove it.
+   *
+   * @param {number} period - Number of VM instructions between notifications.
+   * @param {(steps: number) => void} [callback] - The function to notify.
+   */
+  progressHandler(period, callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected second argument to be a function");
+    }
+    try {
+      this.db.progressHandler(period, callback);
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Sets how operations that find the database locked are retried, replacing
+   * the `timeout` option.
+   *
+   * @param {((retries: number) => boolean) | { maxRetries?: number, initialDelay?: number, maxDelay?: number } | null} handler -
+   *   A function that is called with the number of retries so far and returns
+   *   whether to retry, an exponential backoff policy, or `null` to restore
+   *   the busy timeout of the `timeout` option.
+   */
+  busyHandler(handler) {
+    if (handler != null && typeof handler !== "function" && typeof handler !== "object") {
+      throw new TypeError("Expected first argument to be a function or an object");
+    }
+    try {
+      if (typeof handler === "function") {
+        this.db.busyHandler(handler);
+      } else if (handler != null) {
+        this.db.busyBackoff(handler);
+      } else {
+        this.db.busyHandler(null);
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Checkpoints the write-ahead log.
+   *
+   * @param {Object} [opts] - Checkpoint options.
+   * @param {"passive" | "full" | "restart" | "truncate"} [opts.mode] - The checkpoint mode, `"passive"` by default.
+   * @param {string} [opts.schema] - The attached database to checkpoint, all of them by default.
+   * @returns {{ busy: boolean, logFrames: number, checkpointedFrames: number }}
+   */
+  checkpoint(opts) {
+    try {
+      return databaseCheckpointSync(this.db, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns how the database is encrypted at rest.
+   *
+   * @returns {{ encrypted: boolean, cipher?: string }}
+   */
+  encryptionInfo() {
+    try {
+      return this.db.encryptionInfo();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Changes the encryption key of the database, which must be reopened with
+   * the new key afterwards. A plaintext database is encrypted, and a `null`
+   * or empty key decrypts the database to plaintext.
+   *
+   * @param {string | null} newKey - The new encryption key.
+   * @param {Object} [opts] - Rekey options.
+   * @param {string} [opts.cipher] - The cipher to re-encrypt the database with.
+   */
+  rekey(newKey, opts) {
+    try {
+      databaseRekeySync(this.db, newKey, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Attaches a database file to the connection under a schema name.
+   *
+   * @param {string} path - The path of the database file, or a URI filename.
+   * @param {string} schemaName - The schema name to attach the database as.
+   * @param {Object} [opts] - Attach options.
+   * @param {string} [opts.encryptionKey] - The encryption key of the attached database.
+   * @param {boolean} [opts.readonly] - Whether to attach the database read-only.
+   */
+  attach(path, schemaName, opts) {
+    try {
+      databaseAttachSync(this.db, path, schemaName, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Detaches a database attached to the connection.
+   *
+   * @param {string} schemaName - The schema name the database is attached as.
+   */
+  detach(schemaName) {
+    try {
+      databaseDetachSync(this.db, schemaName);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Applies the migrations not yet applied to the database, in order and in a
+   * single transaction, and returns the ids of those applied. Fails if the
+   * migrations already applied were changed since. If an embedded replica
+   * then fails to sync the migrations, the error is returned as `syncError`.
+   *
+   * @param {Array<{id: string, up: string}>} migrations - The migrations, in the order they apply.
+   * @param {Object} [opts] - Migration options.
+   * @param {string} [opts.table] - The table recording the applied migrations, `_migrations` by default.
+   */
+  migrate(migrations, opts) {
+    try {
+      const result = databaseMigrateSync(this.db, migrations, opts);
+      if (result.syncError != null) {
+        result.syncError = convertError(new Error(result.syncError));
+      }
+      return result;
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the tables, views, indexes, triggers and virtual tables of the
+   * database, with the columns, foreign keys and indexes of each table.
+   *
+   * @param {Object} [opts] - Schema options.
+   * @param {string} [opts.schema] - The attached database to read the schema of, `main` by default.
+   */
+  schema(opts) {
+    try {
+      return databaseSchemaSync(this.db, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the databases of the connection, each with its schema name, the
+   * path of its file and whether it is read-only.
+   */
+  databases() {
+    try {
+      return databaseDatabasesSync(this.db);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Sets a function to be notified after every commit to a write-ahead log,
+   * which turns automatic checkpointing off, or removes it with `null`.
+   *
+   * @param {((schema: string, pages: number) => void) | null} callback - A function
+   *   that is called with the name of the database committed to and the number
+   *   of frames in its write-ahead log.
+   */
+  onWal(callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected first argument to be a function");
+    }
+    try {
+      this.db.onWal(callback);
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Returns query timeout counters for this connection.
+   */
+  stats() {
+    return this.db.stats();
+  }
+
   /**
    * Closes the database connection.
    */
@@ -298,6 +667,17 @@ class Database {
     return this;
   }
 
+  /**
+   * Sets the default query timeout for subsequent operations, including those
+   * of already prepared statements.
+   *
+   * @param {number} [timeout] - Maximum number of milliseconds a query may run, or 0 for no timeout.
+   */
+  setDefaultQueryTimeout(timeout) {
+    this.db.setDefaultQueryTimeout(timeout);
+    return this;
+  }
+
   unsafeMode(...args) {
     throw new Error("not implemented");
   }
@@ -341,8 +721,47 @@ class Statement {
     return this;
   }
 
+  /**
+   * Binds parameters to the statement for all its executions, which then take no bind parameters.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  bind(...bindParameters) {
+    try {
+      this.stmt.bind(joinBindParameters(bindParameters));
+      return this;
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * The SQL text the statement was prepared from.
+   */
+  get source() {
+    return this.stmt.source;
+  }
+
+  /**
+   * Whether the statement returns rows.
+   */
   get reader() {
-    return this.stmt.columns().length > 0;
+    return this.stmt.reader;
+  }
+
+  /**
+   * Whether the statement makes no direct change to the database. Always
+   * `false` for statements of remote databases.
+   */
+  get readonly() {
+    return this.stmt.readonly;
+  }
+
+  /**
+   * Whether the statement is executing, or has an iterator that is not finished.
+   */
+  get busy() {
+    return this.stmt.busy;
   }
 
   /**
@@ -423,7 +842,36 @@ class Statement {
    * Returns the columns in the result set returned by this prepared statement.
    */
   columns() {
-    return this.stmt.columns();
+    try {
+      return this.stmt.columns();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the parameters of the statement, with their position and name.
+   */
+  parameters() {
+    try {
+      return this.stmt.parameters();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the SQL text of the statement with the bind parameters expanded
+   * into it, or `null` for statements of remote databases.
+   *
+   * @param bindParameters - The bind parameters to expand.
+   */
+  expandedSql(...bindParameters) {
+    try {
+      return this.stmt.expandedSql(joinBindParameters(bindParameters));
+    } catch (err) {
+      throw convertError(err);
+    }
   }
 
   /**
@@ -433,12 +881,27 @@ class Statement {
     this.stmt.safeIntegers(toggle);
     return this;
   }
+
+  /**
+   * Sets the query timeout of the statement, overriding the database default.
+   *
+   * @param {number} [timeout] - Maximum number of milliseconds a query may run,
+   *   0 for no timeout, or `undefined` to use the database default.
+   */
+  setQueryTimeout(timeout) {
+    this.stmt.setQueryTimeout(timeout);
+    return this;
+  }
 }
 
 function wrappedIter(it) {
   return {
     next() {
-      return iteratorNextSync(it);
+      try {
+        return iteratorNextSync(it);
+      } catch (err) {
+        throw convertError(err);
+      }
     },
     return(value) {
       if (typeof it.close === "function") {
@@ -459,6 +922,7 @@ module.exports = Database;
 module.exports.SqliteError = SqliteError;
 module.exports.Authorization = Authorization;
 module.exports.Action = Action;
+module.exports.queryTimeoutStats = queryTimeoutStats;
 
 function normalizeBatchMode(mode) {
   switch (String(mode).toLowerCase()) {
@@ -10,27 +10,317 @@ export interface Options {
   syncUrl?: string
   readYourWrites?: boolean
   syncPeriod?: number
+  offline?: boolean
   encryptionCipher?: string
   encryptionKey?: string
   remoteEncryptionKey?: string
   defaultQueryTimeout?: number
+  readonly?: boolean
+  fileMustExist?: boolean
+  strictParams?: boolean
+}
+/** Retry policy for operations that find the database locked. */
+export interface BusyBackoff {
+  maxRetries?: number
+  initialDelay?: number
+  maxDelay?: number
 }
 /** Per-query execution options. */
 export interface QueryOptions {
   queryTimeout?: number
+  maxSteps?: number
+}
+/** Query timeout statistics. */
+export interface QueryTimeoutStats {
+  registered: number
+  fired: number
+  reaped: number
+  heapSize: number
+  active: number
 }
+/** Database statistics. */
+export interface DatabaseStats {
+  queryTimeouts: QueryTimeoutStats
+}
+/** Returns query timeout statistics for all databases in the process. */
+export declare function queryTimeoutStats(): QueryTimeoutStats
 export declare function connect(path: string, opts?: Options | undefined | null): Promise<Database>
+/** Checkpoint options. */
+export interface CheckpointOptions {
+  mode?: string
+  schema?: string
+}
+/** Close options. */
+export interface CloseOptions {
+  timeout?: number
+}
+/** Encryption at rest of a database. */
+export interface EncryptionInfo {
+  /** Whether the database is encrypted. */
+  encrypted: boolean
+  /** The cipher the database is encrypted with. */
+  cipher?: string
+}
+/** Encryption key change options. */
+export interface RekeyOptions {
+  cipher?: string
+}
+/** Attach options. */
+export interface AttachOptions {
+  encryptionKey?: string
+  readonly?: boolean
+}
+/** A parameter of a statement. */
+export interface StatementParameter {
+  /** The position of the parameter, starting at 1. */
+  index: number
+  /** The name of the parameter, prefix included, unless it is anonymous. */
+  name?: string
+}
+/** A database of a connection. */
+export interface DatabaseEntry {
+  /** The schema name of the database. */
+  name: string
+  /**
+   * The path of the database file, empty for in-memory and temporary
+   * databases.
+   */
+  file: string
+  /** Whether the database is read-only. */
+  readonly: boolean
+}
+/** A schema migration. */
+export interface Migration {
+  id: string
+  up: string
+}
+/** Migration options. */
+export interface MigrateOptions {
+  table?: string
+}
+/** Result of a migration. */
+export interface MigrateResult {
+  /** The ids of the migrations applied, in order. */
+  applied: Array<string>
+  /**
+   * The error an embedded replica failed to sync the applied migrations
+   * from the primary with, if it did, encoded as the reason of a thrown
+   * error for the JavaScript layer to convert.
+   */
+  syncError?: string
+}
+/** Schema introspection options. */
+export interface SchemaOptions {
+  schema?: string
+}
+/** The objects of a database, in the order they were created. */
+export interface DatabaseSchema {
+  /** The ordinary tables. */
+  tables: Array<SchemaTable>
+  /** The views. */
+  views: Array<SchemaView>
+  /** The virtual tables. */
+  virtualTables: Array<SchemaView>
+  /** The indexes created with `CREATE INDEX`. */
+  indexes: Array<SchemaIndex>
+  /** The triggers. */
+  triggers: Array<SchemaTrigger>
+}
+/** An ordinary table. */
+export interface SchemaTable {
+  /** The name of the table. */
+  name: string
+  /** The `CREATE TABLE` statement. */
+  sql?: string
+  /** Whether the table is a `STRICT` table. */
+  strict: boolean
+  /** Whether the table is a `WITHOUT ROWID` table. */
+  withoutRowid: boolean
+  /** The columns, including generated ones. */
+  columns: Array<SchemaColumn>
+  /** The foreign key constraints. */
+  foreignKeys: Array<SchemaForeignKey>
+  /**
+   * The indexes, including those of `UNIQUE` and `PRIMARY KEY`
+   * constraints.
+   */
+  indexes: Array<SchemaTableIndex>
+}
+/** A view, or a virtual table. */
+export interface SchemaView {
+  /** The name of the view or virtual table. */
+  name: string
+  /** The statement that created it. */
+  sql?: string
+  /** The columns, including hidden ones. */
+  columns: Array<SchemaColumn>
+}
+/** A column of a table or view. */
+export interface SchemaColumn {
+  /** The name of the column. */
+  name: string
+  /** The declared type, empty if the column has none. */
+  type: string
+  /** Whether the column has a `NOT NULL` constraint. */
+  notNull: boolean
+  /** The SQL text of the default value. */
+  defaultValue?: string
+  /**
+   * The position of the column in the primary key, starting at 1, or 0 if
+   * it is not part of it.
+   */
+  primaryKey: number
+  /** Whether the column is a hidden column of a virtual table. */
+  hidden: boolean
+  /** `"virtual"` or `"stored"` for generated columns. */
+  generated?: string
+}
+/** A foreign key constraint. */
+export interface SchemaForeignKey {
+  /** The referenced table. */
+  table: string
+  /** The columns of the constraint. */
+  from: Array<string>
+  /**
+   * The referenced columns, `null` for the primary key of the referenced
+   * table.
+   */
+  to: Array<string | undefined | null>
+  /** The `ON UPDATE` action. */
+  onUpdate: string
+  /** The `ON DELETE` action. */
+  onDelete: string
+  /** The `MATCH` clause. */
+  match: string
+}
+/** An index of a table. */
+export interface SchemaTableIndex {
+  /** The name of the index. */
+  name: string
+  /** Whether the index is unique. */
+  unique: boolean
+  /**
+   * `"c"` for indexes created with `CREATE INDEX`, `"u"` for `UNIQUE`
+   * constraints and `"pk"` for `PRIMARY KEY` constraints.
+   */
+  origin: string
+  /** Whether the index is a partial index. */
+  partial: boolean
+  /** The key columns of the index. */
+  columns: Array<SchemaIndexColumn>
+}
+/** A key column of an index. */
+export interface SchemaIndexColumn {
+  /** The name of the indexed column, unset for expressions. */
+  name?: string
+  /** Whether the column is sorted in descending order. */
+  descending: boolean
+  /** The collating sequence of the column. */
+  collation: string
+}
+/** An index created with `CREATE INDEX`. */
+export interface SchemaIndex {
+  /** The name of the index. */
+  name: string
+  /** The table the index is on. */
+  table: string
+  /** The `CREATE INDEX` statement. */
+  sql?: string
+}
+/** A trigger. */
+export interface SchemaTrigger {
+  /** The name of the trigger. */
+  name: string
+  /** The table or view the trigger is on. */
+  table: string
+  /** The `CREATE TRIGGER` statement. */
+  sql?: string
+}
+/** Result of a checkpoint. */
+export interface CheckpointResult {
+  /** Whether readers or writers kept the checkpoint from completing. */
+  busy: boolean
+  /** The number of frames in the write-ahead log. */
+  logFrames: number
+  /** The number of frames checkpointed into the database. */
+  checkpointedFrames: number
+}
+/** Sync options. */
+export interface SyncOptions {
+  timeout?: number
+}
+/** Local changes of an offline replica. */
+export interface PendingChanges {
+  /** The number of write-ahead log frames. */
+  frames: number
+  /** The number of transactions. */
+  transactions: number
+}
+/** What is known about the syncs of a database. */
+export interface SyncStatus {
+  /**
+   * When the last successful sync completed, in milliseconds since the
+   * Unix epoch.
+   */
+  lastSuccess?: number
+  /** The error of the last sync, unless it succeeded. */
+  lastError?: string
+  /** The number of frames synced so far. */
+  framesSynced: number
+  /**
+   * When the next background sync is due, in milliseconds since the Unix
+   * epoch.
+   */
+  nextSync?: number
+  /** Whether background syncs are paused. */
+  paused: boolean
+}
+/** Progress of a database sync operation. */
+export interface SyncProgress {
+  /** The number of frames applied so far. */
+  framesSynced: number
+  /** The number of bytes of frames applied so far. */
+  bytes: number
+  /** The generation being synced, with the v2 sync protocol. */
+  generation?: number
+}
 /** Result of a database sync operation. */
 export interface SyncResult {
   /** The number of frames synced. */
   framesSynced: number
   /** The replication index. */
   replicationIndex: number
+  /** The number of milliseconds the sync took. */
+  duration: number
+  /** Whether the sync downloaded the database from scratch. */
+  bootstrapped: boolean
+  /** The generation synced to, with the v2 sync protocol. */
+  generation?: number
 }
 /** Prepares a statement in blocking mode. */
-export declare function databasePrepareSync(db: Database, sql: string): Statement
+export declare function databasePrepareSync(db: Database, sql: string, queryOptions?: QueryOptions | undefined | null): Statement
 /** Syncs the database in blocking mode. */
-export declare function databaseSyncSync(db: Database): SyncResult
+export declare function databaseSyncSync(db: Database, opts?: SyncOptions | undefined | null, progress?: (...args: any[]) => any | undefined | null): SyncResult
+/** Pushes the local changes of an offline replica in blocking mode. */
+export declare function databasePushSync(db: Database, opts?: SyncOptions | undefined | null): PendingChanges
+/** Pulls the changes of the primary into an offline replica in blocking mode. */
+export declare function databasePullSync(db: Database, opts?: SyncOptions | undefined | null): SyncResult
+/** Checkpoints the write-ahead log in blocking mode. */
+export declare function databaseCheckpointSync(db: Database, opts?: CheckpointOptions | undefined | null): CheckpointResult
+/** Changes the encryption key of the database in blocking mode. */
+export declare function databaseRekeySync(db: Database, newKey?: string | undefined | null, opts?: RekeyOptions | undefined | null): void
+/** Attaches a database file in blocking mode. */
+export declare function databaseAttachSync(db: Database, path: string, schema: string, opts?: AttachOptions | undefined | null): void
+/** Detaches a database in blocking mode. */
+export declare function databaseDetachSync(db: Database, schema: string): void
+/** Applies schema migrations in blocking mode. */
+export declare function databaseMigrateSync(db: Database, migrations: Array<Migration>, opts?: MigrateOptions | undefined | null): MigrateResult
+/** Returns the schema of a database in blocking mode. */
+export declare function databaseSchemaSync(db: Database, opts?: SchemaOptions | undefined | null): DatabaseSchema
+/** Returns the databases of the connection in blocking mode. */
+export declare function databaseDatabasesSync(db: Database): Array<DatabaseEntry>
+/** Executes a script statement by statement in blocking mode. */
+export declare function databaseExecMultiSync(db: Database, sql: string, queryOptions?: QueryOptions | undefined | null): Array<ScriptStatementResult>
 /** Executes SQL in blocking mode. */
 export declare function databaseExecSync(db: Database, sql: string, queryOptions?: QueryOptions | undefined | null): void
 /** Gets first row from statement in blocking mode. */
@@ -38,6 +328,23 @@ export declare function statementGetSync(stmt: Statement, params?: unknown | und
 /** Runs a statement in blocking mode. */
 export declare function statementRunSync(stmt: Statement, params?: unknown | undefined | null, queryOptions?: QueryOptions | undefined | null): RunResult
 export declare function statementIterateSync(stmt: Statement, params?: unknown | undefined | null, queryOptions?: QueryOptions | undefined | null): RowsIterator
+/** The outcome of a statement of a script, as returned by `execMulti()`. */
+export interface ScriptStatementResult {
+  /** The SQL text of the statement. */
+  sql: string
+  /** The offset of the statement in the script. */
+  start: number
+  /** The offset of the end of the statement in the script. */
+  end: number
+  /** The names of the columns of the rows. */
+  columns: Array<string>
+  /** The rows returned by the statement. */
+  rows: Array<unknown>
+  /** The number of rows changed by the statement. */
+  changes: number
+  /** The rowid of the last row inserted by the connection. */
+  lastInsertRowid: number
+}
 /** SQLite `run()` result object */
 export interface RunResult {
   changes: number
@@ -59,20 +366,28 @@ export declare class Database {
   constructor(path: string, opts?: Options | undefined | null)
   /** Returns whether the database is in memory-only mode. */
   get memory(): boolean
+  /** Returns whether the database was opened in read-only mode. */
+  get readonly(): boolean
+  /** Returns the path the database was opened with. */
+  get name(): string
+  /** Returns whether the database connection is open. */
+  get open(): boolean
   /** Returns whether the database is in a transaction. */
-  inTransaction(): boolean
+  get inTransaction(): boolean
   /**
    * Prepares a statement for execution.
    *
    * # Arguments
    *
    * * `sql` - The SQL statement to prepare.
+   * * `query_options` - Optional per-call overrides, such as the timeout
+   *   for preparing the statement.
    *
    * # Returns
    *
    * A `Statement` instance.
    */
-  prepare(sql: string): Promise<Statement>
+  prepare(sql: string, queryOptions?: QueryOptions | undefined | null): Promise<Statement>
   /**
    * Sets the authorizer for the database.
    *
@@ -135,14 +450,185 @@ export declare class Database {
    * * `sql` - The SQL statement to execute.
    */
   exec(sql: string, queryOptions?: QueryOptions | undefined | null): Promise<void>
+  /**
+   * Executes a script statement by statement.
+   *
+   * # Arguments
+   *
+   * * `sql` - The script.
+   * * `query_options` - Optional per-query overrides, for the whole script.
+   *
+   * # Returns
+   *
+   * The outcome of each statement, or the error of the first statement that
+   * failed, with its index and its offset in the script.
+   */
+  execMulti(sql: string, queryOptions?: QueryOptions | undefined | null): Promise<Array<ScriptStatementResult>>
   /**
    * Syncs the database.
    *
+   * # Arguments
+   *
+   * * `opts` - Optional sync options. If `timeout` is set and the sync does
+   *   not complete in time, it is abandoned with a `SQLITE_SYNC_TIMEOUT`
+   *   error.
+   * * `progress` - Optional function notified as the sync applies frames.
+   *
    * # Returns
    *
    * A `SyncResult` instance.
    */
-  sync(): Promise<SyncResult>
+  sync(opts?: SyncOptions | undefined | null, progress?: ((value: SyncProgress) => any) | undefined | null): Promise<SyncResult>
+  /** Returns what is known about the syncs of the database. */
+  syncStatus(): SyncStatus
+  /** Pauses background syncs. A sync in progress completes. */
+  pauseSync(): void
+  /** Resumes background syncs, starting with one right away. */
+  resumeSync(): void
+  /**
+   * Sets a callback to be notified of failed background syncs, or removes
+   * it when called without one.
+   *
+   * The callback is invoked asynchronously on the JavaScript thread with
+   * the error message of the failed sync.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   * * `callback` - The function to notify.
+   */
+  onSyncError(callback?: (...args: any[]) => any | undefined | null): void
+  /**
+   * Returns the local changes of an offline replica that have not been
+   * pushed to the primary yet.
+   */
+  pendingChanges(): PendingChanges
+  /**
+   * Pushes the local changes of an offline replica to the primary.
+   *
+   * # Arguments
+   *
+   * * `opts` - Optional sync options.
+   *
+   * # Returns
+   *
+   * The changes that were pushed. If the primary cannot be reached, none
+   * are, and they remain pending.
+   */
+  push(opts?: SyncOptions | undefined | null): Promise<PendingChanges>
+  /**
+   * Pulls the changes of the primary into an offline replica, which must
+   * not have local changes waiting to be pushed.
+   *
+   * # Arguments
+   *
+   * * `opts` - Optional sync options.
+   *
+   * # Returns
+   *
+   * A `SyncResult` instance.
+   */
+  pull(opts?: SyncOptions | undefined | null): Promise<SyncResult>
+  /**
+   * Checkpoints the write-ahead log.
+   *
+   * # Arguments
+   *
+   * * `opts` - Optional checkpoint mode and schema. By default, a passive
+   *   checkpoint of every attached database is run.
+   *
+   * # Returns
+   *
+   * A `CheckpointResult` instance. The frame counts are -1 if the database
+   * is not in WAL mode.
+   */
+  checkpoint(opts?: CheckpointOptions | undefined | null): Promise<CheckpointResult>
+  /**
+   * Sets a callback to be notified after every commit to a write-ahead
+   * log, or removes it when called without one.
+   *
+   * The callback is invoked asynchronously on the JavaScript thread with
+   * the name of the database committed to and the number of frames in its
+   * log. Setting a callback turns automatic checkpointing off, so that the
+   * application checkpoints on its own schedule; removing it turns
+   * automatic checkpointing back on.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   * * `callback` - The function to notify.
+   */
+  onWal(callback?: (...args: any[]) => any | undefined | null): void
+  /**
+   * Returns how the database is encrypted at rest.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   */
+  encryptionInfo(): EncryptionInfo
+  /**
+   * Changes the encryption key of the database.
+   *
+   * The database is re-encrypted in place, so it must be reopened with the
+   * new key afterwards. A plaintext database is encrypted, and an empty or
+   * missing key decrypts the database to plaintext.
+   *
+   * # Arguments
+   *
+   * * `new_key` - The new encryption key.
+   * * `opts` - Optional cipher to re-encrypt the database with.
+   */
+  rekey(newKey?: string | undefined | null, opts?: RekeyOptions | undefined | null): Promise<void>
+  /**
+   * Attaches a database file to the connection.
+   *
+   * # Arguments
+   *
+   * * `path` - The path of the database file, or a URI filename.
+   * * `schema` - The schema name to attach the database as.
+   * * `opts` - Optional encryption key and read-only mode.
+   */
+  attach(path: string, schema: string, opts?: AttachOptions | undefined | null): Promise<void>
+  /**
+   * Detaches a database attached to the connection.
+   *
+   * # Arguments
+   *
+   * * `schema` - The schema name the database is attached as.
+   */
+  detach(schema: string): Promise<void>
+  /**
+   * Applies the migrations not yet applied to the database, in order and
+   * in a single transaction. The applied migrations are recorded in a
+   * table with a checksum of their SQL, and migrating fails if they no
+   * longer match the first of the given migrations.
+   *
+   * # Arguments
+   *
+   * * `migrations` - The migrations, in the order they apply.
+   * * `opts` - Optional name of the table recording applied migrations.
+   *
+   * # Returns
+   *
+   * A `MigrateResult` instance.
+   */
+  migrate(migrations: Array<Migration>, opts?: MigrateOptions | undefined | null): Promise<MigrateResult>
+  /**
+   * Returns the tables, views, indexes, triggers and virtual tables of a
+   * database, with the columns, foreign keys and indexes of each table.
+   *
+   * # Arguments
+   *
+   * * `opts` - Optional name of the attached database to read the schema
+   *   of.
+   */
+  schema(opts?: SchemaOptions | undefined | null): Promise<DatabaseSchema>
+  /**
+   * Returns the databases of the connection: the main database, the
+   * temporary database if it has been created, and the attached ones.
+   */
+  databases(): Promise<Array<DatabaseEntry>>
   /**
    * Interrupts any ongoing database operations.
    *
@@ -151,6 +637,61 @@ export declare class Database {
    * * `env` - The environment.
    */
   interrupt(): void
+  /**
+   * Sets a callback to be notified as the connection executes queries, or
+   * removes it when called without one.
+   *
+   * The callback is invoked with the running count of virtual machine
+   * instructions executed by the connection, roughly every `period`
+   * instructions. It is called asynchronously on the JavaScript thread and
+   * cannot stop the query; use the `maxSteps` query option for that.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   * * `period` - Number of VM instructions between notifications.
+   * * `callback` - The function to notify.
+   */
+  progressHandler(period?: number | undefined | null, callback?: (...args: any[]) => any | undefined | null): void
+  /**
+   * Sets a callback that decides whether to retry an operation that finds
+   * the database locked, replacing the busy timeout and any previous busy
+   * handler, or removes the busy handler when called without one.
+   *
+   * The callback is invoked with the number of retries so far for the same
+   * lock and returns whether to retry. Without a busy handler, operations
+   * fail with `SQLITE_BUSY` as soon as the database is locked.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   * * `callback` - The function deciding whether to retry.
+   */
+  busyHandler(callback?: (...args: any[]) => any | undefined | null): void
+  /**
+   * Makes operations that find the database locked retry with exponential
+   * backoff and jitter, replacing the busy timeout and any busy handler.
+   *
+   * # Arguments
+   *
+   * * `env` - The environment.
+   * * `backoff` - The retry policy.
+   */
+  busyBackoff(backoff?: BusyBackoff | undefined | null): void
+  /** Returns statistics about the database connection. */
+  stats(): DatabaseStats
+  /**
+   * Prepares the database to be closed gracefully: stops accepting new
+   * operations, stops background syncs, letting a sync in progress
+   * complete, and waits for the operations in flight and the iterators
+   * not yet finished.
+   *
+   * # Arguments
+   *
+   * * `opts` - Optional timeout after which the operations still running
+   *   are interrupted instead of waited for.
+   */
+  drain(opts?: CloseOptions | undefined | null): object
   /** Closes the database connection. */
   close(): void
   /**
@@ -161,6 +702,17 @@ export declare class Database {
    * * `toggle` - Whether to use safe integers by default.
    */
   defaultSafeIntegers(toggle?: boolean | undefined | null): void
+  /**
+   * Sets the default query timeout of the connection. The timeout applies
+   * to operations started afterwards, including those of statements
+   * already prepared on the connection that have no timeout of their own.
+   *
+   * # Arguments
+   *
+   * * `timeout` - Maximum time in milliseconds that a query is allowed to
+   *   run, or 0 (or no value) for no timeout.
+   */
+  setDefaultQueryTimeout(timeout?: number | undefined | null): void
 }
 /** SQLite statement object. */
 export declare class Statement {
@@ -194,7 +746,59 @@ export declare class Statement {
   pluck(pluck?: boolean | undefined | null): this
   timing(timing?: boolean | undefined | null): this
   columns(): unknown[]
+  /**
+   * Binds parameters to the statement for all its executions, which then
+   * take no parameters.
+   *
+   * # Arguments
+   *
+   * * `params` - The parameters to bind to the statement.
+   */
+  bind(params?: unknown | undefined | null): this
+  /** Returns the SQL text the statement was prepared from. */
+  get source(): string
+  /** Returns whether the statement returns rows. */
+  get reader(): boolean
+  /**
+   * Returns whether the statement makes no direct change to the database.
+   * Statements of remote databases are not prepared locally, so they are
+   * taken to change it.
+   */
+  get readonly(): boolean
+  /**
+   * Returns whether the statement is executing, or has an iterator that is
+   * not finished.
+   */
+  get busy(): boolean
+  /**
+   * Returns the parameters of the statement, with their position, starting
+   * at 1, and their name, prefix included, unless they are anonymous.
+   */
+  parameters(): Array<StatementParameter>
+  /**
+   * Returns the SQL text of the statement with the given parameters
+   * expanded into it, or `None` for statements of remote databases, which
+   * are not prepared locally.
+   *
+   * The statement is prepared again to bind the parameters, as the one
+   * libSQL runs may be executing.
+   *
+   * # Arguments
+   *
+   * * `params` - The parameters to expand.
+   */
+  expandedSql(params?: unknown | undefined | null): string | null
   safeIntegers(toggle?: boolean | undefined | null): this
+  /**
+   * Sets the query timeout of the statement, overriding the default query
+   * timeout of the connection for operations started afterwards.
+   *
+   * # Arguments
+   *
+   * * `timeout` - Maximum time in milliseconds that a query is allowed to
+   *   run, 0 for no timeout, or no value to use the connection default.
+   */
+  setQueryTimeout(timeout?: number | undefined | null): this
   interrupt(): void
 }
 /** A raw iterator over rows. The JavaScript layer wraps this in a iterable. */
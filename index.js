@@ -310,12 +310,23 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { Database, connect, databasePrepareSync, databaseSyncSync, databaseExecSync, Statement, statementGetSync, statementRunSync, statementIterateSync, RowsIterator, iteratorNextSync, Record } = nativeBinding
+const { Database, queryTimeoutStats, connect, databasePrepareSync, databaseSyncSync, databasePushSync, databasePullSync, databaseCheckpointSync, databaseRekeySync, databaseAttachSync, databaseDetachSync, databaseMigrateSync, databaseSchemaSync, databaseDatabasesSync, databaseExecMultiSync, databaseExecSync, Statement, statementGetSync, statementRunSync, statementIterateSync, RowsIterator, iteratorNextSync, Record } = nativeBinding
 
 module.exports.Database = Database
+module.exports.queryTimeoutStats = queryTimeoutStats
 module.exports.connect = connect
 module.exports.databasePrepareSync = databasePrepareSync
 module.exports.databaseSyncSync = databaseSyncSync
+module.exports.databasePushSync = databasePushSync
+module.exports.databasePullSync = databasePullSync
+module.exports.databaseCheckpointSync = databaseCheckpointSync
+module.exports.databaseRekeySync = databaseRekeySync
+module.exports.databaseAttachSync = databaseAttachSync
+module.exports.databaseDetachSync = databaseDetachSync
+module.exports.databaseMigrateSync = databaseMigrateSync
+module.exports.databaseSchemaSync = databaseSchemaSync
+module.exports.databaseDatabasesSync = databaseDatabasesSync
+module.exports.databaseExecMultiSync = databaseExecMultiSync
 module.exports.databaseExecSync = databaseExecSync
 module.exports.Statement = Statement
 module.exports.statementGetSync = statementGetSync
@@ -1,6 +1,8 @@
 import test from "ava";
 import crypto from 'crypto';
 import fs from 'fs';
+import http from 'http';
+import net from 'net';
 
 
 test.beforeEach(async (t) => {
@@ -32,6 +34,95 @@ test.serial("Open in-memory database", async (t) => {
   t.is(db.memory, true);
 });
 
+test.serial("Open database in read-only mode", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path);
+  t.is(db.readonly, false);
+  await db.exec("CREATE TABLE t(x); INSERT INTO t VALUES (1)");
+  db.close();
+
+  for (const [name, options] of [[path, { readonly: true }], [`file:${path}?mode=ro`, {}]]) {
+    const [readonly] = await connect(name, options);
+    t.is(readonly.readonly, true);
+    t.deepEqual(await (await readonly.prepare("SELECT x FROM t")).all(), [{ x: 1 }]);
+    await t.throwsAsync(readonly.exec("INSERT INTO t VALUES (2)"), {
+      instanceOf: errorType,
+      code: "SQLITE_READONLY",
+    });
+    readonly.close();
+  }
+  fs.rmSync(path, { force: true });
+
+  await t.throwsAsync(connect(genDatabaseFilename(), { fileMustExist: true }), {
+    instanceOf: errorType,
+    code: "SQLITE_CANTOPEN",
+  });
+  await t.throwsAsync(connect(path, { readonly: true, syncUrl: "http://localhost:1" }), {
+    message: "The `readonly` and `fileMustExist` options are only supported for local databases",
+  });
+});
+
+test.serial("Database.close() finalizes statements and iterators", async (t) => {
+  const path = genDatabaseFilename();
+  const [db] = await connect(path);
+  t.is(db.name, path);
+  t.is(db.open, true);
+  await db.exec("CREATE TABLE t(x); INSERT INTO t VALUES (1), (2)");
+  const stmt = await db.prepare("SELECT x FROM t");
+  const iterator = await stmt.iterate();
+  const first = await iterator.next();
+
+  // The unfinished iterator holds a read lock that keeps other connections
+  // from writing until it is finalized.
+  const [other, errorType] = await connect(path);
+  await t.throwsAsync(other.exec("INSERT INTO t VALUES (3)"), { instanceOf: errorType, code: "SQLITE_BUSY" });
+  db.close();
+  t.is(db.open, false);
+  await other.exec("INSERT INTO t VALUES (3)");
+  other.close();
+
+  t.deepEqual(first.value, { x: 1 });
+  await t.throwsAsync(iterator.next(), { instanceOf: TypeError, message: "The database connection is not open" });
+  await t.throwsAsync(stmt.all(), { instanceOf: TypeError, message: "The database connection is not open" });
+  fs.rmSync(path, { force: true });
+});
+
+test.serial("Database.close() waits for operations in flight", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path);
+  await db.exec("CREATE TABLE t(x)");
+  const stmt = await db.prepare("INSERT INTO t VALUES (?)");
+  const inserts = [1, 2, 3].map((x) => stmt.run(x));
+  const iterator = await (await db.prepare("SELECT x FROM t")).iterate();
+
+  const closing = db.close({});
+  await t.throwsAsync(db.exec("INSERT INTO t VALUES (4)"), { instanceOf: TypeError, message: "The database connection is not open" });
+  await t.throwsAsync(stmt.run(4), { instanceOf: TypeError, message: "The database connection is not open" });
+  for (const insert of inserts) {
+    t.is((await insert).changes, 1);
+  }
+  t.is(db.open, true);
+  iterator.return();
+  await closing;
+  t.is(db.open, false);
+
+  // Queries still running after the timeout are interrupted.
+  const [reopened] = await connect(path);
+  t.deepEqual(await (await reopened.prepare("SELECT count(*) AS n FROM t")).get(), { n: 3 });
+  const loop = await reopened.prepare("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT sum(n) FROM infinite_loop;");
+  // Iterators left open do not keep the close waiting past the timeout.
+  const open = await (await reopened.prepare("SELECT x FROM t")).iterate();
+  t.deepEqual((await open.next()).value, { x: 1 });
+  const interrupted = t.throwsAsync(loop.get(), { instanceOf: errorType, code: "SQLITE_INTERRUPT" });
+  await t.throwsAsync(reopened.close({ timeout: -1 }), { message: "The `timeout` option must be a non-negative number" });
+  await t.throwsAsync(reopened.close({ timeout: NaN }), { message: "The `timeout` option must be a non-negative number" });
+  t.is(reopened.open, true);
+  await reopened.close({ timeout: 50 });
+  t.is(reopened.open, false);
+  await interrupted;
+  fs.rmSync(path, { force: true });
+});
+
 test.serial("Statement.prepare() error", async (t) => {
   const db = t.context.db;
 
@@ -139,6 +230,22 @@ test.serial("Statement.iterate()", async (t) => {
   }
 });
 
+test.serial("Statement.iterate() results can be read more than once", async (t) => {
+  const db = t.context.db;
+
+  const stmt = await db.prepare("SELECT id, CAST(name AS BLOB) AS name FROM users ORDER BY id");
+  const it = await stmt.iterate();
+  const next = await it.next();
+  const row = next.value;
+  t.deepEqual(row, { id: 1, name: Buffer.from("Alice") });
+  t.is(next.value, row);
+  t.is(next.done, false);
+  t.deepEqual((await it.next()).value, { id: 2, name: Buffer.from("Bob") });
+  const end = await it.next();
+  t.is(end.done, true);
+  t.is(end.value, null);
+});
+
 test.serial("Statement.iterate() with invalid bind parameter", async (t) => {
   const db = t.context.db;
 
@@ -204,6 +311,27 @@ test.serial("Statement.all() [statement safe integers]", async (t) => {
   t.deepEqual(await stmt.raw().all(), expected);
 });
 
+test.serial("Statement.all() returns blob columns as Buffers", async (t) => {
+  const db = t.context.db;
+
+  await db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (id INTEGER PRIMARY KEY, value BLOB);
+  `);
+
+  const first = crypto.randomBytes(64 * 1024);
+  const second = crypto.randomBytes(16);
+  const insertStmt = await db.prepare("INSERT INTO t (id, value) VALUES (?, ?)");
+  await insertStmt.run([1, first]);
+  await insertStmt.run([2, second]);
+
+  const rows = await (await db.prepare("SELECT value FROM t ORDER BY id")).all();
+  t.true(Buffer.isBuffer(rows[0].value));
+  t.deepEqual(rows[0].value, first);
+  t.deepEqual(rows[1].value, second);
+  t.deepEqual(await (await db.prepare("SELECT value FROM t WHERE id = 2")).raw().get(), [second]);
+});
+
 test.serial("Statement.raw() [failure]", async (t) => {
   const db = t.context.db;
   const stmt = await db.prepare("INSERT INTO users (id, name, email) VALUES (?, ?, ?)");
@@ -256,6 +384,115 @@ test.serial("Statement.columns()", async (t) => {
   ]);
 });
 
+test.serial("Statement.readonly, reader, busy and source", async (t) => {
+  const db = t.context.db;
+
+  const select = await db.prepare("SELECT * FROM users");
+  t.is(select.source, "SELECT * FROM users");
+  t.is(select.reader, true);
+  t.is(select.readonly, true);
+  t.is(select.busy, false);
+
+  const insert = await db.prepare("INSERT INTO users(name, email) VALUES ('x', 'y') RETURNING id");
+  t.is(insert.reader, true);
+  t.is(insert.readonly, false);
+
+  const del = await db.prepare("DELETE FROM users WHERE id = ?");
+  t.is(del.reader, false);
+  t.is(del.readonly, false);
+
+  // Statements prepared together each read their own mode.
+  const [comment, update] = await Promise.all([
+    db.prepare("  -- users\n SELECT 1; "),
+    db.prepare("UPDATE users SET name = name"),
+  ]);
+  t.is(comment.readonly, true);
+  t.is(update.readonly, false);
+
+  const it = await select.iterate();
+  await it.next();
+  t.is(select.busy, true);
+  await it.return();
+  t.is(select.busy, false);
+});
+
+test.serial("Statement.parameters() and Statement.expandedSql()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = await db.prepare("SELECT * FROM users WHERE id = ? AND name = :name");
+  t.deepEqual(stmt.parameters(), [{ index: 1 }, { index: 2, name: ":name" }]);
+  t.deepEqual((await db.prepare("SELECT 1")).parameters(), []);
+
+  t.is(
+    stmt.expandedSql(1, "O'Hara"),
+    "SELECT * FROM users WHERE id = 1.0 AND name = 'O''Hara'"
+  );
+  const named = await db.prepare("SELECT * FROM users WHERE name = :name AND email = $email");
+  t.is(
+    named.expandedSql({ name: "Alice", email: null }),
+    "SELECT * FROM users WHERE name = 'Alice' AND email = NULL"
+  );
+  t.is(named.expandedSql(), "SELECT * FROM users WHERE name = NULL AND email = NULL");
+});
+
+test.serial("Statement.bind()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = (await db.prepare("SELECT name FROM users WHERE id = ?")).bind(1);
+  t.is((await stmt.get()).name, "Alice");
+  t.deepEqual((await stmt.all()).map((row) => row.name), ["Alice"]);
+  await t.throwsAsync(async () => stmt.get(2), { instanceOf: TypeError });
+  t.throws(() => stmt.bind(2), { instanceOf: TypeError });
+
+  const named = (await db.prepare("SELECT name FROM users WHERE email = :email")).bind({
+    email: "bob@example.com",
+  });
+  t.is((await named.get()).name, "Bob");
+
+  // Parameters that look like query options are bound all the same.
+  const options = (await db.prepare("SELECT :queryTimeout AS value")).bind({ queryTimeout: 5 });
+  t.deepEqual(await options.get(), { value: 5 });
+});
+
+test.serial("Statement.get() [mixed positional and named]", async (t) => {
+  const db = t.context.db;
+
+  const anonymous = await db.prepare("SELECT ? AS a, :name AS name, ? AS b");
+  t.deepEqual(await anonymous.get(1, 2, { name: "x" }), { a: 1, name: "x", b: 2 });
+
+  const numbered = await db.prepare("SELECT ?2 AS b, @name AS name, ?1 AS a");
+  t.deepEqual(await numbered.get(1, { name: "x" }, 2), { b: 2, name: "x", a: 1 });
+
+  const lenient = await db.prepare("SELECT :a AS a, :b AS b");
+  t.deepEqual(await lenient.get({ a: 1, b: 2 }), { a: 1, b: 2 });
+  t.deepEqual(await lenient.get({ a: 1, c: 3 }), { a: 1, b: null });
+});
+
+test.serial("Statement.get() [strictParams]", async (t) => {
+  const [db] = await connect(":memory:", { strictParams: true });
+
+  const stmt = await db.prepare("SELECT :a AS a, $b AS b");
+  t.deepEqual(await stmt.get({ a: 1, b: 2 }), { a: 1, b: 2 });
+  t.deepEqual(await stmt.get({ a: 1, b: undefined }), { a: 1, b: null });
+  await t.throwsAsync(stmt.get({ a: 1 }), {
+    instanceOf: RangeError,
+    message: 'Missing named parameter "b"',
+  });
+  await t.throwsAsync(stmt.get({ a: 1, b: 2, c: 3 }), {
+    instanceOf: RangeError,
+    message: 'Unknown named parameter "c"',
+  });
+
+  const mixed = await db.prepare("SELECT ? AS a, :b AS b");
+  t.deepEqual(await mixed.get(1, { b: 2 }), { a: 1, b: 2 });
+  await t.throwsAsync(mixed.get(1, 3, { b: 2 }), {
+    instanceOf: RangeError,
+    message: 'Too many parameter values were provided',
+  });
+
+  db.close();
+});
+
 test.serial("Database.transaction()", async (t) => {
   const db = t.context.db;
 
@@ -328,6 +565,44 @@ test.serial("errors", async (t) => {
   t.is(noTableError.rawCode, 1)
 });
 
+test.serial("errors [structured details]", async (t) => {
+  const db = t.context.db;
+  await db.exec(`
+    DROP TABLE IF EXISTS parents;
+    DROP TABLE IF EXISTS children;
+    CREATE TABLE parents (id INTEGER PRIMARY KEY);
+    CREATE TABLE children (id INTEGER PRIMARY KEY, parent INTEGER NOT NULL REFERENCES parents (id));
+    PRAGMA foreign_keys = ON;
+  `);
+
+  const prepareError = await t.throwsAsync(async () => {
+    await db.prepare("SELECT * FROM parents WHERE nope = 1");
+  }, {
+    instanceOf: t.context.errorType,
+    message: "no such column: nope",
+    code: "SQLITE_ERROR"
+  });
+  t.is(prepareError.sql, "SELECT * FROM parents WHERE nope = 1");
+  t.is(prepareError.offset, 28);
+
+  const stmt = await db.prepare("INSERT INTO children (id, parent) VALUES (?, ?)");
+  const notNullError = await t.throwsAsync(() => stmt.run(1, null), {
+    instanceOf: t.context.errorType,
+    code: "SQLITE_CONSTRAINT_NOTNULL"
+  });
+  t.is(notNullError.table, "children");
+  t.is(notNullError.column, "parent");
+
+  const foreignKeyError = await t.throwsAsync(() => stmt.run(1, 42), {
+    instanceOf: t.context.errorType,
+    code: "SQLITE_CONSTRAINT_FOREIGNKEY"
+  });
+  t.is(foreignKeyError.rawCode, 787);
+  t.is(foreignKeyError.sql, "INSERT INTO children (id, parent) VALUES (?, ?)");
+  t.is(foreignKeyError.table, undefined);
+  await db.exec("PRAGMA foreign_keys = OFF");
+});
+
 test.serial("Database.prepare() after close()", async (t) => {
   const db = t.context.db;
   db.close();
@@ -384,6 +659,129 @@ test.serial("Statement.interrupt()", async (t) => {
   });
 });
 
+test.serial("Query options signal aborts a running statement", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT sum(n) FROM infinite_loop;");
+
+  for (const method of ["all", "get", "run"]) {
+    const controller = new AbortController();
+    const fut = stmt[method](undefined, { signal: controller.signal });
+    await new Promise(resolve => setTimeout(resolve, 100));
+    controller.abort();
+    const error = await t.throwsAsync(async () => {
+      await fut;
+    }, {
+      name: "AbortError",
+      code: "ABORT_ERR",
+    });
+    t.is(error.cause, controller.signal.reason);
+  }
+
+  // The statement remains usable once aborted.
+  const limited = await db.prepare("SELECT * FROM users WHERE id = ?");
+  const controller = new AbortController();
+  t.deepEqual(await limited.get(1, { signal: controller.signal }), { id: 1, name: "Alice", email: "alice@example.org" });
+  controller.abort();
+  t.deepEqual(await limited.get(2), { id: 2, name: "Bob", email: "bob@example.com" });
+});
+
+test.serial("Query options signal aborts an iteration in progress", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT n FROM infinite_loop;");
+  const controller = new AbortController();
+  const it = await stmt.iterate(undefined, { signal: controller.signal });
+  t.deepEqual((await it.next()).value, { n: 1 });
+
+  controller.abort();
+  await t.throwsAsync(async () => {
+    while (!(await it.next()).done) {}
+  }, {
+    name: "AbortError",
+  });
+});
+
+test.serial("Query options signal already aborted rejects without running", async (t) => {
+  const db = t.context.db;
+  const signal = AbortSignal.abort();
+
+  await t.throwsAsync(async () => {
+    await db.exec("INSERT INTO users (id, name, email) VALUES (3, 'Carol', 'carol@example.org')", { signal });
+  }, {
+    name: "AbortError",
+  });
+  await t.throwsAsync(async () => {
+    await db.run("INSERT INTO users (id, name, email) VALUES (3, 'Carol', 'carol@example.org')", { signal });
+  }, {
+    name: "AbortError",
+  });
+  t.is((await db.get("SELECT count(*) AS n FROM users")).n, 2);
+});
+
+test.serial("Query options signal must be an AbortSignal", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("SELECT :signal AS signal");
+  t.deepEqual(await stmt.get({ signal: "green" }), { signal: "green" });
+  t.deepEqual(await stmt.get({ signal: "red" }, { signal: new AbortController().signal }), { signal: "red" });
+});
+
+test.serial("Query options signal aborts Database.exec()", async (t) => {
+  const db = t.context.db;
+  const controller = new AbortController();
+  const fut = db.exec("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT count(*) FROM infinite_loop;", { signal: controller.signal });
+  await new Promise(resolve => setTimeout(resolve, 100));
+  controller.abort();
+  await t.throwsAsync(async () => {
+    await fut;
+  }, {
+    name: "AbortError",
+  });
+  t.is((await db.get("SELECT count(*) AS n FROM users")).n, 2);
+});
+
+test.serial("Query option maxSteps interrupts a statement over its step budget", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT sum(n) FROM infinite_loop;");
+
+  const error = await t.throwsAsync(async () => {
+    await stmt.get(undefined, { maxSteps: 10000 });
+  }, {
+    instanceOf: t.context.errorType,
+    message: "interrupted: query exceeded its budget of 10000 VM steps",
+    code: "SQLITE_MAX_STEPS",
+  });
+  t.is(error.rawCode, 9);
+  t.is(error.maxSteps, 10000);
+
+  // Other statements on the connection are unaffected.
+  t.deepEqual(await db.get("SELECT count(*) AS n FROM users", { maxSteps: 10000 }), { n: 2 });
+
+  // Remote databases have no progress handler to count steps with.
+  const [remote] = await connect("http://localhost:1");
+  await t.throwsAsync(remote.exec("SELECT 1", { maxSteps: 10000 }), {
+    message: "The `maxSteps` option is only supported for local databases",
+  });
+  remote.close();
+});
+
+test.serial("Database.progressHandler()", async (t) => {
+  const db = t.context.db;
+  const steps = [];
+  t.is(db.progressHandler(1000, (n) => steps.push(n)), db);
+
+  await db.get("WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r WHERE n < 100000) SELECT sum(n) FROM r");
+  await new Promise(resolve => setImmediate(resolve));
+  t.true(steps.length > 10);
+  for (let i = 1; i < steps.length; i++) {
+    t.true(steps[i] - steps[i - 1] >= 1000);
+  }
+
+  db.progressHandler(0);
+  const count = steps.length;
+  await db.get("WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r WHERE n < 100000) SELECT sum(n) FROM r");
+  await new Promise(resolve => setImmediate(resolve));
+  t.is(steps.length, count);
+});
+
 test.serial("Timeout option", async (t) => {
   const timeout = 1000;
   const path = genDatabaseFilename();
@@ -408,6 +806,327 @@ test.serial("Timeout option", async (t) => {
   fs.unlinkSync(path);
 });
 
+test.serial("Busy handler retries until the lock is released", async (t) => {
+  const path = genDatabaseFilename();
+  const [conn1] = await connect(path);
+  await conn1.exec("CREATE TABLE t(x)");
+  await conn1.exec("BEGIN IMMEDIATE");
+  const [conn2] = await connect(path, { timeout: 200 });
+  let calls = 0;
+  conn2.busyHandler((retries) => {
+    calls++;
+    return retries < 100_000;
+  });
+  setTimeout(() => conn1.exec("COMMIT"), 50);
+  await conn2.exec("INSERT INTO t VALUES (1)");
+  t.true(calls > 0);
+
+  await conn1.exec("BEGIN IMMEDIATE");
+  // Removing the handler restores the busy timeout.
+  conn2.busyHandler(null);
+  const start = Date.now();
+  await t.throwsAsync(conn2.exec("INSERT INTO t VALUES (2)"), { code: "SQLITE_BUSY" });
+  t.true(Date.now() - start >= 150);
+  await conn1.exec("COMMIT");
+
+  conn1.close();
+  conn2.close();
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.checkpoint() and onWal()", async (t) => {
+  const path = genDatabaseFilename();
+  const [db] = await connect(path);
+  await db.exec("PRAGMA journal_mode = WAL");
+  const commit = new Promise((resolve) => db.onWal((schema, pages) => resolve([schema, pages])));
+  await db.exec("CREATE TABLE t(x)");
+  const [schema, pages] = await commit;
+  t.is(schema, "main");
+  t.true(pages > 0);
+
+  const result = await db.checkpoint({ mode: "truncate" });
+  t.false(result.busy);
+  t.is(result.logFrames, 0);
+  await t.throwsAsync(db.checkpoint({ mode: "bogus" }), {
+    message: "Invalid checkpoint mode 'bogus'. Expected 'passive', 'full', 'restart' or 'truncate'.",
+  });
+  t.throws(() => db.onWal(42), { instanceOf: TypeError });
+
+  db.onWal(null);
+  db.close();
+  for (const suffix of ["", "-wal", "-shm"]) {
+    fs.rmSync(path + suffix, { force: true });
+  }
+});
+
+test.serial("Database.rekey() encrypts, changes the key and decrypts", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path);
+  await db.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('hello')");
+  const plaintext = () => fs.readFileSync(path).subarray(0, 15).toString() === "SQLite format 3";
+  t.true(plaintext());
+  t.deepEqual(db.encryptionInfo(), { encrypted: false });
+
+  await db.rekey("first key");
+  t.false(plaintext());
+  t.deepEqual(db.encryptionInfo(), { encrypted: true, cipher: "aes256cbc" });
+  await db.rekey("second key", { cipher: "aes256cbc" });
+  t.false(plaintext());
+  t.deepEqual(await (await db.prepare("SELECT x FROM t")).all(), [{ x: "hello" }]);
+  await t.throwsAsync(db.rekey("third key", { cipher: "rot13" }), {
+    instanceOf: errorType,
+    code: "SQLITE_INVALID_ENCRYPTION_CIPHER",
+  });
+
+  await db.rekey(null);
+  t.true(plaintext());
+  t.deepEqual(db.encryptionInfo(), { encrypted: false });
+  db.close();
+  await t.throwsAsync(db.rekey("key"), { code: "SQLITE_NOTOPEN" });
+
+  const [reopened] = await connect(path);
+  t.deepEqual(await (await reopened.prepare("SELECT x FROM t")).all(), [{ x: "hello" }]);
+  reopened.close();
+  fs.rmSync(path, { force: true });
+});
+
+test.serial("Database.rekey() changes the key of databases in WAL mode", async (t) => {
+  const path = genDatabaseFilename();
+  const [db] = await connect(path);
+  await db.exec("PRAGMA journal_mode = WAL; CREATE TABLE t(x); INSERT INTO t VALUES ('hello')");
+
+  await db.rekey("key");
+  t.deepEqual(db.encryptionInfo(), { encrypted: true, cipher: "aes256cbc" });
+  t.deepEqual(await (await db.prepare("PRAGMA journal_mode")).get(), { journal_mode: "wal" });
+  await db.exec("INSERT INTO t VALUES ('world')");
+  db.close();
+
+  const [reopened] = await connect(path, { encryptionKey: "key" });
+  t.deepEqual(await (await reopened.prepare("SELECT x FROM t")).all(), [{ x: "hello" }, { x: "world" }]);
+  reopened.close();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { force: true });
+  }
+});
+
+test.serial("Encrypted local databases reject the wrong key", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path, { encryptionKey: "right key" });
+  t.deepEqual(db.encryptionInfo(), { encrypted: true, cipher: "aes256cbc" });
+  await db.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('hello')");
+  db.close();
+
+  const [reopened] = await connect(path, { encryptionKey: "right key", encryptionCipher: "aes256cbc" });
+  t.deepEqual(await (await reopened.prepare("SELECT x FROM t")).all(), [{ x: "hello" }]);
+  reopened.close();
+  await t.throwsAsync(connect(path, { encryptionKey: "wrong key" }), {
+    instanceOf: errorType,
+    code: "SQLITE_NOTADB",
+    message: "Unable to decrypt the database with the `aes256cbc` cipher: the encryption key is wrong, or the database is not encrypted",
+  });
+  fs.rmSync(path, { force: true });
+});
+
+test.serial("Database.attach() and detach()", async (t) => {
+  const tenant = genDatabaseFilename();
+  const [tenantDb] = await connect(tenant);
+  await tenantDb.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('tenant')");
+  tenantDb.close();
+
+  const [db, errorType] = await connect(":memory:");
+  await db.attach(tenant, "tenant");
+  await db.attach(tenant, "snapshot", { readonly: true });
+  t.deepEqual(await db.databases(), [
+    { name: "main", file: "", readonly: false },
+    { name: "tenant", file: fs.realpathSync(tenant), readonly: false },
+    { name: "snapshot", file: fs.realpathSync(tenant), readonly: true },
+  ]);
+  await db.exec("INSERT INTO tenant.t VALUES ('written')");
+  t.deepEqual(await (await db.prepare("SELECT x FROM snapshot.t")).all(), [{ x: "tenant" }, { x: "written" }]);
+  await t.throwsAsync(db.exec("INSERT INTO snapshot.t VALUES ('rejected')"), {
+    instanceOf: errorType,
+    code: "SQLITE_READONLY",
+  });
+  await t.throwsAsync(db.attach(tenant, "tenant"), {
+    instanceOf: errorType,
+    message: "database tenant is already in use",
+  });
+
+  await db.detach("snapshot");
+  await db.detach("tenant");
+  t.deepEqual((await db.databases()).map((database) => database.name), ["main"]);
+  await t.throwsAsync(db.detach("tenant"), { instanceOf: errorType, message: "no such database: tenant" });
+  db.close();
+  fs.rmSync(tenant, { force: true });
+});
+
+test.serial("Database.attach() decrypts encrypted databases", async (t) => {
+  const tenant = genDatabaseFilename();
+  const [tenantDb] = await connect(tenant, { encryptionKey: "tenant key" });
+  await tenantDb.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('secret')");
+  tenantDb.close();
+
+  const [db, errorType] = await connect(":memory:");
+  await t.throwsAsync(db.attach(tenant, "tenant", { encryptionKey: "wrong key" }), {
+    instanceOf: errorType,
+    code: "SQLITE_NOTADB",
+  });
+  t.deepEqual((await db.databases()).map((database) => database.name), ["main"]);
+  await db.attach(tenant, "tenant", { encryptionKey: "tenant key", readonly: true });
+  t.deepEqual(await (await db.prepare("SELECT x FROM tenant.t")).all(), [{ x: "secret" }]);
+  db.close();
+  fs.rmSync(tenant, { force: true });
+});
+
+test.serial("Database.attach() attaches plaintext databases to encrypted ones", async (t) => {
+  const plain = genDatabaseFilename();
+  const [plainDb] = await connect(plain);
+  await plainDb.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('public')");
+  plainDb.close();
+
+  const path = genDatabaseFilename();
+  const [db] = await connect(path, { encryptionKey: "main key" });
+  await db.attach(plain, "plain");
+  t.deepEqual(await (await db.prepare("SELECT x FROM plain.t")).all(), [{ x: "public" }]);
+  db.close();
+  fs.rmSync(plain, { force: true });
+  fs.rmSync(path, { force: true });
+});
+
+test.serial("Database.attach() reads keys from URI filenames", async (t) => {
+  const tenant = genDatabaseFilename();
+  const [tenantDb] = await connect(tenant, { encryptionKey: "tenantkey" });
+  await tenantDb.exec("CREATE TABLE t(x); INSERT INTO t VALUES ('secret')");
+  tenantDb.close();
+
+  for (const options of [{}, { encryptionKey: "main key" }]) {
+    const path = genDatabaseFilename();
+    const [db] = await connect(path, options);
+    await db.attach(`file:${tenant}?cipher=aes256cbc&key=tenantkey`, "tenant");
+    t.deepEqual(await (await db.prepare("SELECT x FROM tenant.t")).all(), [{ x: "secret" }]);
+    db.close();
+    fs.rmSync(path, { force: true });
+  }
+  fs.rmSync(tenant, { force: true });
+});
+
+test.serial("Database.migrate()", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path);
+  const migrations = [
+    { id: "001_users", up: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)" },
+    { id: "002_emails", up: "ALTER TABLE users ADD COLUMN email TEXT" },
+  ];
+  t.deepEqual(await db.migrate(migrations), { applied: ["001_users", "002_emails"] });
+  t.deepEqual(await db.migrate(migrations), { applied: [] });
+  const recorded = await (await db.prepare("SELECT id FROM _migrations")).pluck().all();
+  t.deepEqual(recorded, ["001_users", "002_emails"]);
+
+  // A failing migration rolls back every migration of the run.
+  const failing = [
+    ...migrations,
+    { id: "003_posts", up: "CREATE TABLE posts (id INTEGER PRIMARY KEY)" },
+    { id: "004_broken", up: "INSERT INTO missing VALUES (1)" },
+  ];
+  const error = await t.throwsAsync(db.migrate(failing), { instanceOf: errorType, message: "no such table: missing" });
+  t.is(error.sql, "INSERT INTO missing VALUES (1)");
+  t.false(db.inTransaction);
+  t.deepEqual((await db.schema()).tables.map((table) => table.name), ["_migrations", "users"]);
+
+  const committing = [...migrations, { id: "003_posts", up: "CREATE TABLE posts (id INTEGER PRIMARY KEY); COMMIT;" }];
+  await t.throwsAsync(db.migrate(committing), {
+    message: "Migration '003_posts' must not control transactions, as migrations run in one, but it runs `COMMIT;`",
+  });
+  t.false(db.inTransaction);
+  t.deepEqual((await db.schema()).tables.map((table) => table.name), ["_migrations", "users"]);
+
+  const changed = [migrations[0], { id: "002_emails", up: "ALTER TABLE users ADD COLUMN mail TEXT" }];
+  await t.throwsAsync(db.migrate(changed), {
+    instanceOf: errorType,
+    code: "SQLITE_MIGRATION_DRIFT",
+    message: "Migration '002_emails' was changed after it was applied to the database",
+  });
+  await t.throwsAsync(db.migrate([migrations[0]]), {
+    code: "SQLITE_MIGRATION_DRIFT",
+    message: "Migration '002_emails' was applied to the database but is missing from the migrations",
+  });
+  await t.throwsAsync(db.migrate([migrations[0], migrations[0]]), {
+    message: "Migration ids must be unique, '001_users' is repeated",
+  });
+
+  const posts = [{ id: "001_posts", up: "CREATE TABLE posts (id INTEGER PRIMARY KEY)" }];
+  t.deepEqual(await db.migrate(posts, { table: "schema_versions" }), { applied: ["001_posts"] });
+  db.close();
+  fs.rmSync(path, { force: true });
+});
+
+test.serial("Database.schema()", async (t) => {
+  const [db] = await connect(":memory:");
+  await db.exec(`
+    CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+    CREATE TABLE books (
+      id INTEGER PRIMARY KEY,
+      author_id INTEGER REFERENCES authors(id) ON DELETE CASCADE,
+      title TEXT DEFAULT 'untitled',
+      slug TEXT AS (lower(title)) VIRTUAL
+    ) STRICT;
+    CREATE INDEX books_title ON books(title DESC) WHERE title IS NOT NULL;
+    CREATE VIEW titles AS SELECT title FROM books;
+    CREATE TRIGGER books_insert AFTER INSERT ON books BEGIN SELECT 1; END;
+    CREATE VIRTUAL TABLE search USING fts5(body);
+  `);
+  const schema = await db.schema();
+  t.deepEqual(schema.tables.map((table) => table.name), ["authors", "books"]);
+  const [authors, books] = schema.tables;
+  t.deepEqual(authors.indexes, [
+    { name: "sqlite_autoindex_authors_1", unique: true, origin: "u", partial: false, columns: [{ name: "name", descending: false, collation: "BINARY" }] },
+  ]);
+  t.true(books.strict);
+  t.false(books.withoutRowid);
+  t.deepEqual(books.columns, [
+    { name: "id", type: "INTEGER", notNull: false, primaryKey: 1, hidden: false },
+    { name: "author_id", type: "INTEGER", notNull: false, primaryKey: 0, hidden: false },
+    { name: "title", type: "TEXT", notNull: false, defaultValue: "'untitled'", primaryKey: 0, hidden: false },
+    { name: "slug", type: "TEXT", notNull: false, primaryKey: 0, hidden: false, generated: "virtual" },
+  ]);
+  t.deepEqual(books.foreignKeys, [
+    { table: "authors", from: ["author_id"], to: ["id"], onUpdate: "NO ACTION", onDelete: "CASCADE", match: "NONE" },
+  ]);
+  t.deepEqual(books.indexes, [
+    { name: "books_title", unique: false, origin: "c", partial: true, columns: [{ name: "title", descending: true, collation: "BINARY" }] },
+  ]);
+  t.deepEqual(schema.views.map((view) => [view.name, view.columns.map((column) => column.name)]), [["titles", ["title"]]]);
+  t.deepEqual(schema.virtualTables.map((table) => table.name), ["search"]);
+  t.deepEqual(schema.virtualTables[0].columns.map((column) => [column.name, column.hidden]), [
+    ["body", false],
+    ["search", true],
+    ["rank", true],
+  ]);
+  t.deepEqual(schema.indexes, [{ name: "books_title", table: "books", sql: "CREATE INDEX books_title ON books(title DESC) WHERE title IS NOT NULL" }]);
+  t.deepEqual(schema.triggers.map((trigger) => [trigger.name, trigger.table]), [["books_insert", "books"]]);
+
+  await db.exec("ATTACH DATABASE ':memory:' AS other; CREATE TABLE other.t(x)");
+  t.deepEqual((await db.schema({ schema: "other" })).tables.map((table) => table.name), ["t"]);
+  db.close();
+});
+
+test.serial("Encryption options are validated", async (t) => {
+  const [, errorType] = await connect(":memory:");
+  await t.throwsAsync(connect(genDatabaseFilename(), { encryptionCipher: "aes256cbc" }), {
+    message: "The `encryptionCipher` option requires an `encryptionKey`",
+  });
+  await t.throwsAsync(connect(genDatabaseFilename(), { encryptionKey: "key", encryptionCipher: "rot13" }), {
+    instanceOf: errorType,
+    code: "SQLITE_INVALID_ENCRYPTION_CIPHER",
+  });
+  await t.throwsAsync(connect(":memory:", { encryptionKey: "key" }), {
+    message: "Encryption is not supported for in-memory databases",
+  });
+  await t.throwsAsync(connect("http://localhost:1", { encryptionKey: "key" }), {
+    message: "Local encryption is not supported for remote databases, use the `remoteEncryptionKey` option",
+  });
+});
+
 test.serial("Query timeout option interrupts long-running query", async (t) => {
   const queryTimeout = 100;
   const [db, errorType] = await connect(":memory:", { defaultQueryTimeout: queryTimeout });
@@ -419,13 +1138,43 @@ test.serial("Query timeout option interrupts long-running query", async (t) => {
     await stmt.all();
   }, {
     instanceOf: errorType,
-    message: "interrupted",
-    code: "SQLITE_INTERRUPT",
+    message: "interrupted: query exceeded its 100 ms timeout",
+    code: "SQLITE_QUERY_TIMEOUT",
   });
 
   db.close();
 });
 
+test.serial("Query timeouts can be changed for already prepared statements", async (t) => {
+  const [db, errorType] = await connect(":memory:");
+  const stmt = await db.prepare(
+    "WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT * FROM infinite_loop;"
+  );
+
+  t.is(db.setDefaultQueryTimeout(100), db);
+  let error = await t.throwsAsync(stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 100);
+
+  t.is(stmt.setQueryTimeout(200), stmt);
+  error = await t.throwsAsync(stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 200);
+
+  error = await t.throwsAsync(stmt.all(undefined, { queryTimeout: 50 }), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 50);
+
+  stmt.setQueryTimeout();
+  db.setDefaultQueryTimeout(150);
+  error = await t.throwsAsync(stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 150);
+
+  // A fraction of a millisecond is a timeout, not a disabled one.
+  db.setDefaultQueryTimeout(0.5);
+  error = await t.throwsAsync(stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 1);
+
+  db.close();
+});
+
 test.serial("Query timeout option interrupts long-running Statement.get()", async (t) => {
   const [db, errorType] = await connect(":memory:", { defaultQueryTimeout: 100 });
   const stmt = await db.prepare(`
@@ -444,7 +1193,10 @@ test.serial("Query timeout option interrupts long-running Statement.get()", asyn
   t.true(error instanceof Error);
   t.true(error instanceof errorType);
   t.true(error.message.toLowerCase().includes("interrupt"));
-  t.is(error.code, "SQLITE_INTERRUPT");
+  t.is(error.code, "SQLITE_QUERY_TIMEOUT");
+  t.is(error.rawCode, 9);
+  t.is(error.queryTimeout, 100);
+  t.true(error.elapsed >= 100);
 
   db.close();
 });
@@ -490,8 +1242,8 @@ test.serial("Per-query timeout option interrupts long-running Statement.all()",
     await stmt.all(undefined, { queryTimeout: 100 });
   }, {
     instanceOf: errorType,
-    message: "interrupted",
-    code: "SQLITE_INTERRUPT",
+    message: "interrupted: query exceeded its 100 ms timeout",
+    code: "SQLITE_QUERY_TIMEOUT",
   });
 
   db.close();
@@ -515,7 +1267,10 @@ test.serial("Per-query timeout option interrupts long-running Statement.get()",
   t.true(error instanceof Error);
   t.true(error instanceof errorType);
   t.true(error.message.toLowerCase().includes("interrupt"));
-  t.is(error.code, "SQLITE_INTERRUPT");
+  t.is(error.code, "SQLITE_QUERY_TIMEOUT");
+  t.is(error.rawCode, 9);
+  t.is(error.queryTimeout, 100);
+  t.true(error.elapsed >= 100);
 
   db.close();
 });
@@ -565,7 +1320,7 @@ test.serial("Query timeout interrupts long-running queries under concurrency", a
     promises.push(
       db.prepare(sql).then((stmt) =>
         stmt.all(undefined, { queryTimeout: 100 }).catch((err) => {
-          if (err.code === "SQLITE_INTERRUPT") {
+          if (err.code === "SQLITE_QUERY_TIMEOUT") {
             interrupts++;
           } else {
             throw err;
@@ -589,6 +1344,216 @@ test.serial("Per-query timeout option is accepted by Database.exec()", async (t)
   db.close();
 });
 
+test.serial("Per-query timeout option is accepted by Database.prepare()", async (t) => {
+  const [db] = await connect(":memory:");
+  const stmt = await db.prepare("SELECT 1 AS x", { queryTimeout: 100 });
+  t.deepEqual(await stmt.get(), { x: 1 });
+
+  db.close();
+});
+
+test.serial("Sync timeout option aborts a hung sync", async (t) => {
+  // A primary that answers the protocol probe but never replies to sync requests.
+  const server = net.createServer((socket) => {
+    socket.on("data", (data) => {
+      if (data.toString().startsWith("GET /info")) {
+        socket.end("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+      }
+    });
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path, { syncUrl: `http://127.0.0.1:${server.address().port}` });
+
+  const start = Date.now();
+  const err = await t.throwsAsync(db.sync({ timeout: 100 }), {
+    instanceOf: errorType,
+    message: "sync timed out after 100 ms",
+    code: "SQLITE_SYNC_TIMEOUT",
+  });
+  t.is(err.rawCode, 9);
+  t.true(Date.now() - start >= 100);
+  t.deepEqual(await (await db.prepare("SELECT 1 AS x")).get(), { x: 1 });
+
+  db.close();
+  server.close();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { recursive: true, force: true });
+  }
+});
+
+test.serial("Sync reports progress from the start", async (t) => {
+  // A primary that answers the protocol probe but never replies to sync requests.
+  const server = net.createServer((socket) => {
+    socket.on("data", (data) => {
+      if (data.toString().startsWith("GET /info")) {
+        socket.end("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+      }
+    });
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const path = genDatabaseFilename();
+  const [db] = await connect(path, { syncUrl: `http://127.0.0.1:${server.address().port}` });
+
+  const progress = [];
+  await t.throwsAsync(db.sync({ timeout: 100, onProgress: (p) => progress.push(p) }), {
+    code: "SQLITE_SYNC_TIMEOUT",
+  });
+  t.is(progress.length, 1);
+  t.is(progress[0].framesSynced, 0);
+  t.is(progress[0].bytes, 0);
+  t.is(progress[0].generation, undefined);
+  await t.throwsAsync(db.sync({ onProgress: 42 }), { instanceOf: TypeError });
+
+  db.close();
+  server.close();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { recursive: true, force: true });
+  }
+});
+
+test.serial("Background sync status, pausing and errors", async (t) => {
+  // A primary that answers the protocol probe but drops sync requests.
+  const server = net.createServer((socket) => {
+    socket.on("data", (data) => {
+      if (data.toString().startsWith("GET /info")) {
+        socket.end("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+      } else {
+        socket.destroy();
+      }
+    });
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path, {
+    syncUrl: `http://127.0.0.1:${server.address().port}`,
+    syncPeriod: 0.05,
+  });
+
+  const err = await new Promise((resolve) => db.onSyncError(resolve));
+  t.true(err instanceof errorType);
+  let status = db.syncStatus();
+  t.true(status.lastError instanceof errorType);
+  t.is(status.lastSuccess, undefined);
+  t.is(status.framesSynced, 0);
+  t.false(status.paused);
+
+  t.is(db.pauseSync(), db);
+  await new Promise((resolve) => setTimeout(resolve, 10));
+  status = db.syncStatus();
+  t.true(status.paused);
+  t.is(status.nextSync, undefined);
+  t.is(db.resumeSync(), db);
+  t.false(db.syncStatus().paused);
+
+  db.close();
+  server.close();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { recursive: true, force: true });
+  }
+
+  const [local] = await connect(":memory:");
+  t.throws(() => local.pauseSync(), {
+    message: "Background sync is not enabled, set the `syncPeriod` option to enable it",
+  });
+  t.deepEqual(local.syncStatus(), { framesSynced: 0, paused: false });
+  local.close();
+});
+
+test.serial("Offline replicas push, pull and report pending changes", async (t) => {
+  // A primary speaking the v2 sync protocol that accepts pushes until told
+  // to report a conflict, and has no frames to pull.
+  let conflict = false;
+  const server = http.createServer((req, res) => {
+    req.resume();
+    req.on("end", () => {
+      const send = (status, body) => {
+        res.writeHead(status, { "content-type": "application/json" });
+        res.end(JSON.stringify(body));
+      };
+      if (req.url === "/info") {
+        return send(200, { current_generation: 1 });
+      }
+      if (req.url === "/export/1") {
+        res.writeHead(200);
+        return res.end();
+      }
+      if (req.method === "POST") {
+        const end = Number(req.url.split("/")[4]);
+        return send(200, conflict
+          ? { status: "conflict", generation: 1, max_frame_no: 42 }
+          : { status: "ok", generation: 1, max_frame_no: end - 1 });
+      }
+      send(400, { generation: 1 });
+    });
+  });
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path, {
+    syncUrl: `http://127.0.0.1:${server.address().port}`,
+    offline: true,
+  });
+
+  t.deepEqual(await db.pendingChanges(), { frames: 0, transactions: 0 });
+  await db.exec("CREATE TABLE t(x)");
+  await db.exec("INSERT INTO t VALUES (1); INSERT INTO t VALUES (2)");
+  const pending = await db.pendingChanges();
+  t.is(pending.transactions, 3);
+  await t.throwsAsync(db.pull(), { instanceOf: errorType, code: "SQLITE_SYNC_PENDING_CHANGES" });
+  t.deepEqual(await db.push(), pending);
+  t.deepEqual(await db.pendingChanges(), { frames: 0, transactions: 0 });
+  t.is((await db.pull()).generation, 1);
+
+  await db.exec("INSERT INTO t VALUES (3)");
+  conflict = true;
+  const err = await t.throwsAsync(db.push(), { instanceOf: errorType, code: "SQLITE_SYNC_CONFLICT" });
+  t.deepEqual(err.conflict, { kind: "frame", local: pending.frames + 1, remote: 42 });
+  t.is((await db.pendingChanges()).transactions, 1);
+  await t.throwsAsync(db.checkpoint({ mode: "truncate" }), {
+    message: "Checkpoints are not supported for offline replicas",
+  });
+  t.throws(() => db.onWal(() => {}), {
+    message: "WAL hooks are not supported for offline replicas",
+  });
+  await db.exec("INSERT INTO t VALUES (4)");
+  t.is((await db.pendingChanges()).transactions, 2);
+
+  db.close();
+  server.close();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { recursive: true, force: true });
+  }
+
+  const [local] = await connect(":memory:");
+  t.throws(() => local.pendingChanges(), {
+    message: "Pushing and pulling are only supported for offline replicas, set the `offline` option",
+  });
+  local.close();
+});
+
+test.serial("Database.stats() reports query timeout counters", async (t) => {
+  const [db] = await connect(":memory:");
+  const libsql = await import("libsql/promise");
+  t.deepEqual(db.stats().queryTimeouts, { registered: 0, fired: 0, reaped: 0, heapSize: 0, active: 0 });
+
+  await db.exec("SELECT 1", { queryTimeout: 100 });
+  const stmt = await db.prepare(
+    "WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT * FROM infinite_loop;"
+  );
+  await t.throwsAsync(stmt.all(undefined, { queryTimeout: 100 }), { code: "SQLITE_QUERY_TIMEOUT" });
+
+  const stats = db.stats().queryTimeouts;
+  t.is(stats.registered, 2);
+  t.is(stats.fired, 1);
+  t.is(stats.active, 0);
+
+  const global = libsql.queryTimeoutStats();
+  t.true(global.registered >= stats.registered);
+  t.true(global.fired >= stats.fired);
+
+  db.close();
+});
+
 test.serial("Concurrent writes over same connection", async (t) => {
   const db = t.context.db;
   await db.exec(`
@@ -741,8 +1706,45 @@ test.serial("Database.run() forwards queryOptions", async (t) => {
         { queryTimeout: 50 }
       );
     },
-    { instanceOf: t.context.errorType, code: "SQLITE_INTERRUPT" }
+    { instanceOf: t.context.errorType, code: "SQLITE_QUERY_TIMEOUT" }
+  );
+});
+
+test.serial("Database.execMulti() returns per-statement results", async (t) => {
+  const [db] = await connect(":memory:");
+
+  const script = `-- Set up
+CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);;
+INSERT INTO t (name) VALUES ('a'), ('b');
+SELECT name FROM t ORDER BY id`;
+  const results = await db.execMulti(script);
+  t.is(results.length, 3);
+  t.deepEqual(
+    results.map((r) => script.slice(r.start, r.end)),
+    results.map((r) => r.sql)
   );
+  t.is(results[0].sql, "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);");
+  t.deepEqual(results[0].columns, []);
+  t.is(results[1].changes, 2);
+  t.is(results[1].lastInsertRowid, 2);
+  t.deepEqual(results[1].rows, []);
+  t.deepEqual(results[2].columns, ["name"]);
+  t.deepEqual(results[2].rows, [{ name: "a" }, { name: "b" }]);
+  t.is(results[2].changes, 0);
+  db.close();
+});
+
+test.serial("Database.execMulti() stops at the first failing statement", async (t) => {
+  const [db] = await connect(":memory:");
+
+  const script = "CREATE TABLE t (x); INSERT INTO t VALUES (1); SELECT nope FROM t; INSERT INTO t VALUES (2);";
+  const error = await t.throwsAsync(db.execMulti(script));
+  t.is(error.code, "SQLITE_ERROR");
+  t.is(error.statementIndex, 2);
+  t.is(error.sql, script);
+  t.is(error.offset, script.indexOf("nope"));
+  t.deepEqual(await (await db.prepare("SELECT x FROM t")).all(), [{ x: 1 }]);
+  db.close();
 });
 
 test.serial("Database.batch() returns per-statement result sets", async (t) => {
@@ -1,6 +1,7 @@
 import test from "ava";
 import crypto from 'crypto';
 import fs from 'fs';
+import { Worker } from 'worker_threads';
 
 test.beforeEach(async (t) => {
   const [db, errorType, provider] = await connect();
@@ -32,6 +33,54 @@ test.serial("Open in-memory database", async (t) => {
   t.is(db.memory, true);
 });
 
+test.serial("Open database in read-only mode", async (t) => {
+  const path = genDatabaseFilename();
+  const [db, errorType] = await connect(path);
+  t.is(db.readonly, false);
+  db.exec("CREATE TABLE t(x); INSERT INTO t VALUES (1)");
+  db.close();
+
+  const [readonly] = await connect(path, { readonly: true, fileMustExist: true });
+  t.is(readonly.readonly, true);
+  t.deepEqual(readonly.prepare("SELECT x FROM t").all(), [{ x: 1 }]);
+  t.throws(() => readonly.exec("INSERT INTO t VALUES (2)"), {
+    instanceOf: errorType,
+    code: "SQLITE_READONLY",
+  });
+  readonly.close();
+  fs.rmSync(path, { force: true });
+
+  await t.throwsAsync(connect(genDatabaseFilename(), { fileMustExist: true }), {
+    instanceOf: errorType,
+    code: "SQLITE_CANTOPEN",
+  });
+  for (const memory of [":memory:", ""]) {
+    await t.throwsAsync(connect(memory, { readonly: true }), {
+      message: "In-memory/temporary databases cannot be readonly",
+    });
+  }
+  if (t.context.provider === "libsql") {
+    await t.throwsAsync(connect("file::memory:", { readonly: true }), {
+      message: "In-memory/temporary databases cannot be readonly",
+    });
+  }
+});
+
+test.serial("Database properties and close()", async (t) => {
+  const path = genDatabaseFilename();
+  const [db] = await connect(path);
+  t.is(db.name, path);
+  t.is(db.open, true);
+  t.is(db.inTransaction, false);
+  db.exec("CREATE TABLE t(x); INSERT INTO t VALUES (1)");
+  const stmt = db.prepare("SELECT x FROM t");
+
+  db.close();
+  t.is(db.open, false);
+  t.throws(() => stmt.all(), { instanceOf: TypeError, message: "The database connection is not open" });
+  fs.rmSync(path, { force: true });
+});
+
 test.serial("Statement.prepare() error", async (t) => {
   const db = t.context.db;
 
@@ -269,6 +318,28 @@ test.serial("Statement.run() with Float32Array bind parameter", async (t) => {
   t.deepEqual(selectStmt.raw().get()[0], Buffer.from(array.buffer));
 });
 
+test.serial("Statement.all() returns blob columns as Buffers", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (id INTEGER PRIMARY KEY, value BLOB);
+  `);
+
+  const first = crypto.randomBytes(64 * 1024);
+  const second = crypto.randomBytes(16);
+  const insertStmt = db.prepare("INSERT INTO t (id, value) VALUES (?, ?)");
+  insertStmt.run([1, first]);
+  insertStmt.run([2, second]);
+
+  const rows = db.prepare("SELECT value FROM t ORDER BY id").all();
+  t.true(Buffer.isBuffer(rows[0].value));
+  t.deepEqual(rows[0].value, first);
+  t.deepEqual(rows[1].value, second);
+  t.deepEqual(db.prepare("SELECT value FROM t WHERE id = 2").raw().get(), [second]);
+  t.deepEqual(db.prepare("SELECT value FROM t WHERE id = 1").pluck().get(), first);
+});
+
 test.serial("Statement.run() for vector feature with Float32Array bind parameter", async (t) => {
   if (t.context.provider === 'sqlite') {
     // skip this test for sqlite
@@ -336,6 +407,119 @@ test.serial("Statement.columns()", async (t) => {
   ]);
 });
 
+test.serial("Statement.readonly, reader, busy and source", async (t) => {
+  const db = t.context.db;
+
+  const select = db.prepare("SELECT * FROM users");
+  t.is(select.source, "SELECT * FROM users");
+  t.is(select.reader, true);
+  t.is(select.readonly, true);
+  t.is(select.busy, false);
+
+  const insert = db.prepare("INSERT INTO users(name, email) VALUES ('x', 'y') RETURNING id");
+  t.is(insert.reader, true);
+  t.is(insert.readonly, false);
+
+  const del = db.prepare("DELETE FROM users WHERE id = ?");
+  t.is(del.reader, false);
+  t.is(del.readonly, false);
+
+  const it = select.iterate();
+  it.next();
+  t.is(select.busy, true);
+  it.return();
+  t.is(select.busy, false);
+});
+
+test.serial("Statement.parameters() and Statement.expandedSql()", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT * FROM users WHERE id = ? AND name = :name");
+  t.deepEqual(stmt.parameters(), [{ index: 1 }, { index: 2, name: ":name" }]);
+  t.deepEqual((db.prepare("SELECT 1")).parameters(), []);
+
+  t.is(
+    stmt.expandedSql(1, "O'Hara"),
+    "SELECT * FROM users WHERE id = 1.0 AND name = 'O''Hara'"
+  );
+  const named = db.prepare("SELECT * FROM users WHERE name = :name AND email = $email");
+  t.is(
+    named.expandedSql({ name: "Alice", email: null }),
+    "SELECT * FROM users WHERE name = 'Alice' AND email = NULL"
+  );
+  t.is(named.expandedSql(), "SELECT * FROM users WHERE name = NULL AND email = NULL");
+});
+
+test.serial("Statement.bind()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT name FROM users WHERE id = ?").bind(1);
+  t.is((stmt.get()).name, "Alice");
+  t.deepEqual((stmt.all()).map((row) => row.name), ["Alice"]);
+  t.throws(() => stmt.get(2), { instanceOf: TypeError });
+  t.throws(() => stmt.bind(2), { instanceOf: TypeError });
+
+  const named = (db.prepare("SELECT name FROM users WHERE email = :email")).bind({
+    email: "bob@example.com",
+  });
+  t.is((named.get()).name, "Bob");
+
+  // Parameters that look like query options are bound all the same.
+  const options = db.prepare("SELECT :queryTimeout AS value").bind({ queryTimeout: 5 });
+  t.deepEqual(options.get(), { value: 5 });
+});
+
+test.serial("Statement.get() [mixed positional and named]", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+  const db = t.context.db;
+
+  const anonymous = db.prepare("SELECT ? AS a, :name AS name, ? AS b");
+  t.deepEqual(anonymous.get(1, 2, { name: "x" }), { a: 1, name: "x", b: 2 });
+
+  const numbered = db.prepare("SELECT ?2 AS b, @name AS name, ?1 AS a");
+  t.deepEqual(numbered.get(1, { name: "x" }, 2), { b: 2, name: "x", a: 1 });
+
+  const lenient = db.prepare("SELECT :a AS a, :b AS b");
+  t.deepEqual(lenient.get({ a: 1, b: 2 }), { a: 1, b: 2 });
+  t.deepEqual(lenient.get({ a: 1, c: 3 }), { a: 1, b: null });
+});
+
+test.serial("Statement.get() [strictParams]", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+  const [db] = await connect(":memory:", { strictParams: true });
+
+  const stmt = db.prepare("SELECT :a AS a, $b AS b");
+  t.deepEqual(stmt.get({ a: 1, b: 2 }), { a: 1, b: 2 });
+  t.deepEqual(stmt.get({ a: 1, b: undefined }), { a: 1, b: null });
+  t.throws(() => stmt.get({ a: 1 }), {
+    instanceOf: RangeError,
+    message: 'Missing named parameter "b"',
+  });
+  t.throws(() => stmt.get({ a: 1, b: 2, c: 3 }), {
+    instanceOf: RangeError,
+    message: 'Unknown named parameter "c"',
+  });
+
+  const mixed = db.prepare("SELECT ? AS a, :b AS b");
+  t.deepEqual(mixed.get(1, { b: 2 }), { a: 1, b: 2 });
+  t.throws(() => mixed.get(1, 3, { b: 2 }), {
+    instanceOf: RangeError,
+    message: 'Too many parameter values were provided',
+  });
+
+  db.close();
+});
+
 test.serial("Database.transaction()", async (t) => {
   const db = t.context.db;
 
@@ -421,6 +605,47 @@ test.serial("errors", async (t) => {
   }
 });
 
+test.serial("errors [structured details]", async (t) => {
+  if (t.context.provider !== "libsql") {
+    t.pass();
+    return;
+  }
+  const db = t.context.db;
+  db.exec("CREATE UNIQUE INDEX users_email ON users (email)");
+
+  const prepareError = t.throws(() => {
+    db.prepare("SELECT name FROM users WHERE nope = 1");
+  }, {
+    instanceOf: t.context.errorType,
+    message: "no such column: nope",
+    code: "SQLITE_ERROR"
+  });
+  t.is(prepareError.sql, "SELECT name FROM users WHERE nope = 1");
+  t.is(prepareError.offset, 29);
+
+  const sql = "INSERT INTO users (id, name, email) VALUES (?, ?, ?)";
+  const uniqueError = t.throws(() => {
+    db.prepare(sql).run(3, "Carol", "alice@example.org");
+  }, {
+    instanceOf: t.context.errorType,
+    code: "SQLITE_CONSTRAINT_UNIQUE"
+  });
+  t.is(uniqueError.rawCode, 2067);
+  t.is(uniqueError.sql, sql);
+  t.is(uniqueError.table, "users");
+  t.is(uniqueError.column, "email");
+  t.deepEqual(uniqueError.columns, ["email"]);
+
+  const primaryKeyError = t.throws(() => {
+    db.prepare(sql).run(1, "Carol", "carol@example.org");
+  }, {
+    instanceOf: t.context.errorType,
+    code: "SQLITE_CONSTRAINT_PRIMARYKEY"
+  });
+  t.is(primaryKeyError.table, "users");
+  t.is(primaryKeyError.column, "id");
+});
+
 test.serial("Database.prepare() after close()", async (t) => {
   const db = t.context.db;
   db.close();
@@ -467,6 +692,133 @@ test.serial("Timeout option", async (t) => {
   fs.unlinkSync(path);
 });
 
+test.serial("Busy handler decides whether to retry", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const path = genDatabaseFilename();
+  const [conn1] = await connect(path);
+  conn1.exec("CREATE TABLE t(x)");
+  conn1.exec("BEGIN IMMEDIATE");
+  const calls = [];
+  const [conn2] = await connect(path, {
+    busyHandler: (retries) => {
+      calls.push(retries);
+      return retries < 2;
+    },
+  });
+  t.throws(() => conn2.exec("INSERT INTO t VALUES (1)"), { code: "SQLITE_BUSY" });
+  t.deepEqual(calls, [0, 1, 2]);
+
+  conn2.busyHandler(() => {
+    throw new Error("boom");
+  });
+  t.throws(() => conn2.exec("INSERT INTO t VALUES (1)"), { message: "boom" });
+  conn2.busyHandler(() => conn2.prepare("SELECT 1").get());
+  t.throws(() => conn2.exec("INSERT INTO t VALUES (1)"), {
+    message: "The database cannot be used synchronously from a busy handler",
+  });
+
+  t.is(conn2.busyHandler({ maxRetries: 3, initialDelay: 1, maxDelay: 2 }), conn2);
+  t.throws(() => conn2.exec("INSERT INTO t VALUES (1)"), { code: "SQLITE_BUSY" });
+
+  conn1.exec("COMMIT");
+  conn2.exec("INSERT INTO t VALUES (1)");
+  conn1.close();
+  conn2.close();
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.checkpoint()", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const path = genDatabaseFilename();
+  const [db] = await connect(path);
+  t.deepEqual(db.checkpoint(), { busy: false, logFrames: -1, checkpointedFrames: -1 });
+  db.pragma("journal_mode = WAL");
+  db.exec("CREATE TABLE t(x); INSERT INTO t VALUES (1)");
+  const result = db.checkpoint({ mode: "full", schema: "main" });
+  t.false(result.busy);
+  t.true(result.logFrames > 0);
+  t.is(result.checkpointedFrames, result.logFrames);
+  t.throws(() => db.checkpoint({ schema: "missing" }), { code: "SQLITE_ERROR" });
+
+  db.close();
+  for (const suffix of ["", "-wal", "-shm"]) {
+    fs.rmSync(path + suffix, { force: true });
+  }
+});
+
+test.serial("Database.attach() and detach()", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const tenant = genDatabaseFilename();
+  const [db, errorType] = await connect(":memory:");
+  db.attach(tenant, "tenant");
+  db.exec("CREATE TABLE tenant.t(x); INSERT INTO tenant.t VALUES (1)");
+  t.deepEqual(db.databases().map((database) => database.name), ["main", "tenant"]);
+  t.deepEqual(db.prepare("SELECT x FROM tenant.t").all(), [{ x: 1 }]);
+  db.detach("tenant");
+  t.throws(() => db.prepare("SELECT x FROM tenant.t"), {
+    instanceOf: errorType,
+    message: "no such table: tenant.t",
+  });
+
+  db.attach(tenant, "snapshot", { readonly: true });
+  t.deepEqual(db.databases()[1], { name: "snapshot", file: fs.realpathSync(tenant), readonly: true });
+  t.throws(() => db.exec("INSERT INTO snapshot.t VALUES (2)"), {
+    instanceOf: errorType,
+    code: "SQLITE_READONLY",
+  });
+  db.close();
+  fs.rmSync(tenant, { force: true });
+});
+
+test.serial("Database.migrate()", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const [db, errorType] = await connect(":memory:");
+  const migrations = [{ id: "1", up: "CREATE TABLE t(x)" }];
+  t.deepEqual(db.migrate(migrations), { applied: ["1"] });
+  migrations.push({ id: "2", up: "INSERT INTO t VALUES (1)" });
+  t.deepEqual(db.migrate(migrations), { applied: ["2"] });
+  t.throws(() => db.migrate([{ id: "1", up: "CREATE TABLE t(y)" }]), {
+    instanceOf: errorType,
+    code: "SQLITE_MIGRATION_DRIFT",
+  });
+  t.deepEqual(db.prepare("SELECT x FROM t").all(), [{ x: 1 }]);
+  db.close();
+});
+
+test.serial("Database.schema()", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const [db] = await connect(":memory:");
+  db.exec("CREATE TABLE t(id INTEGER PRIMARY KEY, x TEXT NOT NULL); CREATE INDEX t_x ON t(x)");
+  const schema = db.schema();
+  t.deepEqual(schema.tables.map((table) => table.name), ["t"]);
+  t.deepEqual(schema.tables[0].columns.map((column) => [column.name, column.type, column.notNull]), [
+    ["id", "INTEGER", false],
+    ["x", "TEXT", true],
+  ]);
+  t.deepEqual(schema.indexes.map((index) => index.name), ["t_x"]);
+  db.close();
+});
+
 test.serial("Query timeout option interrupts long-running query", async (t) => {
   if (t.context.provider === "sqlite") {
     t.assert(true);
@@ -482,13 +834,35 @@ test.serial("Query timeout option interrupts long-running query", async (t) => {
     stmt.all();
   }, {
     instanceOf: errorType,
-    message: "interrupted",
-    code: "SQLITE_INTERRUPT",
+    message: "interrupted: query exceeded its 100 ms timeout",
+    code: "SQLITE_QUERY_TIMEOUT",
   });
 
   db.close();
 });
 
+test.serial("Query timeouts can be changed for already prepared statements", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+
+  const [db, errorType] = await connect(":memory:");
+  const stmt = db.prepare(
+    "WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT * FROM infinite_loop;"
+  );
+
+  db.setDefaultQueryTimeout(100);
+  let error = t.throws(() => stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 100);
+
+  stmt.setQueryTimeout(200);
+  error = t.throws(() => stmt.all(), { instanceOf: errorType, code: "SQLITE_QUERY_TIMEOUT" });
+  t.is(error.queryTimeout, 200);
+
+  db.close();
+});
+
 test.serial("Query timeout option interrupts long-running Statement.get()", async (t) => {
   if (t.context.provider === "sqlite") {
     t.assert(true);
@@ -512,8 +886,46 @@ test.serial("Query timeout option interrupts long-running Statement.get()", asyn
   t.true(error instanceof Error);
   t.true(error instanceof errorType);
   t.true(error.message.toLowerCase().includes("interrupt"));
-  t.is(error.code, "SQLITE_INTERRUPT");
+  t.is(error.code, "SQLITE_QUERY_TIMEOUT");
+  t.is(error.rawCode, 9);
+  t.is(error.queryTimeout, 100);
+  t.true(error.elapsed >= 100);
+
+  db.close();
+});
+
+test.serial("Query option maxSteps interrupts a statement over its step budget", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
 
+  const [db, errorType] = await connect(":memory:");
+  const stmt = db.prepare(`
+    WITH RECURSIVE numbers(value) AS (
+      SELECT 1
+      UNION ALL
+      SELECT value + 1 FROM numbers WHERE value < 1000000000
+    )
+    SELECT sum(value) FROM numbers;
+  `);
+
+  const error = t.throws(() => {
+    stmt.get(undefined, { maxSteps: 10000 });
+  });
+  t.true(error instanceof errorType);
+  t.is(error.code, "SQLITE_MAX_STEPS");
+  t.is(error.rawCode, 9);
+  t.is(error.maxSteps, 10000);
+
+  const small = db.prepare("SELECT 1 AS value");
+  t.deepEqual(small.get(undefined, { maxSteps: 10000 }), { value: 1 });
+  t.throws(() => {
+    db.exec("WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r) SELECT count(*) FROM r", { maxSteps: 10000 });
+  }, {
+    instanceOf: errorType,
+    code: "SQLITE_MAX_STEPS",
+  });
   db.close();
 });
 
@@ -544,8 +956,8 @@ test.serial("Per-query timeout option interrupts long-running Statement.all()",
     stmt.all(undefined, { queryTimeout: 100 });
   }, {
     instanceOf: errorType,
-    message: "interrupted",
-    code: "SQLITE_INTERRUPT",
+    message: "interrupted: query exceeded its 100 ms timeout",
+    code: "SQLITE_QUERY_TIMEOUT",
   });
 
   db.close();
@@ -574,7 +986,10 @@ test.serial("Per-query timeout option interrupts long-running Statement.get()",
   t.true(error instanceof Error);
   t.true(error instanceof errorType);
   t.true(error.message.toLowerCase().includes("interrupt"));
-  t.is(error.code, "SQLITE_INTERRUPT");
+  t.is(error.code, "SQLITE_QUERY_TIMEOUT");
+  t.is(error.rawCode, 9);
+  t.is(error.queryTimeout, 100);
+  t.true(error.elapsed >= 100);
 
   db.close();
 });
@@ -667,6 +1082,51 @@ test.serial("Statement.reader [DELETE RETURNING is true]", async (t) => {
   t.is(stmt.reader, true);
 });
 
+test.serial("Database.execMulti() returns per-statement results", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+  const [db] = await connect(":memory:");
+
+  const script = `-- Set up
+CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);;
+INSERT INTO t (name) VALUES ('a'), ('b');
+SELECT name FROM t ORDER BY id`;
+  const results = db.execMulti(script);
+  t.is(results.length, 3);
+  t.deepEqual(
+    results.map((r) => script.slice(r.start, r.end)),
+    results.map((r) => r.sql)
+  );
+  t.is(results[0].sql, "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT);");
+  t.deepEqual(results[0].columns, []);
+  t.is(results[1].changes, 2);
+  t.is(results[1].lastInsertRowid, 2);
+  t.deepEqual(results[1].rows, []);
+  t.deepEqual(results[2].columns, ["name"]);
+  t.deepEqual(results[2].rows, [{ name: "a" }, { name: "b" }]);
+  t.is(results[2].changes, 0);
+  db.close();
+});
+
+test.serial("Database.execMulti() stops at the first failing statement", async (t) => {
+  if (t.context.provider === "sqlite") {
+    t.assert(true);
+    return;
+  }
+  const [db] = await connect(":memory:");
+
+  const script = "CREATE TABLE t (x); INSERT INTO t VALUES (1); SELECT nope FROM t; INSERT INTO t VALUES (2);";
+  const error = t.throws(() => db.execMulti(script));
+  t.is(error.code, "SQLITE_ERROR");
+  t.is(error.statementIndex, 2);
+  t.is(error.sql, script);
+  t.is(error.offset, script.indexOf("nope"));
+  t.deepEqual(db.prepare("SELECT x FROM t").all(), [{ x: 1 }]);
+  db.close();
+});
+
 test.serial("Database.batch() returns per-statement result sets", async (t) => {
   if (t.context.provider !== "libsql") {
     t.pass();
@@ -759,6 +1219,67 @@ test.serial("Database.batch() rejects non-array argument", async (t) => {
   t.throws(() => db.batch("SELECT 1"), { instanceOf: TypeError });
 });
 
+test.serial("Database.progressHandler() coalesces notifications", async (t) => {
+  if (t.context.provider !== "libsql") {
+    t.pass();
+    return;
+  }
+  const db = t.context.db;
+  const steps = [];
+  db.progressHandler(1, (n) => steps.push(n));
+  // The JavaScript thread is busy while the query runs, so the notifications
+  // pile up into a single one reporting the last count.
+  db.prepare("WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r WHERE n < 100000) SELECT sum(n) FROM r").get();
+  await new Promise(resolve => setTimeout(resolve, 100));
+  t.is(steps.length, 1);
+  t.true(steps[0] > 100000);
+  db.progressHandler(0);
+});
+
+test.serial("Database.sync() progress callbacks can query the database", async (t) => {
+  if (t.context.provider !== "libsql") {
+    t.pass();
+    return;
+  }
+  // A primary that answers the protocol probe but never replies to sync
+  // requests. It runs on a worker, since sync() blocks this thread.
+  const server = new Worker(`
+    const net = require("net");
+    const { parentPort } = require("worker_threads");
+    const server = net.createServer((socket) => {
+      socket.on("data", (data) => {
+        if (data.toString().startsWith("GET /info")) {
+          socket.end("HTTP/1.1 404 Not Found\\r\\ncontent-length: 0\\r\\n\\r\\n");
+        }
+      });
+    });
+    server.listen(0, "127.0.0.1", () => parentPort.postMessage(server.address().port));
+  `, { eval: true });
+  const port = await new Promise((resolve) => server.once("message", resolve));
+  const path = genDatabaseFilename();
+  const [db] = await connect(path, { syncUrl: `http://127.0.0.1:${port}` });
+
+  const progress = [];
+  const start = Date.now();
+  let reportedAfter;
+  t.throws(() => db.sync({
+    timeout: 300,
+    onProgress: (p) => {
+      reportedAfter = Date.now() - start;
+      progress.push([p.framesSynced, db.prepare("SELECT 1 AS x").get()]);
+    },
+  }), { code: "SQLITE_SYNC_TIMEOUT" });
+  t.deepEqual(progress, [[0, { x: 1 }]]);
+  // Progress is reported while the sync runs, not once it has timed out.
+  t.true(reportedAfter < 200);
+
+  db.close();
+  await server.terminate();
+  for (const file of fs.readdirSync(".").filter((file) => file.startsWith(path))) {
+    fs.rmSync(file, { recursive: true, force: true });
+  }
+});
+
 const connect = async (path_opt, options = {}) => {
   const path = path_opt ?? "hello.db";
   const provider = process.env.PROVIDER;
@@ -1,6 +1,6 @@
 "use strict";
 
-const { Database: NativeDb, connect: nativeConnect } = require("./index.js");
+const { Database: NativeDb, connect: nativeConnect, queryTimeoutStats } = require("./index.js");
 const SqliteError = require("./sqlite-error.js");
 const { Authorization, Action } = require("./auth");
 
@@ -25,13 +25,19 @@ function convertError(err) {
       if (data && data.libsqlError) {
         if (data.code === "SQLITE_AUTH") {
           // For SQLITE_AUTH, preserve the JSON string for the test
-          return new SqliteError(err.message, data.code, data.rawCode);
+          return new SqliteError(err.message, data.code, data.rawCode, data);
         } else if (data.code === "SQLITE_NOTOPEN") {
           // Convert SQLITE_NOTOPEN to TypeError with expected message
           return new TypeError("The database connection is not open");
+        } else if (data.code === "SQLITE_RANGE") {
+          // Convert parameter binding errors to RangeError, as better-sqlite3 does
+          return new RangeError(data.message);
+        } else if (data.code === "SQLITE_MISUSE") {
+          // Convert misuse of bound parameters to TypeError, as better-sqlite3 does
+          return new TypeError(data.message);
         } else {
           // For all other errors, use the plain message string
-          return new SqliteError(data.message, data.code, data.rawCode);
+          return new SqliteError(data.message, data.code, data.rawCode, data);
         }
       }
     } catch (_) {
@@ -45,7 +51,73 @@ function isQueryOptions(value) {
   return value != null
     && typeof value === "object"
     && !Array.isArray(value)
-    && Object.prototype.hasOwnProperty.call(value, "queryTimeout");
+    && (Object.prototype.hasOwnProperty.call(value, "queryTimeout")
+      || Object.prototype.hasOwnProperty.call(value, "maxSteps")
+      // `signal` is also a valid parameter name, so only a signal counts.
+      || (Object.prototype.hasOwnProperty.call(value, "signal") && value.signal instanceof AbortSignal));
+}
+
+/**
+ * Error thrown when an operation is cancelled through an `AbortSignal`.
+ */
+class AbortError extends Error {
+  constructor(message = "The operation was aborted", options = undefined) {
+    super(message, options);
+    this.name = "AbortError";
+    this.code = "ABORT_ERR";
+  }
+}
+
+function abortError(signal) {
+  return new AbortError(undefined, { cause: signal.reason });
+}
+
+/**
+ * Converts an error raised by an operation that `signal` may have aborted:
+ * the interrupt caused by the abort becomes an `AbortError`.
+ */
+function convertAbortError(err, signal) {
+  err = convertError(err);
+  if (signal != null && signal.aborted && err.code === "SQLITE_INTERRUPT") {
+    return abortError(signal);
+  }
+  return err;
+}
+
+/**
+ * Calls `interrupt` when `signal` aborts, until the returned function is
+ * called. Throws an `AbortError` if the signal has already been aborted.
+ */
+function onAbort(signal, interrupt) {
+  if (signal == null) {
+    return () => {};
+  }
+  if (signal.aborted) {
+    throw abortError(signal);
+  }
+  signal.addEventListener("abort", interrupt, { once: true });
+  return () => signal.removeEventListener("abort", interrupt);
+}
+
+/**
+ * Runs `fn`, interrupting it with `interrupt` if `signal` aborts meanwhile.
+ */
+async function abortable(signal, interrupt, fn) {
+  const release = onAbort(signal, interrupt);
+  try {
+    return await fn();
+  } catch (err) {
+    throw convertAbortError(err, signal);
+  } finally {
+    release();
+  }
+}
+
+function joinBindParameters(bindParameters) {
+  if (bindParameters.length === 0) {
+    return undefined;
+  }
+  return bindParameters.length === 1 ? bindParameters[0] : bindParameters;
 }
 
 function splitBindParameters(bindParameters) {
@@ -61,18 +133,27 @@ function splitBindParameters(bindParameters) {
       queryOptions: bindParameters[bindParameters.length - 1],
     };
   }
-  return { params: bindParameters.length === 1 ? bindParameters[0] : bindParameters, queryOptions: undefined };
+  return { params: joinBindParameters(bindParameters), queryOptions: undefined };
 }
 
 /**
  * Creates a new database connection.
  *
  * @param {string} path - Path to the database file.
- * @param {NativeOptions} opts - Options.
+ * @param {NativeOptions & { busyHandler?: ((retries: number) => boolean) | object }} opts - Options.
  */
 const connect = async (path, opts) => {
-  const db = await nativeConnect(path, opts);
-  return new Database(db);
+  let nativeDb;
+  try {
+    nativeDb = await nativeConnect(path, opts);
+  } catch (err) {
+    throw convertError(err);
+  }
+  const db = new Database(nativeDb);
+  if (opts?.busyHandler != null) {
+    db.busyHandler(opts.busyHandler);
+  }
+  return db;
 };
 
 /**
@@ -88,45 +169,164 @@ class Database {
   constructor(db) {
     this.db = db;
     this.memory = this.db.memory
+    this.readonly = this.db.readonly
+    this.name = this.db.name
 
     /** @type boolean */
     this.inTransaction;
+    /** @type boolean */
+    this.open;
 
     Object.defineProperties(this, {
       inTransaction: {
         get() {
-          return db.inTransaction();
+          return db.inTransaction;
+        }
+      },
+      open: {
+        get() {
+          return db.open;
         }
       },
     });
   }
 
-  sync() {
+  /**
+   * Syncs the database with its remote primary.
+   *
+   * @param {{ timeout?: number, onProgress?: (progress: { framesSynced: number, bytes: number, generation?: number }) => void }} [opts] -
+   *   Sync options. If the sync takes longer than `timeout` milliseconds, it
+   *   is abandoned with a `SQLITE_SYNC_TIMEOUT` error. `onProgress` is called
+   *   when the sync starts and as it applies frames.
+   */
+  async sync(opts) {
+    const onProgress = opts?.onProgress;
+    if (onProgress != null && typeof onProgress !== "function") {
+      throw new TypeError("Expected onProgress to be a function");
+    }
+    const progress = onProgress == null ? null : (progress) => {
+      try {
+        onProgress(progress);
+      } catch (_) {
+        // A failing progress callback does not abort the sync.
+      }
+    };
     try {
-      return this.db.sync();
+      return await this.db.sync(opts, progress);
     } catch (err) {
       throw convertError(err);
     }
   }
 
-  syncUntil(replicationIndex) {
-    throw new Error("not implemented");
+  /**
+   * Returns the status of the syncs of the database.
+   *
+   * @returns {{ lastSuccess?: number, lastError?: Error, framesSynced: number, nextSync?: number, paused: boolean }}
+   *   Times are in milliseconds since the Unix epoch.
+   */
+  syncStatus() {
+    const status = this.db.syncStatus();
+    if (status.lastError !== undefined) {
+      status.lastError = convertError(new Error(status.lastError));
+    }
+    return status;
   }
 
   /**
-   * Prepares a SQL statement for execution.
+   * Pauses the background syncs enabled with the `syncPeriod` option.
+   */
+  pauseSync() {
+    this.db.pauseSync();
+    return this;
+  }
+
+  /**
+   * Resumes background syncs, starting with one right away.
+   */
+  resumeSync() {
+    this.db.resumeSync();
+    return this;
+  }
+
+  /**
+   * Sets a function to be notified of failed background syncs, or removes it
+   * with `null`.
    *
-   * @param {string} sql - The SQL statement string to prepare.
+   * @param {((err: Error) => void) | null} callback - A function that is
+   *   called with the error of every failed background sync.
    */
-  async prepare(sql) {
+  onSyncError(callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected first argument to be a function");
+    }
+    this.db.onSyncError(callback == null ? null : (message) => {
+      try {
+        callback(convertError(new Error(message)));
+      } catch (_) {
+        // A failing callback does not stop background syncs.
+      }
+    });
+    return this;
+  }
+
+  /**
+   * Pushes the local changes of an offline replica to its remote primary.
+   *
+   * @param {{ timeout?: number }} [opts] - Sync options.
+   * @returns {{ frames: number, transactions: number }} The changes pushed.
+   */
+  async push(opts) {
     try {
-      const stmt = await this.db.prepare(sql);
-      return new Statement(stmt);
+      return await this.db.push(opts);
     } catch (err) {
       throw convertError(err);
     }
   }
 
+  /**
+   * Pulls the changes of the remote primary into an offline replica, which
+   * must not have local changes waiting to be pushed.
+   *
+   * @param {{ timeout?: number }} [opts] - Sync options.
+   */
+  async pull(opts) {
+    try {
+      return await this.db.pull(opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the local changes of an offline replica that have not been
+   * pushed yet.
+   *
+   * @returns {{ frames: number, transactions: number }}
+   */
+  pendingChanges() {
+    try {
+      return this.db.pendingChanges();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  syncUntil(replicationIndex) {
+    throw new Error("not implemented");
+  }
+
+  /**
+   * Prepares a SQL statement for execution.
+   *
+   * @param {string} sql - The SQL statement string to prepare.
+   * @param {object} [queryOptions] - Optional per-call overrides, such as `queryTimeout`.
+   */
+  async prepare(sql, queryOptions) {
+    const signal = queryOptions?.signal;
+    const stmt = await abortable(signal, () => this.db.interrupt(), () => this.db.prepare(sql, queryOptions));
+    return new Statement(stmt);
+  }
+
   /**
    * Returns a function that executes the given function in a transaction.
    *
@@ -275,13 +475,28 @@ class Database {
    * Executes a SQL statement.
    *
    * @param {string} sql - The SQL statement string to execute.
+   * @param {{ queryTimeout?: number, signal?: AbortSignal }} [queryOptions] - Optional query options.
+   *   Aborting `signal` interrupts every statement running on the connection.
    */
   async exec(sql, queryOptions) {
-    try {
-      await this.db.exec(sql, queryOptions);
-    } catch (err) {
-      throw convertError(err);
-    }
+    const signal = queryOptions?.signal;
+    await abortable(signal, () => this.db.interrupt(), () => this.db.exec(sql, queryOptions));
+  }
+
+  /**
+   * Executes a SQL script statement by statement, returning one result object
+   * per statement.
+   *
+   * Execution stops at the first statement that fails. The error has the
+   * `statementIndex` of the statement and its `offset` in the script.
+   *
+   * @param {string} sql - The SQL script to execute.
+   * @param {object} [queryOptions] - Optional per-query overrides, for the whole script.
+   * @returns {Promise<Array<{ sql: string, start: number, end: number, columns: string[], rows: Array<Record<string, any>>, changes: number, lastInsertRowid: number }>>}
+   */
+  async execMulti(sql, queryOptions) {
+    const signal = queryOptions?.signal;
+    return await abortable(signal, () => this.db.interrupt(), () => this.db.execMulti(sql, queryOptions));
   }
 
   /**
@@ -324,7 +539,7 @@ class Database {
         const columnNames = cols.map((c) => c.name);
         const columnTypes = cols.map((c) => c.type ?? "");
 
-        if (columnNames.length > 0) {
+        if (stmt.reader) {
           // Reader statement: collect the returned rows.
           if (raw) {
             stmt.raw(true);
@@ -362,10 +577,219 @@ class Database {
     this.db.interrupt();
   }
 
+  /**
+   * Registers a callback notified with the connection's running count of
+   * virtual machine instructions, roughly every `period` instructions.
+   * Call without a callback to remove it.
+   *
+   * @param {number} period - Number of VM instructions between notifications.
+   * @param {(steps: number) => void} [callback] - The function to notify.
+   */
+  progressHandler(period, callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected second argument to be a function");
+    }
+    try {
+      this.db.progressHandler(period, callback);
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Sets how operations that find the database locked are retried, replacing
+   * the `timeout` option.
+   *
+   * @param {((retries: number) => boolean) | { maxRetries?: number, initialDelay?: number, maxDelay?: number } | null} handler -
+   *   A function that is called with the number of retries so far and returns
+   *   whether to retry, an exponential backoff policy, or `null` to restore
+   *   the busy timeout of the `timeout` option.
+   */
+  busyHandler(handler) {
+    if (handler != null && typeof handler !== "function" && typeof handler !== "object") {
+      throw new TypeError("Expected first argument to be a function or an object");
+    }
+    try {
+      if (typeof handler === "function") {
+        this.db.busyHandler(handler);
+      } else if (handler != null) {
+        this.db.busyBackoff(handler);
+      } else {
+        this.db.busyHandler(null);
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Checkpoints the write-ahead log.
+   *
+   * @param {Object} [opts] - Checkpoint options.
+   * @param {"passive" | "full" | "restart" | "truncate"} [opts.mode] - The checkpoint mode, `"passive"` by default.
+   * @param {string} [opts.schema] - The attached database to checkpoint, all of them by default.
+   * @returns {{ busy: boolean, logFrames: number, checkpointedFrames: number }}
+   */
+  async checkpoint(opts) {
+    try {
+      return await this.db.checkpoint(opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns how the database is encrypted at rest.
+   *
+   * @returns {{ encrypted: boolean, cipher?: string }}
+   */
+  encryptionInfo() {
+    try {
+      return this.db.encryptionInfo();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Changes the encryption key of the database, which must be reopened with
+   * the new key afterwards. A plaintext database is encrypted, and a `null`
+   * or empty key decrypts the database to plaintext.
+   *
+   * @param {string | null} newKey - The new encryption key.
+   * @param {Object} [opts] - Rekey options.
+   * @param {string} [opts.cipher] - The cipher to re-encrypt the database with.
+   */
+  async rekey(newKey, opts) {
+    try {
+      await this.db.rekey(newKey, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Attaches a database file to the connection under a schema name.
+   *
+   * @param {string} path - The path of the database file, or a URI filename.
+   * @param {string} schemaName - The schema name to attach the database as.
+   * @param {Object} [opts] - Attach options.
+   * @param {string} [opts.encryptionKey] - The encryption key of the attached database.
+   * @param {boolean} [opts.readonly] - Whether to attach the database read-only.
+   */
+  async attach(path, schemaName, opts) {
+    try {
+      await this.db.attach(path, schemaName, opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Detaches a database attached to the connection.
+   *
+   * @param {string} schemaName - The schema name the database is attached as.
+   */
+  async detach(schemaName) {
+    try {
+      await this.db.detach(schemaName);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Applies the migrations not yet applied to the database, in order and in a
+   * single transaction, and returns the ids of those applied. Fails if the
+   * migrations already applied were changed since. If an embedded replica
+   * then fails to sync the migrations, the error is returned as `syncError`.
+   *
+   * @param {Array<{id: string, up: string}>} migrations - The migrations, in the order they apply.
+   * @param {Object} [opts] - Migration options.
+   * @param {string} [opts.table] - The table recording the applied migrations, `_migrations` by default.
+   */
+  async migrate(migrations, opts) {
+    try {
+      const result = await this.db.migrate(migrations, opts);
+      if (result.syncError != null) {
+        result.syncError = convertError(new Error(result.syncError));
+      }
+      return result;
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the tables, views, indexes, triggers and virtual tables of the
+   * database, with the columns, foreign keys and indexes of each table.
+   *
+   * @param {Object} [opts] - Schema options.
+   * @param {string} [opts.schema] - The attached database to read the schema of, `main` by default.
+   */
+  async schema(opts) {
+    try {
+      return await this.db.schema(opts);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the databases of the connection, each with its schema name, the
+   * path of its file and whether it is read-only.
+   */
+  async databases() {
+    try {
+      return await this.db.databases();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Sets a function to be notified after every commit to a write-ahead log,
+   * which turns automatic checkpointing off, or removes it with `null`.
+   *
+   * @param {((schema: string, pages: number) => void) | null} callback - A function
+   *   that is called with the name of the database committed to and the number
+   *   of frames in its write-ahead log.
+   */
+  onWal(callback) {
+    if (callback != null && typeof callback !== "function") {
+      throw new TypeError("Expected first argument to be a function");
+    }
+    try {
+      this.db.onWal(callback);
+    } catch (err) {
+      throw convertError(err);
+    }
+    return this;
+  }
+
+  /**
+   * Returns query timeout counters for this connection.
+   */
+  stats() {
+    return this.db.stats();
+  }
+
   /**
    * Closes the database connection.
+   *
+   * @param {Object} [opts] - Waits for the operations in flight before closing.
+   * @param {number} [opts.timeout] - Milliseconds after which the operations still running are interrupted.
    */
-  close() {
+  async close(opts) {
+    if (opts != null) {
+      try {
+        await this.db.drain(opts);
+      } catch (err) {
+        throw convertError(err);
+      }
+    }
     this.db.close();
   }
 
@@ -387,6 +811,17 @@ class Database {
     return this;
   }
 
+  /**
+   * Sets the default query timeout for subsequent operations, including those
+   * of already prepared statements.
+   *
+   * @param {number} [timeout] - Maximum number of milliseconds a query may run, or 0 for no timeout.
+   */
+  setDefaultQueryTimeout(timeout) {
+    this.db.setDefaultQueryTimeout(timeout);
+    return this;
+  }
+
   unsafeMode(...args) {
     throw new Error("not implemented");
   }
@@ -433,20 +868,56 @@ class Statement {
     return this;
   }
 
+  /**
+   * Binds parameters to the statement for all its executions, which then take no bind parameters.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  bind(...bindParameters) {
+    try {
+      this.stmt.bind(joinBindParameters(bindParameters));
+      return this;
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * The SQL text the statement was prepared from.
+   */
+  get source() {
+    return this.stmt.source;
+  }
+
+  /**
+   * Whether the statement returns rows.
+   */
   get reader() {
-    return this.stmt.columns().length > 0;
+    return this.stmt.reader;
+  }
+
+  /**
+   * Whether the statement makes no direct change to the database. Always
+   * `false` for statements of remote databases.
+   */
+  get readonly() {
+    return this.stmt.readonly;
+  }
+
+  /**
+   * Whether the statement is executing, or has an iterator that is not finished.
+   */
+  get busy() {
+    return this.stmt.busy;
   }
 
   /**
    * Executes the SQL statement and returns an info object.
    */
   async run(...bindParameters) {
-    try {
-      const { params, queryOptions } = splitBindParameters(bindParameters);
-      return await this.stmt.run(params, queryOptions);
-    } catch (err) {
-      throw convertError(err);
-    }
+    const { params, queryOptions } = splitBindParameters(bindParameters);
+    const signal = queryOptions?.signal;
+    return await abortable(signal, () => this.stmt.interrupt(), () => this.stmt.run(params, queryOptions));
   }
 
   /**
@@ -455,12 +926,9 @@ class Statement {
    * @param bindParameters - The bind parameters for executing the statement.
    */
   async get(...bindParameters) {
-    try {
-      const { params, queryOptions } = splitBindParameters(bindParameters);
-      return await this.stmt.get(params, queryOptions);
-    } catch (err) {
-      throw convertError(err);
-    }
+    const { params, queryOptions } = splitBindParameters(bindParameters);
+    const signal = queryOptions?.signal;
+    return await abortable(signal, () => this.stmt.interrupt(), () => this.stmt.get(params, queryOptions));
   }
 
   /**
@@ -469,12 +937,16 @@ class Statement {
    * @param bindParameters - The bind parameters for executing the statement.
    */
   async iterate(...bindParameters) {
+    const { params, queryOptions } = splitBindParameters(bindParameters);
+    const signal = queryOptions?.signal;
+    // The signal stays armed until the iterator is exhausted or closed.
+    const release = onAbort(signal, () => this.stmt.interrupt());
     try {
-      const { params, queryOptions } = splitBindParameters(bindParameters);
       const it = await this.stmt.iterate(params, queryOptions);
-      return wrappedIter(it);
+      return signal != null ? abortableIter(it, signal, release) : wrappedIter(it);
     } catch (err) {
-      throw convertError(err);
+      release();
+      throw convertAbortError(err, signal);
     }
   }
 
@@ -515,7 +987,36 @@ class Statement {
    * Returns the columns in the result set returned by this prepared statement.
    */
   columns() {
-    return this.stmt.columns();
+    try {
+      return this.stmt.columns();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the parameters of the statement, with their position and name.
+   */
+  parameters() {
+    try {
+      return this.stmt.parameters();
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the SQL text of the statement with the bind parameters expanded
+   * into it, or `null` for statements of remote databases.
+   *
+   * @param bindParameters - The bind parameters to expand.
+   */
+  expandedSql(...bindParameters) {
+    try {
+      return this.stmt.expandedSql(joinBindParameters(bindParameters));
+    } catch (err) {
+      throw convertError(err);
+    }
   }
 
   /**
@@ -525,12 +1026,27 @@ class Statement {
     this.stmt.safeIntegers(toggle);
     return this;
   }
+
+  /**
+   * Sets the query timeout of the statement, overriding the database default.
+   *
+   * @param {number} [timeout] - Maximum number of milliseconds a query may run,
+   *   0 for no timeout, or `undefined` to use the database default.
+   */
+  setQueryTimeout(timeout) {
+    this.stmt.setQueryTimeout(timeout);
+    return this;
+  }
 }
 
 function wrappedIter(it) {
   return {
-    next() {
-      return it.next();
+    async next() {
+      try {
+        return await it.next();
+      } catch (err) {
+        throw convertError(err);
+      }
     },
     return(value) {
       if (typeof it.close === "function") {
@@ -547,13 +1063,40 @@ function wrappedIter(it) {
   };
 }
 
+function abortableIter(it, signal, release) {
+  const iter = wrappedIter(it);
+  return {
+    async next() {
+      try {
+        const next = await it.next();
+        if (next.done) {
+          release();
+        }
+        return next;
+      } catch (err) {
+        release();
+        throw convertAbortError(err, signal);
+      }
+    },
+    return(value) {
+      release();
+      return iter.return(value);
+    },
+    [Symbol.asyncIterator]() {
+      return this;
+    }
+  };
+}
+
 module.exports = {
+  AbortError,
   Action,
   Authorization,
   Database,
   SqliteError,
   Statement,
   connect,
+  queryTimeoutStats,
 };
 
 function normalizeBatchMode(mode) {
@@ -1,9 +1,9 @@
 'use strict';
 const descriptor = { value: 'SqliteError', writable: true, enumerable: false, configurable: true };
 
-function SqliteError(message, code, rawCode) {
+function SqliteError(message, code, rawCode, details) {
         if (new.target !== SqliteError) {
-                return new SqliteError(message, code);
+                return new SqliteError(message, code, rawCode, details);
         }
         if (typeof code !== 'string') {
                 throw new TypeError('Expected second argument to be a string');
@@ -14,6 +14,18 @@ function SqliteError(message, code, rawCode) {
         Error.captureStackTrace(this, SqliteError);
         this.code = code;
         this.rawCode = rawCode
+        if (details != null) {
+                if (details.sql !== undefined) this.sql = details.sql;
+                if (details.offset !== undefined) this.offset = details.offset;
+                if (details.table !== undefined) this.table = details.table;
+                if (details.column !== undefined) this.column = details.column;
+                if (details.columns !== undefined) this.columns = details.columns;
+                if (details.queryTimeout !== undefined) this.queryTimeout = details.queryTimeout;
+                if (details.elapsed !== undefined) this.elapsed = details.elapsed;
+                if (details.maxSteps !== undefined) this.maxSteps = details.maxSteps;
+                if (details.conflict !== undefined) this.conflict = details.conflict;
+                if (details.statementIndex !== undefined) this.statementIndex = details.statementIndex;
+        }
 }
 Object.setPrototypeOf(SqliteError, Error);
 Object.setPrototypeOf(SqliteError.prototype, Error.prototype);
@@ -0,0 +1,132 @@
+//! Attaching databases to a connection.
+//!
+//! SQLite attaches a database file under a schema name with the `ATTACH`
+//! statement. libSQL builds SQLite with URI filenames enabled, so a database
+//! can be attached read-only with the `mode=ro` parameter of a URI filename,
+//! whatever flags the connection was opened with. An encryption key is passed
+//! in the `KEY` clause of the statement, which SQLite3 Multiple Ciphers uses
+//! to decrypt the attached database and plain SQLite ignores.
+//!
+//! Without the clause, SQLite3 Multiple Ciphers reads the key and cipher from
+//! the parameters of a URI filename, or else decrypts the attached database
+//! with the key of the main database. A clause, even with an empty key, makes
+//! it skip the URI parameters. So a plaintext database is attached to an
+//! encrypted one with an empty key, unless its URI filename sets the key or
+//! the cipher itself.
+
+/// The URI filename parameters SQLite3 Multiple Ciphers configures the
+/// encryption of a database with.
+const KEY_PARAMETERS: [&str; 3] = ["cipher", "key", "hexkey"];
+
+/// Attaches the database at `path` to `conn` as `schema`, decrypting it with
+/// `key` if given. Otherwise, a database attached to an encrypted main
+/// database is taken to be plaintext, unless its URI filename says how it is
+/// encrypted.
+pub async fn attach(
+    conn: &libsql::Connection,
+    path: &str,
+    schema: &str,
+    key: Option<&str>,
+    main_encrypted: bool,
+) -> libsql::Result<()> {
+    let key = key.or((main_encrypted && !has_key_parameters(path)).then_some(""));
+    match key {
+        Some(key) => {
+            conn.execute(
+                "ATTACH DATABASE ?1 AS ?2 KEY ?3",
+                libsql::params![path, schema, key],
+            )
+            .await?
+        }
+        None => {
+            conn.execute("ATTACH DATABASE ?1 AS ?2", libsql::params![path, schema])
+                .await?
+        }
+    };
+    Ok(())
+}
+
+/// Whether `path` is a URI filename with parameters that configure its
+/// encryption.
+fn has_key_parameters(path: &str) -> bool {
+    let Some(uri) = path.strip_prefix("file:") else {
+        return false;
+    };
+    let query = uri.split_once('?').map_or("", |(_, query)| query);
+    let query = query.split_once('#').map_or(query, |(query, _)| query);
+    query.split('&').any(|parameter| {
+        let name = parameter
+            .split_once('=')
+            .map_or(parameter, |(name, _)| name);
+        KEY_PARAMETERS.contains(&name)
+    })
+}
+
+/// Detaches the database attached to `conn` as `schema`.
+pub async fn detach(conn: &libsql::Connection, schema: &str) -> libsql::Result<()> {
+    conn.execute("DETACH DATABASE ?1", libsql::params![schema])
+        .await?;
+    Ok(())
+}
+
+/// Returns the schema names of the databases of `conn`, starting with `main`,
+/// along with the path of their file, which is empty for in-memory and
+/// temporary databases.
+pub async fn list(conn: &libsql::Connection) -> libsql::Result<Vec<(String, String)>> {
+    let mut rows = conn.query("PRAGMA database_list", ()).await?;
+    let mut databases = Vec::new();
+    while let Some(row) = rows.next().await? {
+        databases.push((row.get(1)?, row.get(2)?));
+    }
+    Ok(databases)
+}
+
+/// Returns the URI filename that opens the database at `path` read-only.
+pub fn readonly_uri(path: &str) -> String {
+    if path.starts_with("file:") {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        return format!("{path}{separator}mode=ro");
+    }
+    let mut uri = String::from("file:");
+    for c in path.chars() {
+        // Only these characters have a meaning in the path of a URI filename.
+        match c {
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            c => uri.push(c),
+        }
+    }
+    uri.push_str("?mode=ro");
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_parameters_are_found_in_uri_filenames() {
+        assert!(has_key_parameters("file:tenant.db?key=secret"));
+        assert!(has_key_parameters("file:tenant.db?mode=ro&cipher=chacha20"));
+        assert!(has_key_parameters("file:tenant.db?hexkey=00ff&mode=ro"));
+        assert!(!has_key_parameters("file:tenant.db?mode=ro"));
+        assert!(!has_key_parameters("file:tenant.db#key=secret"));
+        assert!(!has_key_parameters("file:tenant.db?monkey=1"));
+        assert!(!has_key_parameters("tenant.db?key=secret"));
+    }
+
+    #[test]
+    fn readonly_uris_escape_paths() {
+        assert_eq!(readonly_uri("tenant.db"), "file:tenant.db?mode=ro");
+        assert_eq!(
+            readonly_uri("/data/a?b#c%d.db"),
+            "file:/data/a%3fb%23c%25d.db?mode=ro"
+        );
+        assert_eq!(readonly_uri("file:tenant.db"), "file:tenant.db?mode=ro");
+        assert_eq!(
+            readonly_uri("file:tenant.db?cache=shared"),
+            "file:tenant.db?cache=shared&mode=ro"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Status and control of embedded replica syncs.
+//!
+//! With the `syncPeriod` option, an embedded replica is synced periodically by
+//! a task on the runtime. Rather than leaving this to libSQL, which gives no
+//! visibility into it, we run the task ourselves so that the outcome of every
+//! sync is recorded, syncing can be paused while it is undesirable (on
+//! metered connections, for example), and failures can be reported to the
+//! application as they happen.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// A function notified of the error message of every failed background sync.
+pub type ErrorCallback = Box<dyn Fn(&str) + Send + Sync>;
+
+/// The outcome of a sync: the number of frames synced, or an error message.
+pub type Outcome = std::result::Result<u64, String>;
+
+/// What is known about the syncs of a replica.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Status {
+    /// When the last successful sync completed.
+    pub last_success: Option<SystemTime>,
+    /// The error of the last sync, unless it succeeded.
+    pub last_error: Option<String>,
+    /// The number of frames synced so far.
+    pub frames_synced: u64,
+    /// When the next background sync is due, if one is scheduled.
+    pub next_sync: Option<SystemTime>,
+    /// Whether background syncs are paused.
+    pub paused: bool,
+}
+
+/// Tracks the syncs of a replica and runs its background syncs, if any.
+#[derive(Default)]
+pub struct BackgroundSync {
+    status: Mutex<Status>,
+    // Wakes the background task when it is paused or resumed.
+    wake: Notify,
+    on_error: Mutex<Option<ErrorCallback>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    // Whether the background task should stop once done with its sync.
+    finishing: AtomicBool,
+}
+
+impl BackgroundSync {
+    /// Starts syncing every `period` by calling `sync`, which returns `None`
+    /// once there is nothing left to sync, until stopped. Must be called from
+    /// within a runtime.
+    pub fn start<F, Fut>(self: &Arc<Self>, period: Duration, sync: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Option<Outcome>> + Send + 'static,
+    {
+        let task = tokio::spawn(self.clone().run(period, sync));
+        *self.task.lock().unwrap() = Some(task);
+    }
+
+    async fn run<F, Fut>(self: Arc<Self>, period: Duration, sync: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Option<Outcome>>,
+    {
+        let mut due = false;
+        loop {
+            if self.finishing.load(Ordering::SeqCst) {
+                break;
+            }
+            if !due {
+                let paused = {
+                    let mut status = self.status.lock().unwrap();
+                    status.next_sync = (!status.paused).then(|| SystemTime::now() + period);
+                    status.paused
+                };
+                if paused {
+                    // Catch up as soon as syncing is resumed.
+                    self.wake.notified().await;
+                    due = !self.status.lock().unwrap().paused;
+                    continue;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(period) => {}
+                    _ = self.wake.notified() => continue,
+                }
+            }
+            due = false;
+            let Some(outcome) = sync().await else {
+                break;
+            };
+            self.record(&outcome);
+            if let Err(message) = &outcome {
+                if let Some(on_error) = &*self.on_error.lock().unwrap() {
+                    on_error(message);
+                }
+            }
+        }
+        self.status.lock().unwrap().next_sync = None;
+    }
+
+    /// Stops background syncs for good.
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.status.lock().unwrap().next_sync = None;
+    }
+
+    /// Stops background syncs for good, letting a sync in progress complete
+    /// unless it takes longer than `timeout`.
+    pub async fn finish(&self, timeout: Option<Duration>) {
+        let Some(mut task) = self.task.lock().unwrap().take() else {
+            return;
+        };
+        self.finishing.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+        match timeout {
+            Some(timeout) => {
+                if tokio::time::timeout(timeout, &mut task).await.is_err() {
+                    task.abort();
+                }
+            }
+            None => {
+                let _ = task.await;
+            }
+        }
+        self.status.lock().unwrap().next_sync = None;
+    }
+
+    /// Whether background syncs have been started.
+    pub fn is_periodic(&self) -> bool {
+        self.task.lock().unwrap().is_some()
+    }
+
+    /// Records the outcome of a sync.
+    pub fn record(&self, outcome: &Outcome) {
+        let mut status = self.status.lock().unwrap();
+        match outcome {
+            Ok(frames) => {
+                status.last_success = Some(SystemTime::now());
+                status.last_error = None;
+                status.frames_synced += frames;
+            }
+            Err(message) => status.last_error = Some(message.clone()),
+        }
+    }
+
+    /// Returns what is known about the syncs so far.
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Pauses or resumes background syncs. A sync in progress completes.
+    pub fn set_paused(&self, paused: bool) {
+        let mut status = self.status.lock().unwrap();
+        if status.paused != paused {
+            status.paused = paused;
+            self.wake.notify_one();
+        }
+    }
+
+    /// Sets the function notified of failed background syncs, or removes it.
+    pub fn set_error_callback(&self, callback: Option<ErrorCallback>) {
+        *self.on_error.lock().unwrap() = callback;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn syncs_periodically_until_paused() {
+        let background = Arc::new(BackgroundSync::default());
+        let calls = Arc::new(AtomicU32::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        {
+            let errors = errors.clone();
+            background.set_error_callback(Some(Box::new(move |message| {
+                errors.lock().unwrap().push(message.to_string());
+            })));
+        }
+        {
+            let calls = calls.clone();
+            background.start(Duration::from_secs(1), move || {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Some(match call {
+                        1 => Err("unreachable".to_string()),
+                        _ => Ok(2),
+                    })
+                }
+            });
+        }
+        assert!(background.is_periodic());
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let status = background.status();
+        assert_eq!(status.last_error.as_deref(), Some("unreachable"));
+        assert_eq!(status.frames_synced, 2);
+        assert!(status.last_success.is_some());
+        assert!(status.next_sync.is_some());
+        assert_eq!(*errors.lock().unwrap(), vec!["unreachable"]);
+
+        background.set_paused(true);
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let status = background.status();
+        assert!(status.paused);
+        assert_eq!(status.next_sync, None);
+
+        background.set_paused(false);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let status = background.status();
+        assert_eq!(status.last_error, None);
+        assert_eq!(status.frames_synced, 4);
+
+        background.stop();
+        assert!(!background.is_periodic());
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finishing_lets_the_sync_in_progress_complete() {
+        let background = Arc::new(BackgroundSync::default());
+        let calls = Arc::new(AtomicU32::new(0));
+        {
+            let calls = calls.clone();
+            background.start(Duration::from_secs(1), move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    Some(Ok(1))
+                }
+            });
+        }
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        background.finish(None).await;
+        assert!(!background.is_periodic());
+        let status = background.status();
+        assert_eq!(status.frames_synced, 1);
+        assert_eq!(status.next_sync, None);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
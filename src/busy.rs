@@ -0,0 +1,192 @@
+//! Busy handling for local connections, built on `sqlite3_busy_handler`.
+//!
+//! When a connection cannot take a lock because another connection holds it,
+//! SQLite invokes the busy handler of the connection with the number of times
+//! it has already been invoked for the same lock. The handler either asks
+//! SQLite to try again or gives up, in which case the operation fails with
+//! `SQLITE_BUSY`.
+//!
+//! Besides the fixed busy timeout set with `Options.timeout`, a connection can
+//! retry with exponential backoff and jitter, which spreads out writers that
+//! contend for the same database file, or leave the decision to a callback.
+
+use crate::raw::RawConnection;
+use std::{
+    collections::hash_map::RandomState,
+    ffi::c_void,
+    hash::{BuildHasher, Hasher},
+    os::raw::c_int,
+    sync::Arc,
+    time::Duration,
+};
+
+/// A function deciding, given the number of retries so far, whether to retry.
+pub type BusyCallback = Box<dyn Fn(u32) -> bool + Send + Sync>;
+
+/// How a connection handles a lock held by another connection.
+pub enum BusyHandler {
+    /// Retry with exponential backoff and jitter.
+    Backoff(Backoff),
+    /// Ask a callback whether to retry.
+    Callback(BusyCallback),
+}
+
+/// An exponential backoff retry policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Number of retries before giving up.
+    pub max_retries: u32,
+    /// Upper bound of the wait before the first retry.
+    pub initial_delay: Duration,
+    /// Upper bound of the wait before any retry.
+    pub max_delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns how long to wait before retry number `retries`, counting from
+    /// zero, given a `jitter` in `[0, 1)`.
+    ///
+    /// The bound on the wait doubles with every retry, up to `max_delay`. The
+    /// wait itself is picked from the upper half of the bound, so that waits
+    /// keep growing while connections contending for the same lock spread
+    /// out.
+    pub fn delay(&self, retries: u32, jitter: f64) -> Duration {
+        let factor = 1u32.checked_shl(retries).unwrap_or(u32::MAX);
+        let bound = self
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay);
+        bound / 2 + bound.mul_f64(jitter.clamp(0.0, 1.0) / 2.0)
+    }
+
+    fn retry(&self, retries: u32) -> bool {
+        if retries >= self.max_retries {
+            return false;
+        }
+        std::thread::sleep(self.delay(retries, jitter()));
+        true
+    }
+}
+
+/// Returns a random number in `[0, 1)`. Every `RandomState` is seeded
+/// differently, which is random enough to spread out retries.
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+impl BusyHandler {
+    fn retry(&self, retries: u32) -> bool {
+        match self {
+            BusyHandler::Backoff(backoff) => backoff.retry(retries),
+            BusyHandler::Callback(callback) => callback(retries),
+        }
+    }
+}
+
+const CLIENTDATA: &std::ffi::CStr = c"libsql-js.busy";
+
+/// Installs `handler` as the busy handler of the connection, replacing its
+/// busy timeout and any previous busy handler, or removes the busy handler.
+pub fn set_busy_handler(raw: &RawConnection, handler: Option<BusyHandler>) {
+    match handler {
+        Some(handler) => {
+            let handler = Arc::new(handler);
+            // SAFETY: the connection keeps a reference to the handler until it
+            // is replaced or the connection is closed, after which SQLite no
+            // longer invokes it.
+            unsafe {
+                raw.set_busy_handler(Some(on_busy), Arc::as_ptr(&handler) as *mut c_void);
+            }
+            raw.set_clientdata(CLIENTDATA, handler);
+        }
+        None => {
+            // SAFETY: no argument is passed to a removed handler.
+            unsafe {
+                raw.set_busy_handler(None, std::ptr::null_mut());
+            }
+            raw.remove_clientdata(CLIENTDATA);
+        }
+    }
+}
+
+unsafe extern "C" fn on_busy(arg: *mut c_void, retries: c_int) -> c_int {
+    let handler = unsafe { &*(arg as *const BusyHandler) };
+    // Deciding may take a while. Let the runtime move other tasks, such as the
+    // one about to release the lock, off this worker in the meantime.
+    tokio::task::block_in_place(|| handler.retry(retries.max(0) as u32)) as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn backoff_delay_grows_exponentially_up_to_the_maximum() {
+        let backoff = Backoff {
+            max_retries: 20,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+        assert_eq!(backoff.delay(0, 0.0), Duration::from_millis(5));
+        assert_eq!(backoff.delay(0, 1.0), Duration::from_millis(10));
+        assert_eq!(backoff.delay(2, 0.5), Duration::from_millis(30));
+        assert_eq!(backoff.delay(4, 1.0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(40, 0.0), Duration::from_millis(50));
+        assert!((0..100).map(|_| jitter()).all(|j| (0.0..1.0).contains(&j)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn callback_decides_whether_to_retry() {
+        let path = std::env::temp_dir().join(format!("libsql-js-busy-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = libsql::Builder::new_local(&path).build().await.unwrap();
+        let holder = db.connect().unwrap();
+        holder
+            .execute_batch("CREATE TABLE t(x); BEGIN IMMEDIATE; INSERT INTO t VALUES (1)")
+            .await
+            .unwrap();
+
+        let (conn, raw) = crate::raw::connect(&db).unwrap();
+        let raw = raw.unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        {
+            let calls = calls.clone();
+            set_busy_handler(
+                &raw,
+                Some(BusyHandler::Callback(Box::new(move |retries| {
+                    calls.lock().unwrap().push(retries);
+                    retries < 2
+                }))),
+            );
+        }
+        let err = conn
+            .execute("INSERT INTO t VALUES (2)", ())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, libsql::Error::SqliteFailure(code, _) if code & 0xff == libsql::ffi::SQLITE_BUSY)
+        );
+        assert_eq!(*calls.lock().unwrap(), vec![0, 1, 2]);
+
+        set_busy_handler(&raw, None);
+        calls.lock().unwrap().clear();
+        assert!(conn.execute("INSERT INTO t VALUES (2)", ()).await.is_err());
+        assert!(calls.lock().unwrap().is_empty());
+
+        drop((conn, raw, holder, db));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,95 @@
+//! Changing the encryption of local databases.
+//!
+//! libSQL encrypts local databases with SQLite3 Multiple Ciphers, and only
+//! configures the cipher and key when it opens a connection. The extension
+//! also lets an open connection change them with the `cipher` and `rekey`
+//! pragmas, which re-encrypt every page of the database in place. An empty
+//! key decrypts the database, and a key set on a plaintext database encrypts
+//! it.
+
+use crate::sql::quote_identifier;
+
+/// The cipher databases are encrypted with unless another one is asked for.
+pub const DEFAULT_CIPHER: &str = "aes256cbc";
+
+/// Whether SQLite was built with encryption support.
+pub async fn is_supported(conn: &libsql::Connection) -> libsql::Result<bool> {
+    // Without the extension, the pragma is unknown and returns nothing.
+    let mut rows = conn.query("PRAGMA cipher", ()).await?;
+    Ok(rows.next().await?.is_some())
+}
+
+/// Reads the schema of the database attached to `conn` as `schema`, which
+/// fails with `SQLITE_NOTADB` if its key cannot decrypt the database.
+pub async fn check_key(conn: &libsql::Connection, schema: &str) -> libsql::Result<()> {
+    let sql = format!(
+        "SELECT count(*) FROM {}.sqlite_schema",
+        quote_identifier(schema)
+    );
+    let mut rows = conn.query(&sql, ()).await?;
+    rows.next().await?;
+    Ok(())
+}
+
+/// Re-encrypts the database of `conn` with `key` using `cipher`, or decrypts
+/// it if `key` is empty.
+///
+/// SQLite3 Multiple Ciphers does not rekey databases in WAL journal mode, so
+/// such a database is switched to rollback journal mode for the rekey and
+/// back to WAL mode afterwards. The switch fails with `SQLITE_BUSY` while
+/// other connections use the database.
+pub async fn rekey(conn: &libsql::Connection, key: &str, cipher: &str) -> libsql::Result<()> {
+    let wal = journal_mode(conn, None).await? == "wal";
+    if wal && journal_mode(conn, Some("DELETE")).await? != "delete" {
+        return Err(libsql::Error::SqliteFailure(
+            libsql::ffi::SQLITE_ERROR,
+            "cannot leave WAL journal mode to change the encryption key".to_string(),
+        ));
+    }
+    let result = async {
+        conn.execute_batch(&format!("PRAGMA cipher = {}", quote(cipher)))
+            .await?;
+        conn.execute_batch(&format!("PRAGMA rekey = {}", quote(key)))
+            .await
+    }
+    .await;
+    let restored = match wal {
+        true => journal_mode(conn, Some("WAL")).await.map(drop),
+        false => Ok(()),
+    };
+    result?;
+    restored
+}
+
+/// Returns the journal mode of the main database of `conn`, after changing it
+/// to `mode` if given.
+async fn journal_mode(conn: &libsql::Connection, mode: Option<&str>) -> libsql::Result<String> {
+    let sql = match mode {
+        Some(mode) => format!("PRAGMA journal_mode = {mode}"),
+        None => "PRAGMA journal_mode".to_string(),
+    };
+    let mut rows = conn.query(&sql, ()).await?;
+    let row = rows.next().await?;
+    Ok(row
+        .map(|row| row.get::<String>(0))
+        .transpose()?
+        .unwrap_or_default()
+        .to_lowercase())
+}
+
+/// Quotes `value` as an SQL string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pragma_values_are_quoted() {
+        assert_eq!(quote("secret"), "'secret'");
+        assert_eq!(quote("it's"), "'it''s'");
+        assert_eq!(quote(""), "''");
+    }
+}
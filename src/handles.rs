@@ -0,0 +1,86 @@
+//! Release of the libSQL objects of a closed database.
+//!
+//! Statements and iterators hold on to the libSQL objects they run on, and
+//! those keep the SQLite connection open, along with the locks it holds on the
+//! database file, until the JavaScript garbage collector gets to them. So that
+//! closing a database releases everything right away, they keep their objects
+//! in slots registered with the database, which empties every slot when it is
+//! closed.
+
+use std::sync::{Arc, Mutex, Weak};
+
+/// Objects that are dropped when the database they belong to is closed.
+pub struct Slot<T>(Mutex<Option<T>>);
+
+impl<T: Clone> Slot<T> {
+    /// Returns the objects, unless the database has been closed.
+    pub fn get(&self) -> Option<T> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+trait Release: Send + Sync {
+    fn release(&self);
+}
+
+impl<T: Send> Release for Slot<T> {
+    fn release(&self) {
+        // Dropped outside of the lock, as finalizing can take a while.
+        let value = self.0.lock().unwrap().take();
+        drop(value);
+    }
+}
+
+/// The slots of a database.
+#[derive(Default)]
+pub struct Registry {
+    slots: Mutex<Vec<Weak<dyn Release>>>,
+}
+
+impl Registry {
+    /// Puts `value` in a new slot that is emptied when the registry is
+    /// closed.
+    pub fn register<T: Send + 'static>(&self, value: T) -> Arc<Slot<T>> {
+        let slot = Arc::new(Slot(Mutex::new(Some(value))));
+        let mut slots = self.slots.lock().unwrap();
+        // Forget the slots that were dropped before the vector has to grow,
+        // which keeps registration amortized constant time.
+        if slots.len() == slots.capacity() {
+            slots.retain(|slot| slot.strong_count() > 0);
+        }
+        let weak: Weak<dyn Release> = Arc::downgrade(&slot) as Weak<Slot<T>>;
+        slots.push(weak);
+        slot
+    }
+
+    /// Empties every slot.
+    pub fn close(&self) {
+        let slots = std::mem::take(&mut *self.slots.lock().unwrap());
+        for slot in slots {
+            if let Some(slot) = slot.upgrade() {
+                slot.release();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_empties_live_slots() {
+        let registry = Registry::default();
+        let value = Arc::new(());
+        let kept = registry.register(value.clone());
+        for _ in 0..100 {
+            drop(registry.register(value.clone()));
+        }
+        assert!(registry.slots.lock().unwrap().len() < 100);
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        registry.close();
+        assert!(kept.get().is_none());
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
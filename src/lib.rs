@@ -20,21 +20,40 @@
 #![allow(non_snake_case)]
 #![allow(deprecated)]
 
+mod attach;
 mod auth;
+mod background_sync;
+mod busy;
+mod encryption;
+mod handles;
+mod migrate;
+mod operations;
+mod progress;
 mod query_timeout;
+mod raw;
+mod replica;
+mod schema;
+mod script;
+mod sql;
+mod wal;
 
 use napi::{
-    bindgen_prelude::{Array, FromNapiValue, ToNapiValue},
-    Env, JsUnknown, Result, ValueType,
+    bindgen_prelude::{Array, FromNapiValue, FunctionRef, This, ToNapiValue},
+    threadsafe_function::{
+        ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+    },
+    Env, JsObject, JsUnknown, NapiRaw, NapiValue, Result, ValueType,
 };
 use napi_derive::napi;
 use once_cell::sync::OnceCell;
-use query_timeout::{QueryTimeoutGuard, QueryTimeoutManager};
+use query_timeout::{QueryTimeoutGuard, QueryTimeoutManager, TimeoutCounters, TimeoutStats};
 
 use std::{
+    cell::Cell,
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::SyncSender,
         Arc, Mutex,
     },
     time::Duration,
@@ -42,37 +61,174 @@ use std::{
 use tokio::runtime::Runtime;
 use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
-struct Error(libsql::Error);
+struct Error {
+    // The underlying libSQL error.
+    error: libsql::Error,
+    // The SQL text of the statement that failed, if known.
+    sql: Option<String>,
+    // Byte offset into `sql` of the token SQLite blamed for the error, if known.
+    offset: Option<usize>,
+    // The configured timeout and the time elapsed, if the error is an interrupt
+    // caused by a query timeout expiring.
+    timeout: Option<(Duration, Duration)>,
+    // The step budget, if the error is an interrupt caused by it running out.
+    max_steps: Option<u64>,
+    // The index of the failing statement, if `sql` is a script.
+    statement_index: Option<usize>,
+}
+
+impl Error {
+    /// Attaches the SQL text of the failing statement to a libSQL error.
+    fn with_sql(error: libsql::Error, sql: &str) -> Self {
+        Error {
+            error,
+            sql: Some(sql.to_string()),
+            offset: None,
+            timeout: None,
+            max_steps: None,
+            statement_index: None,
+        }
+    }
+
+    /// Attaches the location SQLite reported for the most recent error on
+    /// `raw`, provided that error is the one being converted.
+    fn with_offset_from(mut self, raw: Option<&raw::RawConnection>) -> Self {
+        if let (libsql::Error::SqliteFailure(raw_code, _), Some(raw)) = (&self.error, raw) {
+            if raw.extended_errcode() == *raw_code {
+                self.offset = raw.error_offset();
+            }
+        }
+        self
+    }
+
+    /// Marks the error as a query timeout if it is an interrupt and `guard`'s
+    /// deadline is what triggered it.
+    fn with_timeout(mut self, guard: Option<&QueryTimeoutGuard>) -> Self {
+        if let Some(guard) = guard {
+            if is_sqlite_interrupt(&self.error) && guard.fired() {
+                self.timeout = Some((guard.timeout(), guard.elapsed()));
+            }
+        }
+        self
+    }
+
+    /// Marks the error as a query timeout or an exhausted step budget if it is
+    /// an interrupt caused by one of the limits armed by `guard`.
+    fn with_limits(mut self, guard: &LimitsGuard) -> Self {
+        self = self.with_timeout(guard.timeout.as_ref());
+        if let Some(steps) = &guard.steps {
+            if is_sqlite_interrupt(&self.error) && steps.fired() {
+                self.max_steps = Some(steps.max_steps());
+            }
+        }
+        self
+    }
+}
 
 impl From<Error> for napi::Error {
     fn from(error: Error) -> Self {
         use libsql::Error as E;
-        match &error.0 {
-            E::SqliteFailure(raw_code, msg) => {
-                let code = map_sqlite_code(*raw_code);
-                if *raw_code == libsql::ffi::SQLITE_AUTH {
-                    let err_json = serde_json::json!({
-                        "message": "Authorization denied by JS authorizer",
-                        "libsqlError": true,
-                        "code": code,
-                        "rawCode": *raw_code
-                    });
-                    napi::Error::from_reason(err_json.to_string())
+        let (raw_code, message) = match &error.error {
+            E::SqliteFailure(raw_code, msg) => (*raw_code, msg.clone()),
+            E::RemoteSqliteFailure(code, extended_code, msg) => {
+                let raw_code = if *extended_code != 0 {
+                    *extended_code
                 } else {
-                    throw_sqlite_error(msg.clone(), code, *raw_code)
-                }
+                    *code
+                };
+                (raw_code, msg.clone())
             }
-            other => {
-                let err_json = serde_json::json!({
-                    "message": other.to_string(),
-                    "libsqlError": true,
-                    "code": "SQLITE_ERROR",
-                    "rawCode": 1
-                });
-                napi::Error::from_reason(err_json.to_string())
+            other => (libsql::ffi::SQLITE_ERROR, other.to_string()),
+        };
+        let conflict = match &error.error {
+            E::Sync(err) => replica::Conflict::parse(&err.to_string()),
+            _ => None,
+        };
+        let (message, code) = if raw_code == libsql::ffi::SQLITE_AUTH {
+            (
+                "Authorization denied by JS authorizer".to_string(),
+                map_sqlite_code(raw_code),
+            )
+        } else if let Some((timeout, _)) = error.timeout {
+            (
+                format!(
+                    "interrupted: query exceeded its {} ms timeout",
+                    timeout.as_millis()
+                ),
+                "SQLITE_QUERY_TIMEOUT".to_string(),
+            )
+        } else if let Some(max_steps) = error.max_steps {
+            (
+                format!("interrupted: query exceeded its budget of {max_steps} VM steps"),
+                "SQLITE_MAX_STEPS".to_string(),
+            )
+        } else if conflict.is_some() {
+            (message, "SQLITE_SYNC_CONFLICT".to_string())
+        } else {
+            (message, map_sqlite_code(raw_code))
+        };
+        let mut err_json = serde_json::json!({
+            "message": message,
+            "libsqlError": true,
+            "code": code,
+            "rawCode": raw_code
+        });
+        if let Some((timeout, elapsed)) = error.timeout {
+            err_json["queryTimeout"] = (timeout.as_millis() as u64).into();
+            err_json["elapsed"] = (elapsed.as_millis() as u64).into();
+        }
+        if let Some(max_steps) = error.max_steps {
+            err_json["maxSteps"] = max_steps.into();
+        }
+        if let Some(conflict) = conflict {
+            let (kind, local, remote) = match conflict {
+                replica::Conflict::Frame { local, remote } => ("frame", local, remote),
+                replica::Conflict::Generation { local, remote } => ("generation", local, remote),
+            };
+            err_json["conflict"] = serde_json::json!({
+                "kind": kind,
+                "local": local,
+                "remote": remote
+            });
+        }
+        if let Some(sql) = &error.sql {
+            err_json["sql"] = sql.as_str().into();
+            if let Some(offset) = error.offset.and_then(|offset| sql.get(..offset)) {
+                // Report the offset as a JavaScript string index.
+                err_json["offset"] = offset.encode_utf16().count().into();
             }
         }
+        if let Some(statement_index) = error.statement_index {
+            err_json["statementIndex"] = statement_index.into();
+        }
+        if let Some((table, columns)) = constraint_target(raw_code, &message) {
+            err_json["table"] = table.into();
+            err_json["column"] = columns[0].clone().into();
+            err_json["columns"] = columns.into();
+        }
+        napi::Error::from_reason(err_json.to_string())
+    }
+}
+
+/// Extracts the table and columns named by a UNIQUE, PRIMARY KEY or NOT NULL
+/// constraint violation, whose messages have the form
+/// `UNIQUE constraint failed: users.email` (or `t.a, t.b` for composite keys).
+fn constraint_target(raw_code: i32, message: &str) -> Option<(String, Vec<String>)> {
+    match raw_code {
+        libsql::ffi::SQLITE_CONSTRAINT_UNIQUE
+        | libsql::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+        | libsql::ffi::SQLITE_CONSTRAINT_NOTNULL => {}
+        _ => return None,
     }
+    let (_, targets) = message.split_once("constraint failed: ")?;
+    let mut table = None;
+    let mut columns = Vec::new();
+    for target in targets.split(", ") {
+        let (t, column) = target.split_once('.')?;
+        table.get_or_insert_with(|| t.to_string());
+        columns.push(column.to_string());
+    }
+    Some((table?, columns))
 }
 
 fn map_sqlite_code(code: i32) -> String {
@@ -164,7 +320,7 @@ fn map_sqlite_code(code: i32) -> String {
         libsql::ffi::SQLITE_WARNING_AUTOINDEX => "SQLITE_WARNING_AUTOINDEX".to_owned(),
         libsql::ffi::SQLITE_AUTH_USER => "SQLITE_AUTH_USER".to_owned(),
         libsql::ffi::SQLITE_OK_LOAD_PERMANENTLY => "SQLITE_OK_LOAD_PERMANENTLY".to_owned(),
-        _ => format!("UNKNOWN_SQLITE_ERROR_{}", code),
+        _ => format!("UNKNOWN_SQLITE_ERROR_{code}"),
     }
 }
 
@@ -180,7 +336,14 @@ pub fn throw_sqlite_error(message: String, code: String, raw_code: i32) -> napi:
 
 impl From<libsql::Error> for Error {
     fn from(error: libsql::Error) -> Self {
-        Error(error)
+        Error {
+            error,
+            sql: None,
+            offset: None,
+            timeout: None,
+            max_steps: None,
+            statement_index: None,
+        }
     }
 }
 
@@ -197,6 +360,8 @@ pub struct Options {
     pub readYourWrites: Option<bool>,
     // Sync interval in seconds.
     pub syncPeriod: Option<f64>,
+    // Write locally and push changes to the primary on sync.
+    pub offline: Option<bool>,
     // Encryption cipher for local enryption at rest.
     pub encryptionCipher: Option<String>,
     // Encryption key for local encryption at rest.
@@ -205,6 +370,42 @@ pub struct Options {
     pub remoteEncryptionKey: Option<String>,
     // Default maximum time in milliseconds that a query is allowed to run.
     pub defaultQueryTimeout: Option<f64>,
+    // Open the database in read-only mode.
+    pub readonly: Option<bool>,
+    // Fail instead of creating the database file if it does not exist.
+    pub fileMustExist: Option<bool>,
+    // Fail on missing and unknown named parameters instead of leaving them
+    // unbound.
+    pub strictParams: Option<bool>,
+}
+
+/// Retry policy for operations that find the database locked.
+#[napi(object)]
+pub struct BusyBackoff {
+    // Number of retries before giving up with `SQLITE_BUSY`.
+    pub maxRetries: Option<f64>,
+    // Upper bound in milliseconds of the wait before the first retry.
+    pub initialDelay: Option<f64>,
+    // Upper bound in milliseconds of the wait before any retry.
+    pub maxDelay: Option<f64>,
+}
+
+impl From<BusyBackoff> for busy::Backoff {
+    fn from(options: BusyBackoff) -> Self {
+        let default = busy::Backoff::default();
+        let millis = |ms: Option<f64>, default: Duration| {
+            ms.filter(|ms| ms.is_finite() && *ms >= 0.0)
+                .map_or(default, |ms| Duration::from_millis(ms as u64))
+        };
+        busy::Backoff {
+            max_retries: options
+                .maxRetries
+                .filter(|n| n.is_finite() && *n >= 0.0)
+                .map_or(default.max_retries, |n| n as u32),
+            initial_delay: millis(options.initialDelay, default.initial_delay),
+            max_delay: millis(options.maxDelay, default.max_delay),
+        }
+    }
 }
 
 /// Per-query execution options.
@@ -212,6 +413,49 @@ pub struct Options {
 pub struct QueryOptions {
     // Maximum time in milliseconds that this query is allowed to run.
     pub queryTimeout: Option<f64>,
+    // Maximum number of virtual machine instructions that this query is
+    // allowed to execute (local databases only).
+    pub maxSteps: Option<f64>,
+}
+
+/// Query timeout statistics.
+#[napi(object)]
+pub struct QueryTimeoutStats {
+    // Number of timeouts registered.
+    pub registered: f64,
+    // Number of timeouts that expired and interrupted their query.
+    pub fired: f64,
+    // Number of timeouts of completed queries discarded at their deadline.
+    pub reaped: f64,
+    // Number of deadlines currently pending, including those of completed queries.
+    pub heapSize: f64,
+    // Number of queries currently running with a timeout.
+    pub active: f64,
+}
+
+impl From<TimeoutStats> for QueryTimeoutStats {
+    fn from(stats: TimeoutStats) -> Self {
+        QueryTimeoutStats {
+            registered: stats.registered as f64,
+            fired: stats.fired as f64,
+            reaped: stats.reaped as f64,
+            heapSize: stats.heap_size as f64,
+            active: stats.active as f64,
+        }
+    }
+}
+
+/// Database statistics.
+#[napi(object)]
+pub struct DatabaseStats {
+    // Query timeout statistics for the queries run on this connection.
+    pub queryTimeouts: QueryTimeoutStats,
+}
+
+/// Returns query timeout statistics for all databases in the process.
+#[napi]
+pub fn query_timeout_stats() -> QueryTimeoutStats {
+    QueryTimeoutManager::global().stats().into()
 }
 
 /// Access mode.
@@ -229,19 +473,40 @@ struct AccessMode {
 #[napi]
 pub struct Database {
     // The libSQL database instance.
-    db: Option<libsql::Database>,
+    db: Option<Arc<libsql::Database>>,
     // The libSQL connection instance.
     conn: Option<Arc<libsql::Connection>>,
+    // The raw SQLite handle of the connection, for local databases.
+    raw: Option<Arc<raw::RawConnection>>,
+    // State shared with the statements prepared on the connection.
+    state: Arc<ConnectionState>,
     // Whether to use safe integers by default.
     default_safe_integers: AtomicBool,
     // Whether to use memory-only mode.
     memory: bool,
-    // Maximum time in milliseconds that a query is allowed to run.
-    query_timeout: Option<Duration>,
+    // The path the database was opened with.
+    path: String,
+    // Whether the database is an embedded replica, offline or not.
+    replica: bool,
+    // Whether the database is an offline replica.
+    offline: bool,
+    // The syncs of the database, for embedded replicas.
+    background_sync: Arc<background_sync::BackgroundSync>,
+    // The JavaScript busy handler, if one is set.
+    busy_callback: Mutex<Option<Arc<JsBusyCallback>>>,
+    // The busy timeout set with `Options.timeout`, restored when the busy
+    // handler is removed.
+    busy_timeout: Duration,
+    // The cipher the database is encrypted with at rest, if any.
+    cipher: Mutex<Option<String>>,
+    // Whether the database was opened in read-only mode.
+    readonly: bool,
 }
 
 impl Drop for Database {
     fn drop(&mut self) {
+        self.background_sync.stop();
+        self.raw = None;
         self.conn = None;
         self.db = None;
     }
@@ -250,6 +515,8 @@ impl Drop for Database {
 #[napi]
 pub async fn connect(path: String, opts: Option<Options>) -> Result<Database> {
     let remote = is_remote_path(&path);
+    let encryption = encryption_config(&path, opts.as_ref())?;
+    let flags = open_flags(&path, opts.as_ref())?;
     let db = if remote {
         let auth_token = opts
             .as_ref()
@@ -272,82 +539,250 @@ pub async fn connect(path: String, opts: Option<Options>) -> Result<Database> {
         if let Some(sync_url) = &options.syncUrl {
             let auth_token = options.authToken.as_ref().cloned().unwrap_or_default();
 
-            let encryption_cipher: String = opts
-                .as_ref()
-                .and_then(|o| o.encryptionCipher.as_ref())
-                .cloned()
-                .unwrap_or("aes256cbc".to_string());
-            let cipher = libsql::Cipher::from_str(&encryption_cipher).map_err(|_| {
-                throw_sqlite_error(
-                    "Invalid encryption cipher".to_string(),
-                    "SQLITE_INVALID_ENCRYPTION_CIPHER".to_string(),
-                    0,
+            if options.offline.unwrap_or(false) {
+                let mut builder = libsql::Builder::new_synced_database(
+                    path.clone(),
+                    sync_url.clone(),
+                    auth_token,
                 )
-            })?;
-            let encryption_key = opts
-                .as_ref()
-                .and_then(|o| o.encryptionKey.as_ref())
-                .cloned()
-                .unwrap_or("".to_string());
-
-            let mut builder =
-                libsql::Builder::new_remote_replica(path.clone(), sync_url.clone(), auth_token);
-
-            let read_your_writes = options.readYourWrites.unwrap_or(true);
-            builder = builder.read_your_writes(read_your_writes);
-
-            if encryption_key.len() > 0 {
-                let encryption_config =
-                    libsql::EncryptionConfig::new(cipher, encryption_key.into());
-                builder = builder.encryption_config(encryption_config);
-            }
+                .read_your_writes(options.readYourWrites.unwrap_or(true));
+                if let Some(remote_encryption_key) = &options.remoteEncryptionKey {
+                    let encryption_context = libsql::EncryptionContext {
+                        key: libsql::EncryptionKey::Base64Encoded(
+                            remote_encryption_key.to_string(),
+                        ),
+                    };
+                    builder = builder.remote_encryption(encryption_context);
+                }
+                builder.build().await.map_err(Error::from)?
+            } else {
+                let mut builder =
+                    libsql::Builder::new_remote_replica(path.clone(), sync_url.clone(), auth_token);
 
-            if let Some(remote_encryption_key) = &options.remoteEncryptionKey {
-                let encryption_context = libsql::EncryptionContext {
-                    key: libsql::EncryptionKey::Base64Encoded(remote_encryption_key.to_string()),
-                };
-                builder = builder.remote_encryption(encryption_context);
-            }
+                let read_your_writes = options.readYourWrites.unwrap_or(true);
+                builder = builder.read_your_writes(read_your_writes);
 
-            if let Some(period) = options.syncPeriod {
-                if period > 0.0 {
-                    builder = builder.sync_interval(std::time::Duration::from_secs_f64(period));
+                if let Some((_, encryption_config)) = &encryption {
+                    builder = builder.encryption_config(encryption_config.clone());
+                }
+
+                if let Some(remote_encryption_key) = &options.remoteEncryptionKey {
+                    let encryption_context = libsql::EncryptionContext {
+                        key: libsql::EncryptionKey::Base64Encoded(
+                            remote_encryption_key.to_string(),
+                        ),
+                    };
+                    builder = builder.remote_encryption(encryption_context);
                 }
-            }
 
-            builder.build().await.map_err(Error::from)?
+                builder.build().await.map_err(Error::from)?
+            }
         } else {
-            let builder = libsql::Builder::new_local(&path);
-            builder.build().await.map_err(Error::from)?
+            let mut builder = libsql::Builder::new_local(&path).flags(flags);
+            if let Some((_, encryption_config)) = &encryption {
+                builder = builder.encryption_config(encryption_config.clone());
+            }
+            builder.build().await.map_err(local_open_error)?
         }
     } else {
-        let builder = libsql::Builder::new_local(&path);
-        builder.build().await.map_err(Error::from)?
+        let builder = libsql::Builder::new_local(&path).flags(flags);
+        builder.build().await.map_err(local_open_error)?
     };
-    let conn = Arc::new(db.connect().map_err(Error::from)?);
+    // Connecting opens the local database file, if there is one.
+    let (conn, raw) = raw::connect(&db).map_err(|err| match remote {
+        true => Error::from(err).into(),
+        false => local_open_error(err),
+    })?;
+    // Whether a database is in memory also depends on URI filenames such as
+    // `file::memory:`, so it is only known once the database is open.
+    if flags.contains(libsql::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        && raw.as_ref().is_some_and(|raw| raw.is_memory(c"main"))
+    {
+        return Err(napi::Error::from_reason(
+            "In-memory/temporary databases cannot be readonly",
+        ));
+    }
+    if let Some((cipher, _)) = &encryption {
+        // SQLite only reads the database when it is first queried, so a wrong
+        // key would otherwise go unnoticed until then.
+        encryption::check_key(&conn, "main").await.map_err(|err| match err {
+            libsql::Error::SqliteFailure(libsql::ffi::SQLITE_NOTADB, _) => throw_sqlite_error(
+                format!("Unable to decrypt the database with the `{cipher}` cipher: the encryption key is wrong, or the database is not encrypted"),
+                "SQLITE_NOTADB".to_string(),
+                libsql::ffi::SQLITE_NOTADB,
+            ),
+            err => Error::from(err).into(),
+        })?;
+    }
+    let conn = Arc::new(conn);
     let default_safe_integers = AtomicBool::new(false);
     let memory = path == ":memory:";
     let timeout = match opts {
         Some(ref opts) => opts.timeout.unwrap_or(0.0),
         None => 0.0,
     };
+    let busy_timeout = Duration::from_millis(timeout.max(0.0) as u64);
     if timeout > 0.0 {
-        conn.busy_timeout(Duration::from_millis(timeout as u64))
-            .map_err(Error::from)?
+        conn.busy_timeout(busy_timeout).map_err(Error::from)?
     }
     let query_timeout = opts
         .as_ref()
         .and_then(|o| o.defaultQueryTimeout)
         .and_then(query_timeout_duration);
+    let readonly = raw.as_ref().map_or(
+        flags.contains(libsql::OpenFlags::SQLITE_OPEN_READ_ONLY),
+        |raw| raw.is_readonly(c"main"),
+    );
+    let raw = raw.map(Arc::new);
+    let db = Arc::new(db);
+    let replica = opts
+        .as_ref()
+        .is_some_and(|o| !remote && o.syncUrl.is_some());
+    let offline = replica && opts.as_ref().is_some_and(|o| o.offline.unwrap_or(false));
+    let background_sync = Arc::new(background_sync::BackgroundSync::default());
+    let sync_period = opts
+        .as_ref()
+        .filter(|o| !remote && o.syncUrl.is_some())
+        .and_then(|o| o.syncPeriod)
+        .filter(|period| *period > 0.0);
+    if let Some(period) = sync_period {
+        let db = Arc::downgrade(&db);
+        background_sync.start(Duration::from_secs_f64(period), move || {
+            let db = db.clone();
+            async move {
+                let db = db.upgrade()?;
+                let result = db
+                    .sync()
+                    .await
+                    .map_err(|err| napi::Error::from(Error::from(err)));
+                Some(sync_outcome(result.as_ref()))
+            }
+        });
+    }
+    let state = Arc::new(ConnectionState {
+        progress: raw.clone().map(progress::Progress::new),
+        timeout_counters: Arc::new(TimeoutCounters::default()),
+        default_query_timeout: QueryTimeoutSetting::new(query_timeout),
+        handles: handles::Registry::default(),
+        operations: Arc::new(operations::Operations::default()),
+        strict_params: opts.as_ref().and_then(|o| o.strictParams).unwrap_or(false),
+    });
     Ok(Database {
         db: Some(db),
         conn: Some(conn),
+        raw,
+        state,
         default_safe_integers,
         memory,
-        query_timeout,
+        path,
+        replica,
+        offline,
+        background_sync,
+        busy_callback: Mutex::new(None),
+        busy_timeout,
+        cipher: Mutex::new(encryption.map(|(cipher, _)| cipher)),
+        readonly,
     })
 }
 
+/// Converts the error libSQL fails to open a local database with, which
+/// carries the bare result code at the end of its message, into an SQLite
+/// failure.
+fn local_open_error(err: libsql::Error) -> napi::Error {
+    let err = match &err {
+        libsql::Error::ConnectionFailed(msg) => match msg
+            .rsplit_once(": ")
+            .and_then(|(prefix, rc)| Some((prefix, rc.parse().ok()?)))
+        {
+            Some((prefix, rc)) => {
+                libsql::Error::SqliteFailure(rc, format!("{prefix}: {}", raw::errstr(rc)))
+            }
+            None => err,
+        },
+        _ => err,
+    };
+    Error::from(err).into()
+}
+
+/// Returns the flags to open a local database with.
+fn open_flags(path: &str, opts: Option<&Options>) -> Result<libsql::OpenFlags> {
+    let readonly = opts.and_then(|o| o.readonly).unwrap_or(false);
+    let file_must_exist = opts.and_then(|o| o.fileMustExist).unwrap_or(false);
+    if is_remote_path(path) || opts.is_some_and(|o| o.syncUrl.is_some()) {
+        if readonly || file_must_exist {
+            return Err(napi::Error::from_reason(
+                "The `readonly` and `fileMustExist` options are only supported for local databases",
+            ));
+        }
+        return Ok(libsql::OpenFlags::default());
+    }
+    let mut flags = if readonly {
+        libsql::OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else if file_must_exist {
+        libsql::OpenFlags::SQLITE_OPEN_READ_WRITE
+    } else {
+        libsql::OpenFlags::default()
+    };
+    if path.starts_with("file:") {
+        // Let SQLite interpret the path as a URI filename, which can carry
+        // parameters such as `mode=ro` or `immutable=1`.
+        flags |= libsql::OpenFlags::from_bits_retain(libsql::ffi::SQLITE_OPEN_URI);
+    }
+    Ok(flags)
+}
+
+/// Returns the name of the cipher and the configuration to encrypt a local
+/// database with, if the options ask for encryption at rest.
+fn encryption_config(
+    path: &str,
+    opts: Option<&Options>,
+) -> Result<Option<(String, libsql::EncryptionConfig)>> {
+    let Some(opts) = opts else {
+        return Ok(None);
+    };
+    let key = opts.encryptionKey.as_ref().filter(|key| !key.is_empty());
+    let Some(key) = key else {
+        if opts.encryptionCipher.is_some() {
+            return Err(napi::Error::from_reason(
+                "The `encryptionCipher` option requires an `encryptionKey`",
+            ));
+        }
+        return Ok(None);
+    };
+    if is_remote_path(path) {
+        return Err(napi::Error::from_reason(
+            "Local encryption is not supported for remote databases, use the `remoteEncryptionKey` option",
+        ));
+    }
+    if path == ":memory:" {
+        return Err(napi::Error::from_reason(
+            "Encryption is not supported for in-memory databases",
+        ));
+    }
+    if opts.syncUrl.is_some() && opts.offline.unwrap_or(false) {
+        return Err(napi::Error::from_reason(
+            "Local encryption is not supported for offline replicas",
+        ));
+    }
+    let name = opts
+        .encryptionCipher
+        .clone()
+        .unwrap_or_else(|| encryption::DEFAULT_CIPHER.to_string());
+    let cipher = libsql::Cipher::from_str(&name).map_err(|_| invalid_cipher_error())?;
+    Ok(Some((
+        name,
+        libsql::EncryptionConfig::new(cipher, key.clone().into()),
+    )))
+}
+
+fn invalid_cipher_error() -> napi::Error {
+    throw_sqlite_error(
+        "Invalid encryption cipher".to_string(),
+        "SQLITE_INVALID_ENCRYPTION_CIPHER".to_string(),
+        0,
+    )
+}
+
 #[napi]
 impl Database {
     /// Creates a new database instance.
@@ -369,14 +804,28 @@ impl Database {
         self.memory
     }
 
+    /// Returns whether the database was opened in read-only mode.
+    #[napi(getter)]
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Returns the path the database was opened with.
+    #[napi(getter)]
+    pub fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    /// Returns whether the database connection is open.
+    #[napi(getter)]
+    pub fn open(&self) -> bool {
+        self.conn.is_some()
+    }
+
     /// Returns whether the database is in a transaction.
-    #[napi]
-    pub fn in_transaction(&self) -> Result<bool> {
-        let conn = match &self.conn {
-            Some(conn) => conn.clone(),
-            None => return Ok(false),
-        };
-        Ok(!conn.is_autocommit())
+    #[napi(getter)]
+    pub fn in_transaction(&self) -> bool {
+        self.conn.as_ref().is_some_and(|conn| !conn.is_autocommit())
     }
 
     /// Prepares a statement for execution.
@@ -384,34 +833,45 @@ impl Database {
     /// # Arguments
     ///
     /// * `sql` - The SQL statement to prepare.
+    /// * `query_options` - Optional per-call overrides, such as the timeout
+    ///   for preparing the statement.
     ///
     /// # Returns
     ///
     /// A `Statement` instance.
     #[napi]
-    pub async fn prepare(&self, sql: String) -> Result<Statement> {
-        let conn = match &self.conn {
-            Some(conn) => conn.clone(),
-            None => {
-                return Err(throw_sqlite_error(
-                    "The database connection is not open".to_string(),
-                    "SQLITE_NOTOPEN".to_string(),
-                    0,
-                ));
-            }
-        };
-        let timeout_guard = register_timeout(&conn, self.query_timeout);
+    pub async fn prepare(
+        &self,
+        sql: String,
+        query_options: Option<QueryOptions>,
+    ) -> Result<Statement> {
+        let (conn, _operation) = self.begin()?;
+        let prepare_error = |err| Error::with_sql(err, &sql).with_offset_from(self.raw.as_deref());
+        let timeout = Limits::resolve(
+            self.state.default_query_timeout.get(),
+            query_options,
+            &self.state,
+        )?
+        .timeout;
+        let timeout_guard = register_timeout(&conn, timeout, &self.state);
         let stmt = match conn.prepare(&sql).await {
             Ok(stmt) => stmt,
             Err(err) if is_sqlite_interrupt(&err) => {
+                if timeout_guard.as_ref().is_some_and(|guard| guard.fired()) {
+                    return Err(prepare_error(err)
+                        .with_timeout(timeout_guard.as_ref())
+                        .into());
+                }
                 // Drop our guard before clear_stale_interrupt so the bg thread
                 // can't fire conn.interrupt() for our id mid-probe.
                 drop(timeout_guard);
                 clear_stale_interrupt(&conn).await;
-                let _retry_guard = register_timeout(&conn, self.query_timeout);
-                conn.prepare(&sql).await.map_err(Error::from)?
+                let retry_guard = register_timeout(&conn, timeout, &self.state);
+                conn.prepare(&sql)
+                    .await
+                    .map_err(|err| prepare_error(err).with_timeout(retry_guard.as_ref()))?
             }
-            Err(err) => return Err(Error::from(err).into()),
+            Err(err) => return Err(prepare_error(err).into()),
         };
         let mode = AccessMode {
             safe_ints: self.default_safe_integers.load(Ordering::SeqCst).into(),
@@ -419,7 +879,14 @@ impl Database {
             pluck: false.into(),
             timing: false.into(),
         };
-        Ok(Statement::new(conn, stmt, mode, self.query_timeout))
+        Ok(Statement::new(
+            conn,
+            stmt,
+            self.raw.clone(),
+            sql,
+            mode,
+            self.state.clone(),
+        ))
     }
 
     /// Sets the authorizer for the database.
@@ -459,7 +926,7 @@ impl Database {
         let conn = match &self.conn {
             Some(c) => c.clone(),
             None => {
-                return Err(throw_database_closed_error(&env).into());
+                return Err(throw_database_closed_error(&env));
             }
         };
 
@@ -521,7 +988,7 @@ impl Database {
             conn.load_extension_disable().map_err(Error::from)?;
             Ok(())
         })
-        .map_err(|e| napi::Error::from(e))
+        .map_err(napi::Error::from)
     }
 
     /// Returns the maximum write replication index.
@@ -547,106 +1014,1041 @@ impl Database {
     /// * `sql` - The SQL statement to execute.
     #[napi]
     pub async fn exec(&self, sql: String, query_options: Option<QueryOptions>) -> Result<()> {
-        let conn = match &self.conn {
-            Some(conn) => conn.clone(),
-            None => {
-                return Err(throw_sqlite_error(
-                    "The database connection is not open".to_string(),
-                    "SQLITE_NOTOPEN".to_string(),
-                    0,
-                ));
-            }
-        };
-        let query_timeout = match query_options.and_then(|o| o.queryTimeout) {
-            Some(timeout_ms) => query_timeout_duration(timeout_ms),
-            None => self.query_timeout,
-        };
-        let _timeout_guard = register_timeout(&conn, query_timeout);
-        conn.execute_batch(&sql).await.map_err(Error::from)?;
+        let (conn, _operation) = self.begin()?;
+        let limits = Limits::resolve(
+            self.state.default_query_timeout.get(),
+            query_options,
+            &self.state,
+        )?;
+        let limits_guard = limits.register(&conn, &self.state);
+        conn.execute_batch(&sql)
+            .await
+            .map_err(|err| Error::with_sql(err, &sql).with_limits(&limits_guard))?;
         Ok(())
     }
 
+    /// Executes a script statement by statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The script.
+    /// * `query_options` - Optional per-query overrides, for the whole script.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of each statement, or the error of the first statement that
+    /// failed, with its index and its offset in the script.
+    #[napi(ts_return_type = "Promise<Array<ScriptStatementResult>>")]
+    pub fn exec_multi(
+        &self,
+        env: Env,
+        sql: String,
+        query_options: Option<QueryOptions>,
+    ) -> Result<napi::JsObject> {
+        let safe_ints = self.default_safe_integers.load(Ordering::SeqCst);
+        let future = self.exec_script(sql, query_options)?;
+        env.execute_tokio_future(future, move |env, (sql, statements)| {
+            map_script_statements(env, &sql, statements, safe_ints)
+        })
+    }
+
+    /// Returns the future that runs the statements of a script.
+    fn exec_script(
+        &self,
+        sql: String,
+        query_options: Option<QueryOptions>,
+    ) -> Result<
+        impl std::future::Future<Output = Result<(String, Vec<ScriptStatement>)>> + Send + 'static,
+    > {
+        let (conn, operation) = self.begin()?;
+        let raw = self.raw.clone();
+        let state = self.state.clone();
+        let limits = Limits::resolve(
+            self.state.default_query_timeout.get(),
+            query_options,
+            &self.state,
+        )?;
+        Ok(async move {
+            let _operation = operation;
+            let limits_guard = limits.register(&conn, &state);
+            let mut statements = Vec::new();
+            for (index, range) in script::split(&sql).into_iter().enumerate() {
+                // Errors are reported against the script, at the offset of the
+                // statement, or of the token SQLite blamed within it.
+                let script_error = |err| {
+                    let mut err = Error::with_sql(err, &sql)
+                        .with_offset_from(raw.as_deref())
+                        .with_limits(&limits_guard);
+                    err.offset = Some(range.start + err.offset.unwrap_or(0));
+                    err.statement_index = Some(index);
+                    napi::Error::from(err)
+                };
+                let total_changes_before = conn.total_changes();
+                let stmt = conn
+                    .prepare(&sql[range.clone()])
+                    .await
+                    .map_err(script_error)?;
+                let column_names: Vec<std::ffi::CString> = stmt
+                    .columns()
+                    .iter()
+                    .map(|c| std::ffi::CString::new(c.name().to_string()).unwrap())
+                    .collect();
+                let mut rows = stmt.query(()).await.map_err(script_error)?;
+                let mut collected = Vec::new();
+                while let Some(row) = rows.next().await.map_err(script_error)? {
+                    // Rows read the current values of the statement, so they
+                    // are copied before stepping to the next one.
+                    let values = (0..column_names.len() as i32)
+                        .map(|idx| row.get_value(idx))
+                        .collect::<libsql::Result<Vec<_>>>()
+                        .map_err(script_error)?;
+                    collected.push(values);
+                }
+                let changes = if conn.total_changes() == total_changes_before {
+                    0
+                } else {
+                    conn.changes()
+                };
+                statements.push(ScriptStatement {
+                    range,
+                    column_names,
+                    rows: collected,
+                    changes,
+                    last_insert_rowid: conn.last_insert_rowid(),
+                });
+            }
+            Ok((sql, statements))
+        })
+    }
+
     /// Syncs the database.
     ///
+    /// # Arguments
+    ///
+    /// * `opts` - Optional sync options. If `timeout` is set and the sync does
+    ///   not complete in time, it is abandoned with a `SQLITE_SYNC_TIMEOUT`
+    ///   error.
+    /// * `progress` - Optional function notified as the sync applies frames.
+    ///
     /// # Returns
     ///
     /// A `SyncResult` instance.
     #[napi]
-    pub async fn sync(&self) -> Result<SyncResult> {
-        let db = match &self.db {
-            Some(db) => db,
-            None => {
-                return Err(throw_sqlite_error(
-                    "The database connection is not open".to_string(),
-                    "SQLITE_NOTOPEN".to_string(),
-                    0,
-                ));
+    pub async fn sync(
+        &self,
+        opts: Option<SyncOptions>,
+        progress: Option<ThreadsafeFunction<SyncProgress, ErrorStrategy::Fatal>>,
+    ) -> Result<SyncResult> {
+        let report = progress.map(|tsfn| {
+            move |progress| {
+                tsfn.call(progress, ThreadsafeFunctionCallMode::NonBlocking);
             }
-        };
-        let result = db.sync().await.map_err(Error::from)?;
-        Ok(SyncResult {
-            frames_synced: result.frames_synced() as f64,
-            replication_index: result.frame_no().unwrap_or(0) as f64,
-        })
+        });
+        self.sync_with_progress(opts, report).await
     }
 
-    /// Interrupts any ongoing database operations.
+    /// Returns what is known about the syncs of the database.
+    #[napi]
+    pub fn sync_status(&self) -> SyncStatus {
+        self.background_sync.status().into()
+    }
+
+    /// Pauses background syncs. A sync in progress completes.
+    #[napi]
+    pub fn pause_sync(&self) -> Result<()> {
+        self.set_sync_paused(true)
+    }
+
+    /// Resumes background syncs, starting with one right away.
+    #[napi]
+    pub fn resume_sync(&self) -> Result<()> {
+        self.set_sync_paused(false)
+    }
+
+    /// Sets a callback to be notified of failed background syncs, or removes
+    /// it when called without one.
+    ///
+    /// The callback is invoked asynchronously on the JavaScript thread with
+    /// the error message of the failed sync.
     ///
     /// # Arguments
     ///
     /// * `env` - The environment.
+    /// * `callback` - The function to notify.
     #[napi]
-    pub fn interrupt(&self, env: Env) -> Result<()> {
-        let conn = match &self.conn {
-            Some(conn) => conn.clone(),
-            None => return Err(throw_database_closed_error(&env).into()),
+    pub fn on_sync_error(&self, env: Env, callback: Option<napi::JsFunction>) -> Result<()> {
+        let Some(callback) = callback else {
+            self.background_sync.set_error_callback(None);
+            return Ok(());
         };
-        conn.interrupt().map_err(Error::from)?;
+        let mut tsfn: ThreadsafeFunction<String, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<String>| {
+                Ok(vec![ctx.env.create_string(&ctx.value)?])
+            })?;
+        // Notifications must not keep the process alive.
+        tsfn.unref(&env)?;
+        self.background_sync
+            .set_error_callback(Some(Box::new(move |message| {
+                tsfn.call(message.to_string(), ThreadsafeFunctionCallMode::NonBlocking);
+            })));
         Ok(())
     }
 
-    /// Closes the database connection.
+    /// Returns the local changes of an offline replica that have not been
+    /// pushed to the primary yet.
     #[napi]
-    pub fn close(&mut self) -> Result<()> {
-        self.conn = None;
-        self.db = None;
-        Ok(())
+    pub fn pending_changes(&self) -> Result<PendingChanges> {
+        let raw = self.offline_raw()?;
+        let pending = replica::pending_changes(raw, &self.path).map_err(Error::from)?;
+        Ok(pending.into())
     }
 
-    /// Sets the default safe integers mode.
+    /// Pushes the local changes of an offline replica to the primary.
     ///
     /// # Arguments
     ///
-    /// * `toggle` - Whether to use safe integers by default.
+    /// * `opts` - Optional sync options.
+    ///
+    /// # Returns
+    ///
+    /// The changes that were pushed. If the primary cannot be reached, none
+    /// are, and they remain pending.
     #[napi]
-    pub fn defaultSafeIntegers(&self, toggle: Option<bool>) -> Result<()> {
-        self.default_safe_integers
-            .store(toggle.unwrap_or(true), Ordering::SeqCst);
-        Ok(())
+    pub async fn push(&self, opts: Option<SyncOptions>) -> Result<PendingChanges> {
+        let raw = self.offline_raw()?;
+        let before = replica::pending_changes(raw, &self.path).map_err(Error::from)?;
+        if before.frames == 0 {
+            return Ok(before.into());
+        }
+        // libSQL pushes rather than pulls as long as there are local changes.
+        self.sync_with_progress(opts, None::<fn(SyncProgress)>)
+            .await?;
+        let after = replica::pending_changes(raw, &self.path).map_err(Error::from)?;
+        Ok(PendingChanges {
+            frames: before.frames.saturating_sub(after.frames) as f64,
+            transactions: before.transactions.saturating_sub(after.transactions) as f64,
+        })
     }
-}
 
-fn int_to_authorization(val: i32) -> Result<libsql::Authorization> {
-    match val {
-        0 => Ok(libsql::Authorization::Allow),
-        1 => Ok(libsql::Authorization::Deny),
-        2 => Ok(libsql::Authorization::Ignore),
-        _ => Err(napi::Error::from_reason(format!(
-            "Invalid authorization value '{val}'. Expected 0 (ALLOW), 1 (DENY), or 2 (IGNORE).",
-        ))),
+    /// Pulls the changes of the primary into an offline replica, which must
+    /// not have local changes waiting to be pushed.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - Optional sync options.
+    ///
+    /// # Returns
+    ///
+    /// A `SyncResult` instance.
+    #[napi]
+    pub async fn pull(&self, opts: Option<SyncOptions>) -> Result<SyncResult> {
+        let raw = self.offline_raw()?;
+        let pending = replica::pending_changes(raw, &self.path).map_err(Error::from)?;
+        if pending.frames > 0 {
+            return Err(throw_sqlite_error(
+                "Cannot pull while local changes are waiting to be pushed".to_string(),
+                "SQLITE_SYNC_PENDING_CHANGES".to_string(),
+                0,
+            ));
+        }
+        self.sync_with_progress(opts, None::<fn(SyncProgress)>)
+            .await
     }
-}
 
-/// Parse legacy `{ tableName: 0|1 }` format.
-fn parse_legacy_config(obj: &napi::JsObject) -> Result<crate::auth::Authorizer> {
-    let mut builder = crate::auth::AuthorizerBuilder::new();
-    let prop_names = obj.get_property_names()?;
-    let len = prop_names.get_array_length()?;
-    for idx in 0..len {
-        let key_js: napi::JsString = prop_names.get_element::<napi::JsString>(idx)?;
-        let key = key_js.into_utf8()?.into_owned()?;
-        let value_js: napi::JsNumber = obj.get_named_property(&key)?;
-        let value = value_js.get_int32()?;
+    /// Checkpoints the write-ahead log.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - Optional checkpoint mode and schema. By default, a passive
+    ///   checkpoint of every attached database is run.
+    ///
+    /// # Returns
+    ///
+    /// A `CheckpointResult` instance. The frame counts are -1 if the database
+    /// is not in WAL mode.
+    #[napi]
+    pub async fn checkpoint(&self, opts: Option<CheckpointOptions>) -> Result<CheckpointResult> {
+        let _operation = self.begin()?;
+        let Some(raw) = &self.raw else {
+            return Err(napi::Error::from_reason(
+                "Checkpoints are only supported for local databases",
+            ));
+        };
+        // The log of an offline replica holds the changes to push, which a
+        // checkpoint would lose.
+        if self.offline {
+            return Err(napi::Error::from_reason(
+                "Checkpoints are not supported for offline replicas",
+            ));
+        }
+        let (mode, schema) = opts.map_or((None, None), |o| (o.mode, o.schema));
+        let mode = match mode {
+            Some(mode) => mode.parse().map_err(|_| {
+                napi::Error::from_reason(format!(
+                    "Invalid checkpoint mode '{mode}'. Expected 'passive', 'full', 'restart' or 'truncate'.",
+                ))
+            })?,
+            None => wal::CheckpointMode::Passive,
+        };
+        // The checkpoint waits on the disk, and on readers and writers in the
+        // stricter modes, so keep it off the runtime's workers.
+        let raw = raw.clone();
+        let result =
+            tokio::task::spawn_blocking(move || wal::checkpoint(&raw, schema.as_deref(), mode))
+                .await
+                .map_err(|err| napi::Error::from_reason(err.to_string()))?
+                .map_err(Error::from)?;
+        Ok(result.into())
+    }
+
+    /// Sets a callback to be notified after every commit to a write-ahead
+    /// log, or removes it when called without one.
+    ///
+    /// The callback is invoked asynchronously on the JavaScript thread with
+    /// the name of the database committed to and the number of frames in its
+    /// log. Setting a callback turns automatic checkpointing off, so that the
+    /// application checkpoints on its own schedule; removing it turns
+    /// automatic checkpointing back on.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    /// * `callback` - The function to notify.
+    #[napi]
+    pub fn on_wal(&self, env: Env, callback: Option<napi::JsFunction>) -> Result<()> {
+        if self.conn.is_none() {
+            return Err(throw_database_closed_error(&env));
+        }
+        let Some(raw) = &self.raw else {
+            return Err(napi::Error::from_reason(
+                "WAL hooks are only supported for local databases",
+            ));
+        };
+        if self.offline {
+            return Err(napi::Error::from_reason(
+                "WAL hooks are not supported for offline replicas",
+            ));
+        }
+        let Some(callback) = callback else {
+            wal::set_wal_hook(raw, None).map_err(Error::from)?;
+            return Ok(());
+        };
+        let mut tsfn: ThreadsafeFunction<(String, u32), ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<(String, u32)>| {
+                let (schema, pages) = ctx.value;
+                Ok(vec![
+                    ctx.env.create_string(&schema)?.into_unknown(),
+                    ctx.env.create_uint32(pages)?.into_unknown(),
+                ])
+            })?;
+        // Notifications must not keep the process alive.
+        tsfn.unref(&env)?;
+        wal::set_wal_hook(
+            raw,
+            Some(Box::new(move |schema, pages| {
+                tsfn.call(
+                    (schema.to_string(), pages),
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            })),
+        )
+        .map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Returns how the database is encrypted at rest.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    #[napi]
+    pub fn encryption_info(&self, env: Env) -> Result<EncryptionInfo> {
+        if self.conn.is_none() {
+            return Err(throw_database_closed_error(&env));
+        }
+        let cipher = self.cipher.lock().unwrap().clone();
+        Ok(EncryptionInfo {
+            encrypted: cipher.is_some(),
+            cipher,
+        })
+    }
+
+    /// Changes the encryption key of the database.
+    ///
+    /// The database is re-encrypted in place, so it must be reopened with the
+    /// new key afterwards. A plaintext database is encrypted, and an empty or
+    /// missing key decrypts the database to plaintext.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_key` - The new encryption key.
+    /// * `opts` - Optional cipher to re-encrypt the database with.
+    #[napi]
+    pub async fn rekey(&self, new_key: Option<String>, opts: Option<RekeyOptions>) -> Result<()> {
+        let (conn, _operation) = self.begin()?;
+        if self.raw.is_none() {
+            return Err(napi::Error::from_reason(
+                "Encryption keys can only be changed for local databases",
+            ));
+        }
+        if self.offline {
+            return Err(napi::Error::from_reason(
+                "Local encryption is not supported for offline replicas",
+            ));
+        }
+        // The cipher must be set even when it does not change, as SQLite3
+        // Multiple Ciphers would otherwise pick its own default to encrypt a
+        // plaintext database with.
+        let cipher = opts
+            .and_then(|o| o.cipher)
+            .or_else(|| self.cipher.lock().unwrap().clone())
+            .unwrap_or_else(|| encryption::DEFAULT_CIPHER.to_string());
+        libsql::Cipher::from_str(&cipher).map_err(|_| invalid_cipher_error())?;
+        if !encryption::is_supported(&conn).await.map_err(Error::from)? {
+            return Err(napi::Error::from_reason(
+                "Encryption is not supported by this build of libSQL",
+            ));
+        }
+        // SQLite3 Multiple Ciphers cannot rekey a database in WAL mode, which
+        // embedded replicas depend on to apply frames.
+        if self.replica {
+            return Err(napi::Error::from_reason(
+                "Encryption keys cannot be changed for embedded replicas, which use WAL journal mode",
+            ));
+        }
+        let new_key = new_key.unwrap_or_default();
+        encryption::rekey(&conn, &new_key, &cipher)
+            .await
+            .map_err(Error::from)?;
+        *self.cipher.lock().unwrap() = (!new_key.is_empty()).then_some(cipher);
+        Ok(())
+    }
+
+    /// Attaches a database file to the connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the database file, or a URI filename.
+    /// * `schema` - The schema name to attach the database as.
+    /// * `opts` - Optional encryption key and read-only mode.
+    #[napi]
+    pub async fn attach(
+        &self,
+        path: String,
+        schema: String,
+        opts: Option<AttachOptions>,
+    ) -> Result<()> {
+        let (conn, _operation) = self.begin()?;
+        if self.raw.is_none() {
+            return Err(napi::Error::from_reason(
+                "Attaching databases is only supported for local databases",
+            ));
+        }
+        let (key, readonly) = opts.map_or((None, false), |o| {
+            (o.encryptionKey, o.readonly.unwrap_or(false))
+        });
+        let path = if readonly {
+            if path.is_empty() || path == ":memory:" {
+                return Err(napi::Error::from_reason(
+                    "In-memory/temporary databases cannot be readonly",
+                ));
+            }
+            attach::readonly_uri(&path)
+        } else {
+            path
+        };
+        if key.as_deref().is_some_and(|key| !key.is_empty())
+            && !encryption::is_supported(&conn).await.map_err(Error::from)?
+        {
+            return Err(napi::Error::from_reason(
+                "Encryption is not supported by this build of libSQL",
+            ));
+        }
+        let main_encrypted = self.cipher.lock().unwrap().is_some();
+        attach::attach(&conn, &path, &schema, key.as_deref(), main_encrypted)
+            .await
+            .map_err(Error::from)?;
+        if key.is_some() {
+            if let Err(err) = encryption::check_key(&conn, &schema).await {
+                let _ = attach::detach(&conn, &schema).await;
+                return Err(match err {
+                    libsql::Error::SqliteFailure(libsql::ffi::SQLITE_NOTADB, _) => throw_sqlite_error(
+                        format!("Unable to decrypt the database attached as '{schema}': the encryption key is wrong, or the database is not encrypted"),
+                        "SQLITE_NOTADB".to_string(),
+                        libsql::ffi::SQLITE_NOTADB,
+                    ),
+                    err => Error::from(err).into(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Detaches a database attached to the connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema name the database is attached as.
+    #[napi]
+    pub async fn detach(&self, schema: String) -> Result<()> {
+        let (conn, _operation) = self.begin()?;
+        attach::detach(&conn, &schema).await.map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Applies the migrations not yet applied to the database, in order and
+    /// in a single transaction. The applied migrations are recorded in a
+    /// table with a checksum of their SQL, and migrating fails if they no
+    /// longer match the first of the given migrations.
+    ///
+    /// # Arguments
+    ///
+    /// * `migrations` - The migrations, in the order they apply.
+    /// * `opts` - Optional name of the table recording applied migrations.
+    ///
+    /// # Returns
+    ///
+    /// A `MigrateResult` instance.
+    #[napi]
+    pub async fn migrate(
+        &self,
+        migrations: Vec<Migration>,
+        opts: Option<MigrateOptions>,
+    ) -> Result<MigrateResult> {
+        let (conn, _operation) = self.begin()?;
+        let mut ids = std::collections::HashSet::new();
+        for migration in &migrations {
+            if migration.id.is_empty() {
+                return Err(napi::Error::from_reason("Migration ids must not be empty"));
+            }
+            if !ids.insert(migration.id.as_str()) {
+                return Err(napi::Error::from_reason(format!(
+                    "Migration ids must be unique, '{}' is repeated",
+                    migration.id
+                )));
+            }
+        }
+        let table = opts
+            .and_then(|o| o.table)
+            .unwrap_or_else(|| "_migrations".to_string());
+        let migrations: Vec<_> = migrations
+            .into_iter()
+            .map(|migration| migrate::Migration {
+                id: migration.id,
+                up: migration.up,
+            })
+            .collect();
+        let applied =
+            migrate::migrate(&conn, &table, &migrations)
+                .await
+                .map_err(|err| match err {
+                    migrate::Error::Drift(message) => {
+                        throw_sqlite_error(message, "SQLITE_MIGRATION_DRIFT".to_string(), 0)
+                    }
+                    migrate::Error::Transaction(message) => napi::Error::from_reason(message),
+                    migrate::Error::Sql(err, Some(sql)) => Error::with_sql(err, &sql).into(),
+                    migrate::Error::Sql(err, None) => Error::from(err).into(),
+                })?;
+        // Embedded replicas run the migrations on the primary, so wait for
+        // them to be replicated before the new schema is queried locally. The
+        // migrations are committed by then, so a failure to sync is reported
+        // along with them rather than as an error.
+        let mut sync_error = None;
+        if let Some(db) = &self.db {
+            if let Some(index) = db.max_write_replication_index() {
+                match db.sync_until(index).await {
+                    Ok(_) | Err(libsql::Error::SyncNotSupported(_)) => {}
+                    Err(err) => sync_error = Some(napi::Error::from(Error::from(err)).reason),
+                }
+            }
+        }
+        Ok(MigrateResult {
+            applied,
+            sync_error,
+        })
+    }
+
+    /// Returns the tables, views, indexes, triggers and virtual tables of a
+    /// database, with the columns, foreign keys and indexes of each table.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - Optional name of the attached database to read the schema
+    ///   of.
+    #[napi]
+    pub async fn schema(&self, opts: Option<SchemaOptions>) -> Result<DatabaseSchema> {
+        let (conn, _operation) = self.begin()?;
+        let name = opts
+            .and_then(|o| o.schema)
+            .unwrap_or_else(|| "main".to_string());
+        let schema = schema::read(&conn, &name).await.map_err(Error::from)?;
+        Ok(schema.into())
+    }
+
+    /// Returns the databases of the connection: the main database, the
+    /// temporary database if it has been created, and the attached ones.
+    #[napi]
+    pub async fn databases(&self) -> Result<Vec<DatabaseEntry>> {
+        let (conn, _operation) = self.begin()?;
+        let databases = attach::list(&conn).await.map_err(Error::from)?;
+        Ok(databases
+            .into_iter()
+            .map(|(name, file)| {
+                let readonly = std::ffi::CString::new(name.as_str()).is_ok_and(|schema| {
+                    self.raw
+                        .as_ref()
+                        .is_some_and(|raw| raw.is_readonly(&schema))
+                });
+                DatabaseEntry {
+                    name,
+                    file,
+                    readonly,
+                }
+            })
+            .collect())
+    }
+
+    /// Interrupts any ongoing database operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    #[napi]
+    pub fn interrupt(&self, env: Env) -> Result<()> {
+        let conn = match &self.conn {
+            Some(conn) => conn.clone(),
+            None => return Err(throw_database_closed_error(&env)),
+        };
+        conn.interrupt().map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Sets a callback to be notified as the connection executes queries, or
+    /// removes it when called without one.
+    ///
+    /// The callback is invoked with the running count of virtual machine
+    /// instructions executed by the connection, roughly every `period`
+    /// instructions. It is called asynchronously on the JavaScript thread and
+    /// cannot stop the query; use the `maxSteps` query option for that.
+    /// Notifications that arrive while one is queued are coalesced into it.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    /// * `period` - Number of VM instructions between notifications.
+    /// * `callback` - The function to notify.
+    #[napi]
+    pub fn progress_handler(
+        &self,
+        env: Env,
+        period: Option<f64>,
+        callback: Option<napi::JsFunction>,
+    ) -> Result<()> {
+        if self.conn.is_none() {
+            return Err(throw_database_closed_error(&env));
+        }
+        let Some(progress) = &self.state.progress else {
+            return Err(napi::Error::from_reason(
+                "Progress handlers are only supported for local databases",
+            ));
+        };
+        let (period, callback) = match (period.and_then(step_budget), callback) {
+            (Some(period), Some(callback)) => (period, callback),
+            _ => {
+                progress.set_listener(None);
+                return Ok(());
+            }
+        };
+        // A long query with a short period would queue notifications faster
+        // than the JavaScript thread runs them. Only one notification is queued
+        // at a time, and it reports the latest count when it runs.
+        let latest = Arc::new(AtomicU64::new(0));
+        let queued = Arc::new(AtomicBool::new(false));
+        let mut tsfn: ThreadsafeFunction<(), ErrorStrategy::Fatal> = {
+            let (latest, queued) = (latest.clone(), queued.clone());
+            callback.create_threadsafe_function(1, move |ctx: ThreadSafeCallContext<()>| {
+                queued.store(false, Ordering::SeqCst);
+                let steps = latest.load(Ordering::SeqCst);
+                Ok(vec![ctx.env.create_double(steps as f64)?])
+            })?
+        };
+        // Notifications must not keep the process alive.
+        tsfn.unref(&env)?;
+        progress.set_listener(Some((
+            period,
+            Box::new(move |steps| {
+                latest.store(steps, Ordering::SeqCst);
+                if !queued.swap(true, Ordering::SeqCst) {
+                    tsfn.call((), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            }),
+        )));
+        Ok(())
+    }
+
+    /// Sets a callback that decides whether to retry an operation that finds
+    /// the database locked, replacing the busy timeout and any previous busy
+    /// handler, or removes the busy handler when called without one.
+    ///
+    /// The callback is invoked with the number of retries so far for the same
+    /// lock and returns whether to retry. Removing the busy handler restores
+    /// the busy timeout the database was opened with.
+    ///
+    /// An operation running on the runtime waits for the JavaScript thread to
+    /// call the callback for at most `BUSY_CALLBACK_TIMEOUT`, after which it
+    /// stops retrying: the thread may itself be blocked on the database. An
+    /// exception thrown by the callback also stops the retries. It is thrown
+    /// by the blocking call that waits on the lock, if there is one, and is
+    /// otherwise reported as an uncaught exception.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    /// * `callback` - The function deciding whether to retry.
+    #[napi]
+    pub fn busy_handler(&self, env: Env, callback: Option<napi::JsFunction>) -> Result<()> {
+        let raw = self.busy_raw(&env)?;
+        let Some(callback) = callback else {
+            busy::set_busy_handler(raw, None);
+            *self.busy_callback.lock().unwrap() = None;
+            if let Some(conn) = &self.conn {
+                conn.busy_timeout(self.busy_timeout).map_err(Error::from)?;
+            }
+            return Ok(());
+        };
+        let thread = std::thread::current().id();
+        let local = Arc::new(JsBusyCallback {
+            env: env.raw(),
+            function: unsafe { FunctionRef::from_napi_value(env.raw(), callback.raw())? },
+        });
+        // The callback is called from the context of the threadsafe function
+        // rather than by it, which would treat an exception as fatal.
+        let weak = Arc::downgrade(&local);
+        let noop =
+            env.create_function_from_closure("busyHandler", |ctx| ctx.env.get_undefined())?;
+        let mut tsfn: ThreadsafeFunction<(u32, SyncSender<bool>), ErrorStrategy::Fatal> = noop
+            .create_threadsafe_function(
+                0,
+                move |ctx: ThreadSafeCallContext<(u32, SyncSender<bool>)>| {
+                    let (retries, tx) = ctx.value;
+                    let retry = weak.upgrade().is_some_and(|local| {
+                        local.call(retries).unwrap_or_else(|err| {
+                            ctx.env.fatal_exception(err);
+                            false
+                        })
+                    });
+                    let _ = tx.send(retry);
+                    Ok(Vec::<JsUnknown>::new())
+                },
+            )?;
+        // A busy handler must not keep the process alive.
+        tsfn.unref(&env)?;
+        let weak = Arc::downgrade(&local);
+        busy::set_busy_handler(
+            raw,
+            Some(busy::BusyHandler::Callback(Box::new(move |retries| {
+                if std::thread::current().id() == thread {
+                    // SQLite waits on the JavaScript thread, in the blocking
+                    // API, so the callback cannot be queued: call it directly.
+                    let Some(local) = weak.upgrade() else {
+                        return false;
+                    };
+                    let env = local.env;
+                    return within_callback("a busy handler", || local.call(retries))
+                        .unwrap_or_else(|err| {
+                            // SAFETY: this is the thread of `env`. The exception
+                            // stays pending until the blocking call returns,
+                            // which throws it in place of its own error.
+                            unsafe { napi::JsError::from(err).throw_into(env) };
+                            false
+                        });
+                }
+                let (tx, rx) = std::sync::mpsc::sync_channel(1);
+                let status = tsfn.call((retries, tx), ThreadsafeFunctionCallMode::NonBlocking);
+                status == napi::Status::Ok
+                    && rx.recv_timeout(BUSY_CALLBACK_TIMEOUT).unwrap_or(false)
+            }))),
+        );
+        *self.busy_callback.lock().unwrap() = Some(local);
+        Ok(())
+    }
+
+    /// Makes operations that find the database locked retry with exponential
+    /// backoff and jitter, replacing the busy timeout and any busy handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment.
+    /// * `backoff` - The retry policy.
+    #[napi]
+    pub fn busy_backoff(&self, env: Env, backoff: Option<BusyBackoff>) -> Result<()> {
+        let raw = self.busy_raw(&env)?;
+        let backoff = backoff.map_or_else(busy::Backoff::default, Into::into);
+        busy::set_busy_handler(raw, Some(busy::BusyHandler::Backoff(backoff)));
+        *self.busy_callback.lock().unwrap() = None;
+        Ok(())
+    }
+
+    /// Returns statistics about the database connection.
+    #[napi]
+    pub fn stats(&self) -> DatabaseStats {
+        DatabaseStats {
+            queryTimeouts: self.state.timeout_counters.snapshot().into(),
+        }
+    }
+
+    /// Prepares the database to be closed gracefully: stops accepting new
+    /// operations, stops background syncs, letting a sync in progress
+    /// complete, and waits for the operations in flight and the iterators
+    /// not yet finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - Optional timeout after which the operations still running
+    ///   are interrupted and the open iterators finalized, before waiting for
+    ///   the interrupted operations to settle.
+    #[napi]
+    pub fn drain(&self, env: Env, opts: Option<CloseOptions>) -> Result<napi::JsObject> {
+        let conn = self.conn.clone();
+        let timeout = match opts.and_then(|o| o.timeout) {
+            Some(timeout) if !timeout.is_finite() || timeout < 0.0 => {
+                return Err(napi::Error::from_reason(
+                    "The `timeout` option must be a non-negative number",
+                ));
+            }
+            timeout => timeout.map(|timeout| Duration::from_millis(timeout as u64)),
+        };
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        // Queries running on the runtime can keep the future below from being
+        // polled, so the timer wheel interrupts them at the deadline.
+        let interrupt = conn
+            .as_ref()
+            .zip(timeout)
+            .map(|(conn, timeout)| QueryTimeoutManager::global().register(conn, timeout, None));
+        self.state.operations.stop();
+        let state = self.state.clone();
+        let background_sync = self.background_sync.clone();
+        let future = async move {
+            let Some(conn) = conn else {
+                return Ok(());
+            };
+            let _interrupt = interrupt;
+            let remaining = || {
+                deadline
+                    .map(|deadline| deadline.saturating_duration_since(std::time::Instant::now()))
+            };
+            background_sync.finish(remaining()).await;
+            let operations = &state.operations;
+            match remaining() {
+                Some(remaining) => {
+                    if tokio::time::timeout(remaining, operations.idle())
+                        .await
+                        .is_err()
+                    {
+                        conn.interrupt().map_err(Error::from)?;
+                        // Iterators that are not being stepped would keep
+                        // their executions going, so they are finalized, and
+                        // the operations that were interrupted are waited for.
+                        state.handles.close();
+                        operations.idle().await;
+                    }
+                }
+                None => operations.idle().await,
+            }
+            Ok(())
+        };
+        env.execute_tokio_future(future, |&mut _env, ()| Ok(()))
+    }
+
+    /// Closes the database connection.
+    #[napi]
+    pub fn close(&mut self) -> Result<()> {
+        self.background_sync.stop();
+        // Finalize the statements of the connection first, as SQLite cannot
+        // close a connection that still has statements.
+        self.state.handles.close();
+        self.raw = None;
+        self.conn = None;
+        self.db = None;
+        Ok(())
+    }
+
+    /// Sets the default safe integers mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `toggle` - Whether to use safe integers by default.
+    #[napi]
+    pub fn defaultSafeIntegers(&self, toggle: Option<bool>) -> Result<()> {
+        self.default_safe_integers
+            .store(toggle.unwrap_or(true), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Sets the default query timeout of the connection. The timeout applies
+    /// to operations started afterwards, including those of statements
+    /// already prepared on the connection that have no timeout of their own.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time in milliseconds that a query is allowed to
+    ///   run, or 0 (or no value) for no timeout.
+    #[napi]
+    pub fn set_default_query_timeout(&self, timeout: Option<f64>) -> Result<()> {
+        self.state
+            .default_query_timeout
+            .set(timeout.and_then(query_timeout_duration));
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Starts an operation on the connection, unless it is closed or
+    /// closing.
+    fn begin(&self) -> Result<(Arc<libsql::Connection>, operations::Operation)> {
+        self.conn
+            .clone()
+            .zip(self.state.operations.start())
+            .ok_or_else(|| {
+                throw_sqlite_error(
+                    "The database connection is not open".to_string(),
+                    "SQLITE_NOTOPEN".to_string(),
+                    0,
+                )
+            })
+    }
+
+    async fn sync_with_progress(
+        &self,
+        opts: Option<SyncOptions>,
+        report: Option<impl FnMut(SyncProgress)>,
+    ) -> Result<SyncResult> {
+        let _operation = self.begin()?;
+        let db = match &self.db {
+            Some(db) => db,
+            None => {
+                return Err(throw_sqlite_error(
+                    "The database connection is not open".to_string(),
+                    "SQLITE_NOTOPEN".to_string(),
+                    0,
+                ));
+            }
+        };
+        let timeout = opts
+            .and_then(|o| o.timeout)
+            .and_then(query_timeout_duration);
+        let started = std::time::Instant::now();
+        let start = replica::Position::read(&self.path);
+        let sync = sync_reporting_progress(db, &self.path, start, report);
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, sync).await.map_err(|_| {
+                throw_sqlite_error(
+                    format!("sync timed out after {} ms", timeout.as_millis()),
+                    "SQLITE_SYNC_TIMEOUT".to_string(),
+                    libsql::ffi::SQLITE_INTERRUPT,
+                )
+            }),
+            None => Ok(sync.await),
+        }
+        .and_then(|result| result.map_err(|err| Error::from(err).into()));
+        self.background_sync.record(&sync_outcome(result.as_ref()));
+        let result = result?;
+        let end = replica::Position::read(&self.path);
+        Ok(SyncResult {
+            frames_synced: result.frames_synced() as f64,
+            replication_index: result.frame_no().unwrap_or(0) as f64,
+            duration: started.elapsed().as_secs_f64() * 1000.0,
+            bootstrapped: end.bootstrapped_from(&start),
+            generation: end.generation.map(f64::from),
+        })
+    }
+
+    fn offline_raw(&self) -> Result<&raw::RawConnection> {
+        if self.conn.is_none() {
+            return Err(throw_sqlite_error(
+                "The database connection is not open".to_string(),
+                "SQLITE_NOTOPEN".to_string(),
+                0,
+            ));
+        }
+        match &self.raw {
+            Some(raw) if self.offline => Ok(raw),
+            _ => Err(napi::Error::from_reason(
+                "Pushing and pulling are only supported for offline replicas, set the `offline` option",
+            )),
+        }
+    }
+
+    fn set_sync_paused(&self, paused: bool) -> Result<()> {
+        if !self.background_sync.is_periodic() {
+            return Err(napi::Error::from_reason(
+                "Background sync is not enabled, set the `syncPeriod` option to enable it",
+            ));
+        }
+        self.background_sync.set_paused(paused);
+        Ok(())
+    }
+
+    fn busy_raw(&self, env: &Env) -> Result<&raw::RawConnection> {
+        if self.conn.is_none() {
+            return Err(throw_database_closed_error(env));
+        }
+        self.raw.as_deref().ok_or_else(|| {
+            napi::Error::from_reason("Busy handlers are only supported for local databases")
+        })
+    }
+}
+
+/// A JavaScript busy handler, for calling it directly on the JavaScript
+/// thread. The database owns it, so that the function reference is released
+/// on that thread.
+struct JsBusyCallback {
+    env: napi::sys::napi_env,
+    function: FunctionRef<u32, JsUnknown>,
+}
+
+// SAFETY: the environment and the function reference are only used, and
+// released, on the JavaScript thread they belong to.
+unsafe impl Send for JsBusyCallback {}
+unsafe impl Sync for JsBusyCallback {}
+
+impl JsBusyCallback {
+    /// Calls the function on its thread. An exception it throws is returned
+    /// as the error.
+    fn call(&self, retries: u32) -> Result<bool> {
+        let env = unsafe { Env::from_raw(self.env) };
+        self.function
+            .borrow_back(&env)
+            .and_then(|function| function.call(retries))
+            .and_then(|retry| retry.coerce_to_bool()?.get_value())
+    }
+}
+
+/// How long an operation running on the runtime waits for the JavaScript
+/// thread to call its busy handler before it stops retrying.
+const BUSY_CALLBACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn int_to_authorization(val: i32) -> Result<libsql::Authorization> {
+    match val {
+        0 => Ok(libsql::Authorization::Allow),
+        1 => Ok(libsql::Authorization::Deny),
+        2 => Ok(libsql::Authorization::Ignore),
+        _ => Err(napi::Error::from_reason(format!(
+            "Invalid authorization value '{val}'. Expected 0 (ALLOW), 1 (DENY), or 2 (IGNORE).",
+        ))),
+    }
+}
+
+/// Parse legacy `{ tableName: 0|1 }` format.
+fn parse_legacy_config(obj: &napi::JsObject) -> Result<crate::auth::Authorizer> {
+    let mut builder = crate::auth::AuthorizerBuilder::new();
+    let prop_names = obj.get_property_names()?;
+    let len = prop_names.get_array_length()?;
+    for idx in 0..len {
+        let key_js: napi::JsString = prop_names.get_element::<napi::JsString>(idx)?;
+        let key = key_js.into_utf8()?.into_owned()?;
+        let value_js: napi::JsNumber = obj.get_named_property(&key)?;
+        let value = value_js.get_int32()?;
         match value {
             0 => {
                 builder.allow(&key);
@@ -779,39 +2181,659 @@ fn parse_pattern(val: JsUnknown, field_name: &str) -> Result<crate::auth::Patter
                 Ok(crate::auth::PatternMatcher::Glob(owned))
             } else {
                 Err(napi::Error::from_reason(format!(
-                    "{} must be a string or {{ glob: \"pattern\" }}",
-                    field_name
+                    "{field_name} must be a string or {{ glob: \"pattern\" }}"
                 )))
             }
         }
         _ => Err(napi::Error::from_reason(format!(
-            "{} must be a string or {{ glob: \"pattern\" }}",
-            field_name
+            "{field_name} must be a string or {{ glob: \"pattern\" }}"
         ))),
     }
 }
 
-/// Result of a database sync operation.
-#[napi(object)]
-pub struct SyncResult {
-    /// The number of frames synced.
-    pub frames_synced: f64,
-    /// The replication index.
-    pub replication_index: f64,
+/// Checkpoint options.
+#[napi(object)]
+pub struct CheckpointOptions {
+    // Checkpoint mode: "passive" (the default), "full", "restart" or "truncate".
+    pub mode: Option<String>,
+    // Name of the attached database to checkpoint, or all of them if not set.
+    pub schema: Option<String>,
+}
+
+/// Close options.
+#[napi(object)]
+pub struct CloseOptions {
+    // Maximum time in milliseconds to wait for operations in flight before
+    // interrupting them.
+    pub timeout: Option<f64>,
+}
+
+/// Encryption at rest of a database.
+#[napi(object)]
+pub struct EncryptionInfo {
+    /// Whether the database is encrypted.
+    pub encrypted: bool,
+    /// The cipher the database is encrypted with.
+    pub cipher: Option<String>,
+}
+
+/// Encryption key change options.
+#[napi(object)]
+pub struct RekeyOptions {
+    // Encryption cipher to re-encrypt the database with.
+    pub cipher: Option<String>,
+}
+
+/// Attach options.
+#[napi(object)]
+pub struct AttachOptions {
+    // Encryption key of the attached database.
+    pub encryptionKey: Option<String>,
+    // Whether to attach the database read-only.
+    pub readonly: Option<bool>,
+}
+
+/// A database of a connection.
+#[napi(object)]
+pub struct DatabaseEntry {
+    /// The schema name of the database.
+    pub name: String,
+    /// The path of the database file, empty for in-memory and temporary
+    /// databases.
+    pub file: String,
+    /// Whether the database is read-only.
+    pub readonly: bool,
+}
+
+/// A schema migration.
+#[napi(object)]
+pub struct Migration {
+    // Unique identifier of the migration.
+    pub id: String,
+    // SQL statements that apply the migration.
+    pub up: String,
+}
+
+/// Migration options.
+#[napi(object)]
+pub struct MigrateOptions {
+    // Name of the table that records the applied migrations, "_migrations"
+    // by default.
+    pub table: Option<String>,
+}
+
+/// Result of a migration.
+#[napi(object)]
+pub struct MigrateResult {
+    /// The ids of the migrations applied, in order.
+    pub applied: Vec<String>,
+    /// The error an embedded replica failed to sync the applied migrations
+    /// from the primary with, if it did, encoded as the reason of a thrown
+    /// error for the JavaScript layer to convert.
+    pub sync_error: Option<String>,
+}
+
+/// Schema introspection options.
+#[napi(object)]
+pub struct SchemaOptions {
+    // Name of the attached database to read the schema of, "main" by default.
+    pub schema: Option<String>,
+}
+
+/// The objects of a database, in the order they were created.
+#[napi(object)]
+pub struct DatabaseSchema {
+    /// The ordinary tables.
+    pub tables: Vec<SchemaTable>,
+    /// The views.
+    pub views: Vec<SchemaView>,
+    /// The virtual tables.
+    pub virtual_tables: Vec<SchemaView>,
+    /// The indexes created with `CREATE INDEX`.
+    pub indexes: Vec<SchemaIndex>,
+    /// The triggers.
+    pub triggers: Vec<SchemaTrigger>,
+}
+
+/// An ordinary table.
+#[napi(object)]
+pub struct SchemaTable {
+    /// The name of the table.
+    pub name: String,
+    /// The `CREATE TABLE` statement.
+    pub sql: Option<String>,
+    /// Whether the table is a `STRICT` table.
+    pub strict: bool,
+    /// Whether the table is a `WITHOUT ROWID` table.
+    pub without_rowid: bool,
+    /// The columns, including generated ones.
+    pub columns: Vec<SchemaColumn>,
+    /// The foreign key constraints.
+    pub foreign_keys: Vec<SchemaForeignKey>,
+    /// The indexes, including those of `UNIQUE` and `PRIMARY KEY`
+    /// constraints.
+    pub indexes: Vec<SchemaTableIndex>,
+}
+
+/// A view, or a virtual table.
+#[napi(object)]
+pub struct SchemaView {
+    /// The name of the view or virtual table.
+    pub name: String,
+    /// The statement that created it.
+    pub sql: Option<String>,
+    /// The columns, including hidden ones.
+    pub columns: Vec<SchemaColumn>,
+}
+
+/// A column of a table or view.
+#[napi(object)]
+pub struct SchemaColumn {
+    /// The name of the column.
+    pub name: String,
+    /// The declared type, empty if the column has none.
+    #[napi(js_name = "type")]
+    pub declared_type: String,
+    /// Whether the column has a `NOT NULL` constraint.
+    pub not_null: bool,
+    /// The SQL text of the default value.
+    pub default_value: Option<String>,
+    /// The position of the column in the primary key, starting at 1, or 0 if
+    /// it is not part of it.
+    pub primary_key: u32,
+    /// Whether the column is a hidden column of a virtual table.
+    pub hidden: bool,
+    /// `"virtual"` or `"stored"` for generated columns.
+    pub generated: Option<String>,
+}
+
+/// A foreign key constraint.
+#[napi(object)]
+pub struct SchemaForeignKey {
+    /// The referenced table.
+    pub table: String,
+    /// The columns of the constraint.
+    pub from: Vec<String>,
+    /// The referenced columns, `null` for the primary key of the referenced
+    /// table.
+    pub to: Vec<Option<String>>,
+    /// The `ON UPDATE` action.
+    pub on_update: String,
+    /// The `ON DELETE` action.
+    pub on_delete: String,
+    /// The `MATCH` clause.
+    #[napi(js_name = "match")]
+    pub match_: String,
+}
+
+/// An index of a table.
+#[napi(object)]
+pub struct SchemaTableIndex {
+    /// The name of the index.
+    pub name: String,
+    /// Whether the index is unique.
+    pub unique: bool,
+    /// `"c"` for indexes created with `CREATE INDEX`, `"u"` for `UNIQUE`
+    /// constraints and `"pk"` for `PRIMARY KEY` constraints.
+    pub origin: String,
+    /// Whether the index is a partial index.
+    pub partial: bool,
+    /// The key columns of the index.
+    pub columns: Vec<SchemaIndexColumn>,
+}
+
+/// A key column of an index.
+#[napi(object)]
+pub struct SchemaIndexColumn {
+    /// The name of the indexed column, unset for expressions.
+    pub name: Option<String>,
+    /// Whether the column is sorted in descending order.
+    pub descending: bool,
+    /// The collating sequence of the column.
+    pub collation: String,
+}
+
+/// An index created with `CREATE INDEX`.
+#[napi(object)]
+pub struct SchemaIndex {
+    /// The name of the index.
+    pub name: String,
+    /// The table the index is on.
+    pub table: String,
+    /// The `CREATE INDEX` statement.
+    pub sql: Option<String>,
+}
+
+/// A trigger.
+#[napi(object)]
+pub struct SchemaTrigger {
+    /// The name of the trigger.
+    pub name: String,
+    /// The table or view the trigger is on.
+    pub table: String,
+    /// The `CREATE TRIGGER` statement.
+    pub sql: Option<String>,
+}
+
+impl From<schema::Schema> for DatabaseSchema {
+    fn from(schema: schema::Schema) -> Self {
+        DatabaseSchema {
+            tables: schema.tables.into_iter().map(Into::into).collect(),
+            views: schema.views.into_iter().map(Into::into).collect(),
+            virtual_tables: schema.virtual_tables.into_iter().map(Into::into).collect(),
+            indexes: schema
+                .indexes
+                .into_iter()
+                .map(|index| SchemaIndex {
+                    name: index.name,
+                    table: index.table,
+                    sql: index.sql,
+                })
+                .collect(),
+            triggers: schema
+                .triggers
+                .into_iter()
+                .map(|trigger| SchemaTrigger {
+                    name: trigger.name,
+                    table: trigger.table,
+                    sql: trigger.sql,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<schema::Table> for SchemaTable {
+    fn from(table: schema::Table) -> Self {
+        SchemaTable {
+            name: table.name,
+            sql: table.sql,
+            strict: table.strict,
+            without_rowid: table.without_rowid,
+            columns: table.columns.into_iter().map(Into::into).collect(),
+            foreign_keys: table
+                .foreign_keys
+                .into_iter()
+                .map(|fk| SchemaForeignKey {
+                    table: fk.table,
+                    from: fk.from,
+                    to: fk.to,
+                    on_update: fk.on_update,
+                    on_delete: fk.on_delete,
+                    match_: fk.match_,
+                })
+                .collect(),
+            indexes: table
+                .indexes
+                .into_iter()
+                .map(|index| SchemaTableIndex {
+                    name: index.name,
+                    unique: index.unique,
+                    origin: index.origin,
+                    partial: index.partial,
+                    columns: index
+                        .columns
+                        .into_iter()
+                        .map(|column| SchemaIndexColumn {
+                            name: column.name,
+                            descending: column.descending,
+                            collation: column.collation,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<schema::View> for SchemaView {
+    fn from(view: schema::View) -> Self {
+        SchemaView {
+            name: view.name,
+            sql: view.sql,
+            columns: view.columns.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<schema::Column> for SchemaColumn {
+    fn from(column: schema::Column) -> Self {
+        SchemaColumn {
+            name: column.name,
+            declared_type: column.declared_type,
+            not_null: column.not_null,
+            default_value: column.default_value,
+            primary_key: column.primary_key,
+            hidden: column.hidden,
+            generated: column.generated.map(str::to_string),
+        }
+    }
+}
+
+/// Result of a checkpoint.
+#[napi(object)]
+pub struct CheckpointResult {
+    /// Whether readers or writers kept the checkpoint from completing.
+    pub busy: bool,
+    /// The number of frames in the write-ahead log.
+    pub log_frames: f64,
+    /// The number of frames checkpointed into the database.
+    pub checkpointed_frames: f64,
+}
+
+impl From<wal::Checkpoint> for CheckpointResult {
+    fn from(checkpoint: wal::Checkpoint) -> Self {
+        CheckpointResult {
+            busy: checkpoint.busy,
+            log_frames: checkpoint.log_frames as f64,
+            checkpointed_frames: checkpoint.checkpointed_frames as f64,
+        }
+    }
+}
+
+/// Sync options.
+#[napi(object)]
+pub struct SyncOptions {
+    // Maximum number of milliseconds the sync is allowed to take.
+    pub timeout: Option<f64>,
+}
+
+/// Progress of a database sync operation.
+#[napi(object)]
+pub struct SyncProgress {
+    /// The number of frames applied so far.
+    pub frames_synced: f64,
+    /// The number of bytes of frames applied so far.
+    pub bytes: f64,
+    /// The generation being synced, with the v2 sync protocol.
+    pub generation: Option<f64>,
+}
+
+impl SyncProgress {
+    fn new(frames: u64, position: &replica::Position, path: &str) -> Self {
+        let frame_size = replica::frame_size(replica::page_size(path));
+        SyncProgress {
+            frames_synced: frames as f64,
+            bytes: (frames * frame_size) as f64,
+            generation: position.generation.map(f64::from),
+        }
+    }
+}
+
+/// How often a sync in progress checks how far it got.
+const SYNC_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Syncs `db`, notifying `report` of the frames applied so far at the start,
+/// whenever the replica at `path` moves past a commit, and at the end.
+///
+/// The sync runs on a task of its own, which is never dropped: a caller that
+/// stops waiting for it, on timeout, does not abandon libSQL half way through
+/// applying frames.
+async fn sync_reporting_progress(
+    db: &Arc<libsql::Database>,
+    path: &str,
+    start: replica::Position,
+    report: Option<impl FnMut(SyncProgress)>,
+) -> libsql::Result<libsql::replication::Replicated> {
+    let task = {
+        let db = db.clone();
+        tokio::spawn(async move { db.sync().await })
+    };
+    let sync = async {
+        match task.await {
+            Ok(result) => result,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    };
+    let Some(mut report) = report else {
+        return sync.await;
+    };
+    report(SyncProgress::new(0, &start, path));
+    let (mut last, mut frames) = (start, 0);
+    let mut check = |report: &mut dyn FnMut(SyncProgress)| {
+        let position = replica::Position::read(path);
+        if position != last {
+            frames += position.frames_since(&last);
+            last = position;
+            report(SyncProgress::new(frames, &last, path));
+        }
+    };
+    tokio::pin!(sync);
+    let mut ticks = tokio::time::interval(SYNC_PROGRESS_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut sync => break result,
+            _ = ticks.tick() => check(&mut report),
+        }
+    };
+    check(&mut report);
+    result
+}
+
+/// Local changes of an offline replica.
+#[napi(object)]
+pub struct PendingChanges {
+    /// The number of write-ahead log frames.
+    pub frames: f64,
+    /// The number of transactions.
+    pub transactions: f64,
+}
+
+impl From<replica::Pending> for PendingChanges {
+    fn from(pending: replica::Pending) -> Self {
+        PendingChanges {
+            frames: pending.frames as f64,
+            transactions: pending.transactions as f64,
+        }
+    }
+}
+
+/// What is known about the syncs of a database.
+#[napi(object)]
+pub struct SyncStatus {
+    /// When the last successful sync completed, in milliseconds since the
+    /// Unix epoch.
+    pub last_success: Option<f64>,
+    /// The error of the last sync, unless it succeeded.
+    pub last_error: Option<String>,
+    /// The number of frames synced so far.
+    pub frames_synced: f64,
+    /// When the next background sync is due, in milliseconds since the Unix
+    /// epoch.
+    pub next_sync: Option<f64>,
+    /// Whether background syncs are paused.
+    pub paused: bool,
+}
+
+impl From<background_sync::Status> for SyncStatus {
+    fn from(status: background_sync::Status) -> Self {
+        let millis = |time: std::time::SystemTime| {
+            time.duration_since(std::time::UNIX_EPOCH)
+                .map_or(0.0, |since| since.as_secs_f64() * 1000.0)
+        };
+        SyncStatus {
+            last_success: status.last_success.map(millis),
+            last_error: status.last_error,
+            frames_synced: status.frames_synced as f64,
+            next_sync: status.next_sync.map(millis),
+            paused: status.paused,
+        }
+    }
+}
+
+fn sync_outcome(
+    result: std::result::Result<&libsql::replication::Replicated, &napi::Error>,
+) -> background_sync::Outcome {
+    result
+        .map(|replicated| replicated.frames_synced() as u64)
+        .map_err(|err| err.reason.clone())
+}
+
+/// Result of a database sync operation.
+#[napi(object)]
+pub struct SyncResult {
+    /// The number of frames synced.
+    pub frames_synced: f64,
+    /// The replication index.
+    pub replication_index: f64,
+    /// The number of milliseconds the sync took.
+    pub duration: f64,
+    /// Whether the sync downloaded the database from scratch.
+    pub bootstrapped: bool,
+    /// The generation synced to, with the v2 sync protocol.
+    pub generation: Option<f64>,
+}
+
+/// Prepares a statement in blocking mode.
+#[napi]
+pub fn database_prepare_sync(
+    db: &Database,
+    sql: String,
+    query_options: Option<QueryOptions>,
+) -> Result<Statement> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.prepare(sql, query_options).await })
+}
+
+/// Syncs the database in blocking mode.
+#[napi]
+pub fn database_sync_sync(
+    env: Env,
+    db: &Database,
+    opts: Option<SyncOptions>,
+    progress: Option<napi::JsFunction>,
+) -> Result<SyncResult> {
+    let rt = runtime()?;
+    let Some(callback) = progress else {
+        return rt.block_on(db.sync_with_progress(opts, None::<fn(SyncProgress)>));
+    };
+    // JavaScript must not run inside `block_on`, where a callback that uses
+    // the blocking API would enter the runtime again. The sync is driven in
+    // slices instead, each ending with the next progress report, which is
+    // delivered between slices.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let report = move |progress| {
+        let _ = tx.send(progress);
+    };
+    let mut sync = std::pin::pin!(db.sync_with_progress(opts, Some(report)));
+    loop {
+        let (progress, result) = rt.block_on(async {
+            tokio::select! {
+                biased;
+                Some(progress) = rx.recv() => (Some(progress), None),
+                result = &mut sync => (None, Some(result)),
+            }
+        });
+        // Pending reports are delivered before the result of the sync.
+        for progress in progress
+            .into_iter()
+            .chain(std::iter::from_fn(|| rx.try_recv().ok()))
+        {
+            // The JavaScript wrappers catch exceptions thrown by the callback.
+            let _ = unsafe { SyncProgress::to_napi_value(env.raw(), progress) }.and_then(|value| {
+                callback.call(
+                    None,
+                    &[unsafe { JsUnknown::from_raw_unchecked(env.raw(), value) }],
+                )
+            });
+        }
+        if let Some(result) = result {
+            return result;
+        }
+    }
+}
+
+/// Pushes the local changes of an offline replica in blocking mode.
+#[napi]
+pub fn database_push_sync(db: &Database, opts: Option<SyncOptions>) -> Result<PendingChanges> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.push(opts).await })
+}
+
+/// Pulls the changes of the primary into an offline replica in blocking mode.
+#[napi]
+pub fn database_pull_sync(db: &Database, opts: Option<SyncOptions>) -> Result<SyncResult> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.pull(opts).await })
+}
+
+/// Checkpoints the write-ahead log in blocking mode.
+#[napi]
+pub fn database_checkpoint_sync(
+    db: &Database,
+    opts: Option<CheckpointOptions>,
+) -> Result<CheckpointResult> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.checkpoint(opts).await })
+}
+
+/// Changes the encryption key of the database in blocking mode.
+#[napi]
+pub fn database_rekey_sync(
+    db: &Database,
+    new_key: Option<String>,
+    opts: Option<RekeyOptions>,
+) -> Result<()> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.rekey(new_key, opts).await })
 }
 
-/// Prepares a statement in blocking mode.
+/// Attaches a database file in blocking mode.
 #[napi]
-pub fn database_prepare_sync(db: &Database, sql: String) -> Result<Statement> {
+pub fn database_attach_sync(
+    db: &Database,
+    path: String,
+    schema: String,
+    opts: Option<AttachOptions>,
+) -> Result<()> {
     let rt = runtime()?;
-    rt.block_on(async move { db.prepare(sql).await })
+    rt.block_on(async move { db.attach(path, schema, opts).await })
 }
 
-/// Syncs the database in blocking mode.
+/// Detaches a database in blocking mode.
+#[napi]
+pub fn database_detach_sync(db: &Database, schema: String) -> Result<()> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.detach(schema).await })
+}
+
+/// Applies schema migrations in blocking mode.
+#[napi]
+pub fn database_migrate_sync(
+    db: &Database,
+    migrations: Vec<Migration>,
+    opts: Option<MigrateOptions>,
+) -> Result<MigrateResult> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.migrate(migrations, opts).await })
+}
+
+/// Returns the schema of a database in blocking mode.
+#[napi]
+pub fn database_schema_sync(db: &Database, opts: Option<SchemaOptions>) -> Result<DatabaseSchema> {
+    let rt = runtime()?;
+    rt.block_on(async move { db.schema(opts).await })
+}
+
+/// Returns the databases of the connection in blocking mode.
 #[napi]
-pub fn database_sync_sync(db: &Database) -> Result<SyncResult> {
+pub fn database_databases_sync(db: &Database) -> Result<Vec<DatabaseEntry>> {
     let rt = runtime()?;
-    rt.block_on(async move { db.sync().await })
+    rt.block_on(async move { db.databases().await })
+}
+
+/// Executes a script statement by statement in blocking mode.
+#[napi]
+pub fn database_exec_multi_sync(
+    db: &Database,
+    env: Env,
+    sql: String,
+    query_options: Option<QueryOptions>,
+) -> Result<Vec<ScriptStatementResult>> {
+    let safe_ints = db.default_safe_integers.load(Ordering::SeqCst);
+    let future = db.exec_script(sql, query_options)?;
+    let (sql, statements) = runtime()?.block_on(future)?;
+    map_script_statements(&env, &sql, statements, safe_ints)
 }
 
 /// Executes SQL in blocking mode.
@@ -838,7 +2860,9 @@ fn throw_database_closed_error(env: &Env) -> napi::Error {
 
 fn query_timeout_duration(timeout_ms: f64) -> Option<Duration> {
     if timeout_ms.is_finite() && timeout_ms > 0.0 {
-        Some(Duration::from_millis(timeout_ms as u64))
+        // Rounded up, as a timeout under a millisecond would otherwise become
+        // 0, which the settings take to mean no timeout.
+        Some(Duration::from_millis(timeout_ms.ceil() as u64))
     } else {
         None
     }
@@ -851,18 +2875,149 @@ fn is_sqlite_interrupt(err: &libsql::Error) -> bool {
     )
 }
 
-
 /// Register a query timeout against `target` for the operation about to run,
 /// returning a guard that cancels the timeout when dropped. The target is the
 /// statement for per-statement operations (so the timer interrupts only that
 /// statement, leaving concurrent operations on the same connection untouched)
-/// or the connection for connection-wide operations. Returns `None` when no
-/// timeout is in effect.
+/// or the connection for connection-wide operations. The operation is
+/// accounted for in the statistics of the connection it runs on. Returns
+/// `None` when no timeout is in effect.
 fn register_timeout<T: query_timeout::Interruptible + 'static>(
     target: &Arc<T>,
     timeout: Option<Duration>,
+    state: &ConnectionState,
 ) -> Option<QueryTimeoutGuard> {
-    timeout.map(|t| QueryTimeoutManager::global().register(target, t))
+    timeout
+        .map(|t| QueryTimeoutManager::global().register(target, t, Some(&state.timeout_counters)))
+}
+
+/// Converts a `maxSteps` query option into a step budget, if one is set.
+fn step_budget(max_steps: f64) -> Option<u64> {
+    if max_steps.is_finite() && max_steps >= 1.0 {
+        Some(max_steps as u64)
+    } else {
+        None
+    }
+}
+
+/// The limits that apply to one operation.
+#[derive(Clone, Copy)]
+struct Limits {
+    // Maximum time the operation is allowed to run.
+    timeout: Option<Duration>,
+    // Maximum number of VM instructions the operation is allowed to execute.
+    max_steps: Option<u64>,
+}
+
+impl Limits {
+    /// Resolves the limits of an operation from its query options, falling
+    /// back to `default_timeout` when no timeout is given. Step budgets are
+    /// rejected on connections without a progress handler, i.e. remote ones.
+    fn resolve(
+        default_timeout: Option<Duration>,
+        query_options: Option<QueryOptions>,
+        state: &ConnectionState,
+    ) -> Result<Self> {
+        let (timeout_ms, max_steps) =
+            query_options.map_or((None, None), |o| (o.queryTimeout, o.maxSteps));
+        let max_steps = max_steps.and_then(step_budget);
+        if max_steps.is_some() && state.progress.is_none() {
+            return Err(napi::Error::from_reason(
+                "The `maxSteps` option is only supported for local databases",
+            ));
+        }
+        Ok(Limits {
+            timeout: match timeout_ms {
+                Some(timeout_ms) => query_timeout_duration(timeout_ms),
+                None => default_timeout,
+            },
+            max_steps,
+        })
+    }
+
+    /// Arms the limits against `target` for the operation about to run (see
+    /// `register_timeout` for how the target is chosen). Step budgets are only
+    /// enforced on connections with a progress handler, i.e. local ones.
+    fn register<T: query_timeout::Interruptible + 'static>(
+        self,
+        target: &Arc<T>,
+        state: &ConnectionState,
+    ) -> LimitsGuard {
+        LimitsGuard {
+            timeout: register_timeout(target, self.timeout, state),
+            steps: self
+                .max_steps
+                .zip(state.progress.as_ref())
+                .map(|(max_steps, progress)| progress.register(target, max_steps)),
+        }
+    }
+}
+
+/// Per-connection state shared by a `Database` and the statements prepared
+/// on it.
+struct ConnectionState {
+    // The progress handler of the connection, for local databases.
+    progress: Option<progress::Progress>,
+    // Query timeout statistics for the operations run on the connection.
+    timeout_counters: Arc<TimeoutCounters>,
+    // Maximum time that a query is allowed to run, unless overridden.
+    default_query_timeout: QueryTimeoutSetting,
+    // The libSQL objects of the statements and iterators of the connection.
+    handles: handles::Registry,
+    // The operations in flight on the connection.
+    operations: Arc<operations::Operations>,
+    // Whether missing and unknown named parameters are errors.
+    strict_params: bool,
+}
+
+/// A query timeout that can be changed while operations run. Changes apply
+/// to the operations started afterwards.
+struct QueryTimeoutSetting(AtomicU64);
+
+impl QueryTimeoutSetting {
+    // Sentinel for a setting that defers to the connection default.
+    const UNSET: u64 = u64::MAX;
+
+    fn new(timeout: Option<Duration>) -> Self {
+        let setting = QueryTimeoutSetting(AtomicU64::new(Self::UNSET));
+        setting.set(timeout);
+        setting
+    }
+
+    fn unset() -> Self {
+        QueryTimeoutSetting(AtomicU64::new(Self::UNSET))
+    }
+
+    /// Sets the timeout, where `None` means no timeout.
+    fn set(&self, timeout: Option<Duration>) {
+        let millis = timeout.map_or(0, |t| (t.as_millis() as u64).clamp(1, Self::UNSET - 1));
+        self.0.store(millis, Ordering::SeqCst);
+    }
+
+    /// Makes the setting defer to the connection default again.
+    fn reset(&self) {
+        self.0.store(Self::UNSET, Ordering::SeqCst);
+    }
+
+    /// Returns the timeout in effect, if any.
+    fn get(&self) -> Option<Duration> {
+        self.get_or(None)
+    }
+
+    /// Returns the timeout in effect, falling back to `default` when unset.
+    fn get_or(&self, default: Option<Duration>) -> Option<Duration> {
+        match self.0.load(Ordering::SeqCst) {
+            Self::UNSET => default,
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+}
+
+/// The limits armed for one operation, disarmed when dropped.
+struct LimitsGuard {
+    timeout: Option<QueryTimeoutGuard>,
+    steps: Option<progress::StepBudgetGuard>,
 }
 
 async fn clear_stale_interrupt(conn: &Arc<libsql::Connection>) {
@@ -880,16 +3035,26 @@ async fn clear_stale_interrupt(conn: &Arc<libsql::Connection>) {
 /// SQLite statement object.
 #[napi]
 pub struct Statement {
-    // The libSQL connection instance.
-    conn: Arc<libsql::Connection>,
-    // The libSQL statement instance.
-    stmt: Arc<libsql::Statement>,
+    // The libSQL connection and statement instances, until the database is
+    // closed.
+    prepared: Arc<handles::Slot<Prepared>>,
+    // The SQL text the statement was prepared from.
+    sql: Arc<str>,
     // The column names.
     column_names: Vec<std::ffi::CString>,
     // The access mode.
     mode: AccessMode,
-    // Maximum time in milliseconds that a query is allowed to run.
-    query_timeout: Option<Duration>,
+    // Maximum time that a query is allowed to run, if set for the statement.
+    query_timeout: QueryTimeoutSetting,
+    // State shared with the database the statement was prepared on.
+    state: Arc<ConnectionState>,
+    // The number of executions of the statement in progress.
+    executions: Arc<AtomicUsize>,
+    // Whether the statement makes no direct change to the database, for
+    // statements of local databases.
+    readonly: Option<bool>,
+    // The parameters bound to the statement with `bind()`, if any.
+    bound: std::sync::OnceLock<libsql::params::Params>,
 }
 
 #[napi]
@@ -900,25 +3065,40 @@ impl Statement {
     ///
     /// * `conn` - The connection instance.
     /// * `stmt` - The libSQL statement instance.
+    /// * `raw` - The raw SQLite handle of the connection, for local databases.
+    /// * `sql` - The SQL text the statement was prepared from.
     /// * `mode` - The access mode.
     pub(crate) fn new(
         conn: Arc<libsql::Connection>,
         stmt: libsql::Statement,
+        raw: Option<Arc<raw::RawConnection>>,
+        sql: String,
         mode: AccessMode,
-        query_timeout: Option<Duration>,
+        state: Arc<ConnectionState>,
     ) -> Self {
         let column_names: Vec<std::ffi::CString> = stmt
             .columns()
             .iter()
             .map(|c| std::ffi::CString::new(c.name().to_string()).unwrap())
             .collect();
-        let stmt = Arc::new(stmt);
-        Self {
+        // Read from the statement libSQL just prepared, before it is reset or
+        // another statement is prepared.
+        let readonly = raw.as_deref().and_then(|raw| raw.prepared_readonly(&sql));
+        let prepared = state.handles.register(Prepared {
             conn,
-            stmt,
+            stmt: Arc::new(stmt),
+            raw,
+        });
+        Self {
+            prepared,
+            sql: sql.into(),
             column_names,
             mode,
-            query_timeout,
+            query_timeout: QueryTimeoutSetting::unset(),
+            state,
+            executions: Arc::new(AtomicUsize::new(0)),
+            readonly,
+            bound: std::sync::OnceLock::new(),
         }
     }
 
@@ -934,17 +3114,21 @@ impl Statement {
         params: Option<napi::JsUnknown>,
         query_options: Option<QueryOptions>,
     ) -> Result<napi::JsObject> {
-        self.stmt.reset();
-        let params = map_params(&self.stmt, params)?;
-        let total_changes_before = self.conn.total_changes();
+        let (Prepared { conn, stmt, .. }, execution) = self.begin()?;
+        stmt.reset();
+        let params = self.params(&stmt, params)?;
+        let total_changes_before = conn.total_changes();
         let start = std::time::Instant::now();
-        let stmt = self.stmt.clone();
-        let conn = self.conn.clone();
-        let query_timeout = self.resolve_query_timeout(query_options);
+        let sql = self.sql.clone();
+        let limits = self.resolve_limits(query_options)?;
+        let state = self.state.clone();
 
         let future = async move {
-            let _timeout_guard = register_timeout(&stmt, query_timeout);
-            stmt.run(params).await.map_err(Error::from)?;
+            let _execution = execution;
+            let limits_guard = limits.register(&stmt, &state);
+            stmt.run(params)
+                .await
+                .map_err(|err| Error::with_sql(err, &sql).with_limits(&limits_guard))?;
             let changes = if conn.total_changes() == total_changes_before {
                 0
             } else {
@@ -980,7 +3164,11 @@ impl Statement {
         let pluck = self.mode.pluck.load(Ordering::SeqCst);
         let timed = self.mode.timing.load(Ordering::SeqCst);
 
-        let params = map_params(&self.stmt, params)?;
+        // Resetting also clears a pending interrupt left over from an earlier
+        // execution, such as one aborted just as it completed.
+        let (Prepared { stmt, .. }, execution) = self.begin()?;
+        stmt.reset();
+        let params = self.params(&stmt, params)?;
         let column_names = self.column_names.clone();
 
         let start = if timed {
@@ -989,15 +3177,23 @@ impl Statement {
             None
         };
 
-        let stmt = self.stmt.clone();
         let stmt_fut = stmt.clone();
-        let query_timeout = self.resolve_query_timeout(query_options);
+        let sql = self.sql.clone();
+        let limits = self.resolve_limits(query_options)?;
+        let state = self.state.clone();
         let future = async move {
+            let _execution = execution;
             let result: std::result::Result<(Option<libsql::Row>, Option<f64>), Error> = {
-                let _timeout_guard = register_timeout(&stmt_fut, query_timeout);
+                let limits_guard = limits.register(&stmt_fut, &state);
                 async {
-                    let mut rows = stmt_fut.query(params).await.map_err(Error::from)?;
-                    let row = rows.next().await.map_err(Error::from)?;
+                    let mut rows = stmt_fut
+                        .query(params)
+                        .await
+                        .map_err(|err| Error::with_sql(err, &sql).with_limits(&limits_guard))?;
+                    let row = rows
+                        .next()
+                        .await
+                        .map_err(|err| Error::with_sql(err, &sql).with_limits(&limits_guard))?;
                     let duration: Option<f64> = start.map(|start| start.elapsed().as_secs_f64());
                     Ok((row, duration))
                 }
@@ -1011,7 +3207,7 @@ impl Statement {
 
         env.execute_tokio_future(future, move |&mut env, (row, duration)| {
             let result =
-                Self::get_internal(&env, &row, &column_names, safe_ints, raw, pluck, duration);
+                Self::get_internal(&env, row, &column_names, safe_ints, raw, pluck, duration);
             stmt.reset();
             Ok(result)
         })
@@ -1019,7 +3215,7 @@ impl Statement {
 
     fn get_internal(
         env: &Env,
-        row: &Option<libsql::Row>,
+        row: Option<libsql::Row>,
         column_names: &[std::ffi::CString],
         safe_ints: bool,
         raw: bool,
@@ -1027,13 +3223,13 @@ impl Statement {
         duration: Option<f64>,
     ) -> Result<napi::JsUnknown> {
         match row {
-            Some(row) => {
+            Some(mut row) => {
                 if raw {
-                    let js_array = map_row_raw(&env, &column_names, &row, safe_ints, pluck)?;
+                    let js_array = map_row_raw(env, column_names, &mut row, safe_ints, pluck)?;
                     Ok(js_array.into_unknown())
                 } else {
                     let mut js_object =
-                        map_row_object(&env, &column_names, &row, safe_ints, pluck)?
+                        map_row_object(env, column_names, &mut row, safe_ints, pluck)?
                             .coerce_to_object()?;
                     if let Some(duration) = duration {
                         let mut metadata = env.create_object()?;
@@ -1067,34 +3263,47 @@ impl Statement {
         let safe_ints = self.mode.safe_ints.load(Ordering::SeqCst);
         let raw = self.mode.raw.load(Ordering::SeqCst);
         let pluck = self.mode.pluck.load(Ordering::SeqCst);
-        let stmt = self.stmt.clone();
+        let (Prepared { stmt, .. }, execution) = self.begin()?;
         stmt.reset();
-        let params = map_params(&stmt, params)?;
-        let stmt_for_query = self.stmt.clone();
-        let stmt_for_iter = stmt_for_query.clone();
-        let query_timeout = self.resolve_query_timeout(query_options);
+        let params = self.params(&stmt, params)?;
+        let stmt_for_query = stmt.clone();
+        let sql = self.sql.clone();
+        let sql_for_iter = sql.clone();
+        let limits = self.resolve_limits(query_options)?;
+        let state = self.state.clone();
         let future = async move {
-            let timeout_guard = register_timeout(&stmt_for_query, query_timeout);
-            let rows = stmt_for_query.query(params).await.map_err(Error::from)?;
-            Ok::<_, napi::Error>((rows, timeout_guard))
+            let limits_guard = limits.register(&stmt_for_query, &state);
+            let rows = stmt_for_query
+                .query(params)
+                .await
+                .map_err(|err| Error::with_sql(err, &sql).with_limits(&limits_guard))?;
+            Ok::<_, napi::Error>((rows, limits_guard, execution))
         };
         let column_names = self.column_names.clone();
-        env.execute_tokio_future(future, move |&mut _env, (result, timeout_guard)| {
-            Ok(RowsIterator::new(
-                Arc::new(tokio::sync::Mutex::new(result)),
-                stmt_for_iter,
-                column_names,
-                safe_ints,
-                raw,
-                pluck,
-                timeout_guard,
-            ))
-        })
+        let state = self.state.clone();
+        env.execute_tokio_future(
+            future,
+            move |&mut _env, (result, limits_guard, execution)| {
+                Ok(RowsIterator::new(
+                    state.handles.register(Cursor {
+                        rows: Arc::new(tokio::sync::Mutex::new(result)),
+                        stmt,
+                        execution: Arc::new(Mutex::new(Some(execution))),
+                    }),
+                    sql_for_iter,
+                    column_names,
+                    safe_ints,
+                    raw,
+                    pluck,
+                    limits_guard,
+                ))
+            },
+        )
     }
 
     #[napi]
     pub fn raw(&self, raw: Option<bool>) -> Result<&Self> {
-        let returns_data = !self.stmt.columns().is_empty();
+        let returns_data = !self.column_names.is_empty();
         if !returns_data {
             return Err(napi::Error::from_reason(
                 "The raw() method is only for statements that return data",
@@ -1122,7 +3331,8 @@ impl Statement {
 
     #[napi]
     pub fn columns(&self, env: Env) -> Result<Array> {
-        let columns = self.stmt.columns();
+        let stmt = self.prepared()?.stmt;
+        let columns = stmt.columns();
         let mut js_array = env.create_array(columns.len() as u32)?;
         for (i, col) in columns.iter().enumerate() {
             let mut js_obj = env.create_object()?;
@@ -1156,6 +3366,107 @@ impl Statement {
         Ok(js_array)
     }
 
+    /// Binds parameters to the statement for all its executions, which then
+    /// take no parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The parameters to bind to the statement.
+    #[napi]
+    pub fn bind(&self, params: Option<napi::JsUnknown>) -> Result<&Self> {
+        let stmt = self.prepared()?.stmt;
+        let params = map_params(&stmt, params, self.state.strict_params)?;
+        self.bound.set(params).map_err(|_| {
+            params_misuse_error("The bind() method can only be called once per statement")
+        })?;
+        Ok(self)
+    }
+
+    /// Returns the SQL text the statement was prepared from.
+    #[napi(getter)]
+    pub fn source(&self) -> String {
+        self.sql.to_string()
+    }
+
+    /// Returns whether the statement returns rows.
+    #[napi(getter)]
+    pub fn reader(&self) -> bool {
+        !self.column_names.is_empty()
+    }
+
+    /// Returns whether the statement makes no direct change to the database.
+    /// Statements of remote databases are not prepared locally, so they are
+    /// taken to change it.
+    #[napi(getter)]
+    pub fn readonly(&self) -> bool {
+        self.readonly.unwrap_or(false)
+    }
+
+    /// Returns whether the statement is executing, or has an iterator that is
+    /// not finished.
+    #[napi(getter)]
+    pub fn busy(&self) -> bool {
+        self.executions.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns the parameters of the statement, with their position, starting
+    /// at 1, and their name, prefix included, unless they are anonymous.
+    #[napi]
+    pub fn parameters(&self) -> Result<Vec<StatementParameter>> {
+        let stmt = self.prepared()?.stmt;
+        Ok((1..=stmt.parameter_count() as u32)
+            .map(|index| StatementParameter {
+                index,
+                name: stmt.parameter_name(index as i32).map(str::to_string),
+            })
+            .collect())
+    }
+
+    /// Returns the SQL text of the statement with the given parameters
+    /// expanded into it, or `None` for statements of remote databases, which
+    /// are not prepared locally.
+    ///
+    /// The statement is prepared again to bind the parameters, as the one
+    /// libSQL runs may be executing.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The parameters to expand.
+    #[napi]
+    pub fn expanded_sql(&self, params: Option<napi::JsUnknown>) -> Result<Option<String>> {
+        let prepared = self.prepared()?;
+        let params = self.params(&prepared.stmt, params)?;
+        let Some(raw) = self.raw_statement(&prepared)? else {
+            return Ok(None);
+        };
+        let bind_error = |rc| {
+            let raw = prepared.raw.as_deref().unwrap();
+            Error::from(libsql::Error::SqliteFailure(rc, raw.errmsg()))
+        };
+        match params {
+            libsql::params::Params::None => {}
+            libsql::params::Params::Positional(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    raw.bind(index as i32 + 1, value).map_err(bind_error)?;
+                }
+            }
+            libsql::params::Params::Named(values) => {
+                for (name, value) in &values {
+                    if let Some(index) = raw.parameter_index(name) {
+                        raw.bind(index, value).map_err(bind_error)?;
+                    }
+                }
+            }
+        }
+        raw.expanded_sql().map(Some).ok_or_else(|| {
+            Error::from(libsql::Error::SqliteFailure(
+                libsql::ffi::SQLITE_NOMEM,
+                "out of memory".to_string(),
+            ))
+            .into()
+        })
+    }
+
     #[napi]
     pub fn safeIntegers(&self, toggle: Option<bool>) -> Result<&Self> {
         self.mode
@@ -1164,20 +3475,116 @@ impl Statement {
         Ok(self)
     }
 
+    /// Sets the query timeout of the statement, overriding the default query
+    /// timeout of the connection for operations started afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time in milliseconds that a query is allowed to
+    ///   run, 0 for no timeout, or no value to use the connection default.
+    #[napi]
+    pub fn set_query_timeout(&self, timeout: Option<f64>) -> Result<&Self> {
+        match timeout {
+            Some(timeout) => self.query_timeout.set(query_timeout_duration(timeout)),
+            None => self.query_timeout.reset(),
+        }
+        Ok(self)
+    }
+
     #[napi]
     pub fn interrupt(&self) -> Result<()> {
-        self.stmt.interrupt().map_err(Error::from)?;
+        if let Some(prepared) = self.prepared.get() {
+            prepared.stmt.interrupt().map_err(Error::from)?;
+        }
         Ok(())
     }
 }
 
+/// The libSQL objects a statement runs on.
+#[derive(Clone)]
+struct Prepared {
+    conn: Arc<libsql::Connection>,
+    stmt: Arc<libsql::Statement>,
+    // The raw SQLite handle of the connection, for local databases.
+    raw: Option<Arc<raw::RawConnection>>,
+}
+
+/// An execution of a statement, from the time it starts until its result is
+/// read, or until its iterator is finished.
+struct Execution {
+    _operation: operations::Operation,
+    // The number of executions of the statement in progress.
+    executions: Arc<AtomicUsize>,
+}
+
+impl Drop for Execution {
+    fn drop(&mut self) {
+        self.executions.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 impl Statement {
-    fn resolve_query_timeout(&self, query_options: Option<QueryOptions>) -> Option<Duration> {
-        match query_options.and_then(|o| o.queryTimeout) {
-            Some(timeout_ms) => query_timeout_duration(timeout_ms),
-            None => self.query_timeout,
+    fn prepared(&self) -> Result<Prepared> {
+        self.prepared.get().ok_or_else(|| {
+            throw_sqlite_error(
+                "The database connection is not open".to_string(),
+                "SQLITE_NOTOPEN".to_string(),
+                0,
+            )
+        })
+    }
+
+    /// Starts an execution of the statement, unless the database is closed
+    /// or closing.
+    fn begin(&self) -> Result<(Prepared, Execution)> {
+        let prepared = self.prepared()?;
+        let operation = self.state.operations.start().ok_or_else(|| {
+            throw_sqlite_error(
+                "The database connection is not open".to_string(),
+                "SQLITE_NOTOPEN".to_string(),
+                0,
+            )
+        })?;
+        self.executions.fetch_add(1, Ordering::SeqCst);
+        let execution = Execution {
+            _operation: operation,
+            executions: self.executions.clone(),
+        };
+        Ok((prepared, execution))
+    }
+
+    /// Returns the parameters bound to the statement, or maps the given ones.
+    fn params(
+        &self,
+        stmt: &libsql::Statement,
+        params: Option<napi::JsUnknown>,
+    ) -> Result<libsql::params::Params> {
+        match self.bound.get() {
+            Some(_) if params.is_some() => Err(params_misuse_error(
+                "This statement already has bound parameters",
+            )),
+            Some(bound) => Ok(bound.clone()),
+            None => map_params(stmt, params, self.state.strict_params),
         }
     }
+
+    /// Prepares the statement again on the raw SQLite handle, for the
+    /// features that libSQL does not expose, unless the database is remote.
+    fn raw_statement(&self, prepared: &Prepared) -> Result<Option<raw::RawStatement>> {
+        let Some(raw) = prepared.raw.as_deref() else {
+            return Ok(None);
+        };
+        raw.prepare(&self.sql).map(Some).map_err(|rc| {
+            Error::with_sql(libsql::Error::SqliteFailure(rc, raw.errmsg()), &self.sql).into()
+        })
+    }
+
+    fn resolve_limits(&self, query_options: Option<QueryOptions>) -> Result<Limits> {
+        let timeout = self
+            .query_timeout
+            .get_or(self.state.default_query_timeout.get());
+        Limits::resolve(timeout, query_options, &self.state)
+    }
 }
 
 /// Gets first row from statement in blocking mode.
@@ -1200,13 +3607,25 @@ pub fn statement_get_sync(
     };
 
     let rt = runtime()?;
-    let query_timeout = stmt.resolve_query_timeout(query_options);
+    let limits = stmt.resolve_limits(query_options)?;
+    let (
+        Prepared {
+            stmt: inner_stmt, ..
+        },
+        _execution,
+    ) = stmt.begin()?;
     let result: Result<(Option<libsql::Row>, Option<f64>)> = {
-        rt.block_on(async move {
-            let _timeout_guard = register_timeout(&stmt.stmt, query_timeout);
-            let params = map_params(&stmt.stmt, params)?;
-            let mut rows = stmt.stmt.query(params).await.map_err(Error::from)?;
-            let row = rows.next().await.map_err(Error::from)?;
+        rt.block_on(async {
+            let limits_guard = limits.register(&inner_stmt, &stmt.state);
+            let params = stmt.params(&inner_stmt, params)?;
+            let mut rows = inner_stmt
+                .query(params)
+                .await
+                .map_err(|err| Error::with_sql(err, &stmt.sql).with_limits(&limits_guard))?;
+            let row = rows
+                .next()
+                .await
+                .map_err(|err| Error::with_sql(err, &stmt.sql).with_limits(&limits_guard))?;
             let duration: Option<f64> = start.map(|start| start.elapsed().as_secs_f64());
             Ok((row, duration))
         })
@@ -1215,18 +3634,18 @@ pub fn statement_get_sync(
         Ok((row, duration)) => {
             let mapped = Statement::get_internal(
                 &env,
-                &row,
+                row,
                 &stmt.column_names,
                 safe_ints,
                 raw,
                 pluck,
                 duration,
             );
-            stmt.stmt.reset();
+            inner_stmt.reset();
             mapped
         }
         Err(err) => {
-            stmt.stmt.reset();
+            inner_stmt.reset();
             Err(err)
         }
     }
@@ -1239,22 +3658,33 @@ pub fn statement_run_sync(
     params: Option<napi::JsUnknown>,
     query_options: Option<QueryOptions>,
 ) -> Result<RunResult> {
-    stmt.stmt.reset();
+    let (
+        Prepared {
+            conn,
+            stmt: inner_stmt,
+            ..
+        },
+        _execution,
+    ) = stmt.begin()?;
+    inner_stmt.reset();
     let rt = runtime()?;
-    let query_timeout = stmt.resolve_query_timeout(query_options);
+    let limits = stmt.resolve_limits(query_options)?;
     rt.block_on(async move {
-        let _timeout_guard = register_timeout(&stmt.stmt, query_timeout);
-        let params = map_params(&stmt.stmt, params)?;
-        let total_changes_before = stmt.conn.total_changes();
+        let limits_guard = limits.register(&inner_stmt, &stmt.state);
+        let params = stmt.params(&inner_stmt, params)?;
+        let total_changes_before = conn.total_changes();
         let start = std::time::Instant::now();
 
-        stmt.stmt.run(params).await.map_err(Error::from)?;
-        let changes = if stmt.conn.total_changes() == total_changes_before {
+        inner_stmt
+            .run(params)
+            .await
+            .map_err(|err| Error::with_sql(err, &stmt.sql).with_limits(&limits_guard))?;
+        let changes = if conn.total_changes() == total_changes_before {
             0
         } else {
-            stmt.conn.changes()
+            conn.changes()
         };
-        let last_insert_row_id = stmt.conn.last_insert_rowid();
+        let last_insert_row_id = conn.last_insert_rowid();
         let duration = start.elapsed().as_secs_f64();
         Ok(RunResult {
             changes: changes as f64,
@@ -1275,32 +3705,119 @@ pub fn statement_iterate_sync(
     let safe_ints = stmt.mode.safe_ints.load(Ordering::SeqCst);
     let raw = stmt.mode.raw.load(Ordering::SeqCst);
     let pluck = stmt.mode.pluck.load(Ordering::SeqCst);
-    let query_timeout = stmt.resolve_query_timeout(query_options);
-    let inner_stmt = stmt.stmt.clone();
+    let limits = stmt.resolve_limits(query_options)?;
+    let (
+        Prepared {
+            stmt: inner_stmt, ..
+        },
+        execution,
+    ) = stmt.begin()?;
     let iter_stmt = inner_stmt.clone();
-    let (rows, column_names, timeout_guard) = rt.block_on(async move {
-        let timeout_guard = register_timeout(&inner_stmt, query_timeout);
+    let (rows, column_names, limits_guard) = rt.block_on(async move {
+        let limits_guard = limits.register(&inner_stmt, &stmt.state);
         inner_stmt.reset();
-        let params = map_params(&inner_stmt, params)?;
-        let rows = inner_stmt.query(params).await.map_err(Error::from)?;
+        let params = stmt.params(&inner_stmt, params)?;
+        let rows = inner_stmt
+            .query(params)
+            .await
+            .map_err(|err| Error::with_sql(err, &stmt.sql).with_limits(&limits_guard))?;
         let mut column_names = Vec::new();
         for i in 0..rows.column_count() {
             column_names
                 .push(std::ffi::CString::new(rows.column_name(i).unwrap().to_string()).unwrap());
         }
-        Ok::<_, napi::Error>((rows, column_names, timeout_guard))
+        Ok::<_, napi::Error>((rows, column_names, limits_guard))
     })?;
     Ok(RowsIterator::new(
-        Arc::new(tokio::sync::Mutex::new(rows)),
-        iter_stmt,
+        stmt.state.handles.register(Cursor {
+            rows: Arc::new(tokio::sync::Mutex::new(rows)),
+            stmt: iter_stmt,
+            execution: Arc::new(Mutex::new(Some(execution))),
+        }),
+        stmt.sql.clone(),
         column_names,
         safe_ints,
         raw,
         pluck,
-        timeout_guard,
+        limits_guard,
     ))
 }
 
+/// A parameter of a statement.
+#[napi(object)]
+pub struct StatementParameter {
+    /// The position of the parameter, starting at 1.
+    pub index: u32,
+    /// The name of the parameter, prefix included, unless it is anonymous.
+    pub name: Option<String>,
+}
+
+/// The outcome of a statement of a script.
+struct ScriptStatement {
+    // The byte range of the statement in the script.
+    range: std::ops::Range<usize>,
+    column_names: Vec<std::ffi::CString>,
+    rows: Vec<Vec<libsql::Value>>,
+    changes: u64,
+    last_insert_rowid: i64,
+}
+
+/// The outcome of a statement of a script, as returned by `execMulti()`.
+#[napi(object)]
+pub struct ScriptStatementResult {
+    /// The SQL text of the statement.
+    pub sql: String,
+    /// The offset of the statement in the script.
+    pub start: u32,
+    /// The offset of the end of the statement in the script.
+    pub end: u32,
+    /// The names of the columns of the rows.
+    pub columns: Vec<String>,
+    /// The rows returned by the statement.
+    pub rows: Vec<napi::JsUnknown>,
+    /// The number of rows changed by the statement.
+    pub changes: f64,
+    /// The rowid of the last row inserted by the connection.
+    pub last_insert_rowid: i64,
+}
+
+/// Maps the outcomes of the statements of the script `sql` to
+/// `ScriptStatementResult` objects, with offsets as JavaScript string indexes.
+fn map_script_statements(
+    env: &Env,
+    sql: &str,
+    statements: Vec<ScriptStatement>,
+    safe_ints: bool,
+) -> Result<Vec<ScriptStatementResult>> {
+    let utf16_offset = |offset: usize| sql[..offset].encode_utf16().count() as u32;
+    statements
+        .into_iter()
+        .map(|statement| {
+            let rows = statement
+                .rows
+                .into_iter()
+                .map(|mut row| {
+                    map_row_object(env, &statement.column_names, &mut row[..], safe_ints, false)
+                        .map(|row| row.into_unknown())
+                })
+                .collect::<Result<_>>()?;
+            Ok(ScriptStatementResult {
+                sql: sql[statement.range.clone()].to_string(),
+                start: utf16_offset(statement.range.start),
+                end: utf16_offset(statement.range.end),
+                columns: statement
+                    .column_names
+                    .iter()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .collect(),
+                rows,
+                changes: statement.changes as f64,
+                last_insert_rowid: statement.last_insert_rowid,
+            })
+        })
+        .collect()
+}
+
 /// SQLite `run()` result object
 #[napi(object)]
 pub struct RunResult {
@@ -1309,18 +3826,27 @@ pub struct RunResult {
     pub lastInsertRowid: i64,
 }
 
+/// Maps the parameters passed to a statement.
+///
+/// An array holds the values of the anonymous and numbered parameters, in
+/// order, and may hold one plain object with the values of the named
+/// parameters, keyed by name without prefix. With `strict`, missing and
+/// unknown named parameters are errors rather than left unbound.
 fn map_params(
     stmt: &libsql::Statement,
     params: Option<napi::JsUnknown>,
+    strict: bool,
 ) -> Result<libsql::params::Params> {
     if let Some(params) = params {
         match params.get_type()? {
             ValueType::Object => {
                 let object = params.coerce_to_object()?;
                 if object.is_array()? {
-                    map_params_array(object)
+                    map_params_array(stmt, object, strict)
+                } else if is_named_params(&object)? {
+                    map_params_object(stmt, Vec::new(), object, strict)
                 } else {
-                    map_params_object(stmt, object)
+                    map_params_single(object.into_unknown())
                 }
             }
             _ => map_params_single(params),
@@ -1334,33 +3860,134 @@ fn map_params_single(param: napi::JsUnknown) -> Result<libsql::params::Params> {
     Ok(libsql::params::Params::Positional(vec![map_value(param)?]))
 }
 
-fn map_params_array(object: napi::JsObject) -> Result<libsql::params::Params> {
+fn map_params_array(
+    stmt: &libsql::Statement,
+    object: napi::JsObject,
+    strict: bool,
+) -> Result<libsql::params::Params> {
     let mut params = vec![];
+    let mut named = None;
     let length = object.get_array_length()?;
     for i in 0..length {
         let element = object.get_element::<napi::JsUnknown>(i)?;
-        let value = map_value(element)?;
-        params.push(value);
+        if element.get_type()? == ValueType::Object {
+            let element = element.coerce_to_object()?;
+            if is_named_params(&element)? {
+                if named.replace(element).is_some() {
+                    return Err(params_range_error(
+                        "Only one object of named parameters can be passed",
+                    ));
+                }
+                continue;
+            }
+            params.push(map_value(element.into_unknown())?);
+            continue;
+        }
+        params.push(map_value(element)?);
+    }
+    match named {
+        Some(named) => map_params_object(stmt, params, named, strict),
+        None => Ok(libsql::params::Params::Positional(params)),
     }
-    Ok(libsql::params::Params::Positional(params))
 }
 
+/// Maps an object of named parameters, along with the values of the
+/// anonymous and numbered parameters, if any.
 fn map_params_object(
     stmt: &libsql::Statement,
+    positional: Vec<libsql::Value>,
     object: napi::JsObject,
+    strict: bool,
 ) -> Result<libsql::params::Params> {
-    let mut params = vec![];
-    for idx in 0..stmt.parameter_count() {
-        let name = stmt.parameter_name((idx + 1) as i32).unwrap();
-        let name = name.to_string();
-        // Remove the leading ':' or '@' or '$' from parameter name
-        let key = &name[1..];
-        if let Ok(value) = object.get_named_property::<napi::JsUnknown>(key) {
-            let value = map_value(value)?;
-            params.push((name, value));
+    let mut unknown_keys = if strict {
+        let names = object.get_property_names()?;
+        let mut keys = Vec::new();
+        for i in 0..names.get_array_length()? {
+            let key = names.get_element::<napi::JsString>(i)?;
+            keys.push(key.into_utf8()?.into_owned()?);
+        }
+        keys
+    } else {
+        Vec::new()
+    };
+    let mut positional = positional.into_iter();
+    let mut by_name = true;
+    let mut named = Vec::new();
+    let mut values = Vec::new();
+    for index in 1..=stmt.parameter_count() as i32 {
+        let name = stmt.parameter_name(index);
+        // Remove the leading ':' or '@' or '$' from parameter name, or the
+        // '?' from numbered parameters
+        let key = name.map(|name| &name[1..]);
+        if let Some(key) = key {
+            unknown_keys.retain(|unknown| unknown != key);
+        }
+        let value = match (name, key) {
+            (Some(_), Some(key)) if object.has_named_property(key)? => {
+                let value = object.get_named_property::<napi::JsUnknown>(key)?;
+                Some(map_value(value)?)
+            }
+            (Some(name), Some(key)) if !name.starts_with('?') => {
+                if strict {
+                    return Err(params_range_error(&format!(
+                        "Missing named parameter \"{key}\""
+                    )));
+                }
+                None
+            }
+            // Anonymous and numbered parameters, as well as the indexes that
+            // numbered parameters skip, take the positional values in order.
+            _ => {
+                let value = positional.next();
+                by_name &= value.is_none() && name.is_some();
+                value
+            }
+        };
+        let value = value.unwrap_or(libsql::Value::Null);
+        if let Some(name) = name {
+            named.push((name.to_string(), value.clone()));
         }
+        values.push(value);
+    }
+    if let Some(key) = unknown_keys.first() {
+        return Err(params_range_error(&format!(
+            "Unknown named parameter \"{key}\""
+        )));
     }
-    Ok(libsql::params::Params::Named(params))
+    if strict && positional.next().is_some() {
+        return Err(params_range_error(
+            "Too many parameter values were provided",
+        ));
+    }
+    // Parameters missing from the object are bound to NULL either way, so
+    // that a reused statement does not keep the values of an earlier run.
+    if by_name {
+        Ok(libsql::params::Params::Named(named))
+    } else {
+        Ok(libsql::params::Params::Positional(values))
+    }
+}
+
+/// Returns whether `object` holds named parameters, rather than being a value
+/// to bind.
+fn is_named_params(object: &napi::JsObject) -> Result<bool> {
+    Ok(!object.is_array()? && !object.is_buffer()? && !object.is_typedarray()?)
+}
+
+fn params_range_error(message: &str) -> napi::Error {
+    throw_sqlite_error(
+        message.to_string(),
+        "SQLITE_RANGE".to_string(),
+        libsql::ffi::SQLITE_RANGE,
+    )
+}
+
+fn params_misuse_error(message: &str) -> napi::Error {
+    throw_sqlite_error(
+        message.to_string(),
+        "SQLITE_MISUSE".to_string(),
+        libsql::ffi::SQLITE_MISUSE,
+    )
 }
 
 /// Maps a JavaScript value to libSQL value types.
@@ -1439,51 +4066,78 @@ fn map_value(value: JsUnknown) -> Result<libsql::Value> {
 /// A raw iterator over rows. The JavaScript layer wraps this in a iterable.
 #[napi]
 pub struct RowsIterator {
-    rows: Arc<tokio::sync::Mutex<libsql::Rows>>,
-    stmt: Arc<libsql::Statement>,
+    cursor: Arc<handles::Slot<Cursor>>,
+    sql: Arc<str>,
     column_names: Vec<std::ffi::CString>,
     safe_ints: bool,
     raw: bool,
     pluck: bool,
-    timeout_guard: Mutex<Option<QueryTimeoutGuard>>,
+    limits_guard: Mutex<Option<LimitsGuard>>,
 }
 
 #[napi]
 impl RowsIterator {
-    pub fn new(
-        rows: Arc<tokio::sync::Mutex<libsql::Rows>>,
-        stmt: Arc<libsql::Statement>,
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        cursor: Arc<handles::Slot<Cursor>>,
+        sql: Arc<str>,
         column_names: Vec<std::ffi::CString>,
         safe_ints: bool,
         raw: bool,
         pluck: bool,
-        timeout_guard: Option<QueryTimeoutGuard>,
+        limits_guard: LimitsGuard,
     ) -> Self {
         Self {
-            rows,
-            stmt,
+            cursor,
+            sql,
             column_names,
             safe_ints,
             raw,
             pluck,
-            timeout_guard: Mutex::new(timeout_guard),
+            limits_guard: Mutex::new(Some(limits_guard)),
         }
     }
 
     #[napi]
     pub async fn next(&self) -> Result<Record> {
-        let mut rows = self.rows.lock().await;
-        let row = match rows.next().await {
+        let Some(cursor) = self.cursor.get() else {
+            return Err(throw_sqlite_error(
+                "The database connection is not open".to_string(),
+                "SQLITE_NOTOPEN".to_string(),
+                0,
+            ));
+        };
+        let mut rows = cursor.rows.lock().await;
+        let row = match rows.next().await.and_then(|row| {
+            // The row reads from the statement, so its values are copied
+            // before the statement moves on or is finalized.
+            row.map(|row| {
+                (0..self.column_names.len())
+                    .map(|idx| row.get_value(idx as i32))
+                    .collect::<libsql::Result<Vec<_>>>()
+            })
+            .transpose()
+        }) {
             Ok(row) => row,
             Err(err) => {
+                let err = {
+                    let limits_guard = self.limits_guard.lock().unwrap();
+                    match limits_guard.as_ref() {
+                        Some(limits_guard) => {
+                            Error::with_sql(err, &self.sql).with_limits(limits_guard)
+                        }
+                        None => Error::with_sql(err, &self.sql),
+                    }
+                };
                 self.release_operation_resources();
-                return Err(Error::from(err).into());
+                return Err(err.into());
             }
         };
         if row.is_none() {
             self.release_operation_resources();
         }
         Ok(Record {
+            done: row.is_none(),
             row,
             column_names: self.column_names.clone(),
             safe_ints: self.safe_ints,
@@ -1498,12 +4152,25 @@ impl RowsIterator {
     }
 
     fn release_operation_resources(&self) {
-        self.stmt.reset();
-        let mut timeout_guard = self.timeout_guard.lock().unwrap();
-        timeout_guard.take();
+        if let Some(cursor) = self.cursor.get() {
+            cursor.stmt.reset();
+            cursor.execution.lock().unwrap().take();
+        }
+        let mut limits_guard = self.limits_guard.lock().unwrap();
+        limits_guard.take();
     }
 }
 
+/// The libSQL objects an iterator reads from.
+#[derive(Clone)]
+struct Cursor {
+    rows: Arc<tokio::sync::Mutex<libsql::Rows>>,
+    stmt: Arc<libsql::Statement>,
+    // The execution the iterator is part of, until it is finished. Closing
+    // the database ends it, once a step in progress is over.
+    execution: Arc<Mutex<Option<Execution>>>,
+}
+
 /// Retrieve next row from an iterator synchronously. Needed for better-sqlite3 API compatibility.
 #[napi]
 pub fn iterator_next_sync(iter: &RowsIterator) -> Result<Record> {
@@ -1513,7 +4180,9 @@ pub fn iterator_next_sync(iter: &RowsIterator) -> Result<Record> {
 
 #[napi]
 pub struct Record {
-    row: Option<libsql::Row>,
+    // The values of the row, until `value` moves them into JavaScript.
+    row: Option<Vec<libsql::Value>>,
+    done: bool,
     column_names: Vec<std::ffi::CString>,
     safe_ints: bool,
     raw: bool,
@@ -1523,38 +4192,85 @@ pub struct Record {
 #[napi]
 impl Record {
     #[napi(getter)]
-    pub fn value(&self, env: Env) -> napi::Result<napi::JsUnknown> {
-        if let Some(row) = &self.row {
-            Ok(map_row(
-                &env,
-                &self.column_names,
-                &row,
-                self.safe_ints,
-                self.raw,
-                self.pluck,
-            )?)
-        } else {
-            Ok(env.get_null()?.into_unknown())
-        }
+    pub fn value(&mut self, env: Env, mut this: This<JsObject>) -> napi::Result<napi::JsUnknown> {
+        let Some(mut row) = self.row.take() else {
+            return Ok(env.get_null()?.into_unknown());
+        };
+        let value = map_row(
+            &env,
+            &self.column_names,
+            row.as_mut_slice(),
+            self.safe_ints,
+            self.raw,
+            self.pluck,
+        )?;
+        // The values were moved into JavaScript, so later reads find the
+        // converted row on the record itself rather than calling this getter.
+        this.define_properties(&[napi::Property::new("value")?.with_value(&value)])?;
+        Ok(value)
     }
 
     #[napi(getter)]
     pub fn done(&self) -> bool {
-        self.row.is_none()
+        self.done
     }
 }
 
+thread_local! {
+    /// The callback this thread is running from inside the runtime, if any.
+    static CALLBACK: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Runs `f`, which calls into JavaScript from inside the runtime, as `name`.
+/// The blocking API cannot be used until it returns, as it would enter the
+/// runtime again.
+fn within_callback<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let outer = CALLBACK.replace(Some(name));
+    let result = f();
+    CALLBACK.set(outer);
+    result
+}
+
 fn runtime() -> Result<&'static Runtime> {
     static RUNTIME: OnceCell<Runtime> = OnceCell::new();
 
+    if let Some(callback) = CALLBACK.get() {
+        return Err(napi::Error::from_reason(format!(
+            "The database cannot be used synchronously from {callback}"
+        )));
+    }
+
     let rt = RUNTIME.get_or_try_init(Runtime::new).unwrap();
     Ok(rt)
 }
 
+/// The values of a result row, read from the row or moved out of values
+/// read from it earlier.
+trait RowValues {
+    fn take_value(&mut self, idx: i32) -> libsql::Result<libsql::Value>;
+}
+
+impl RowValues for libsql::Row {
+    fn take_value(&mut self, idx: i32) -> libsql::Result<libsql::Value> {
+        libsql::Row::get_value(self, idx)
+    }
+}
+
+impl RowValues for [libsql::Value] {
+    /// Moves the value out, leaving NULL in its place, so that blobs are not
+    /// copied.
+    fn take_value(&mut self, idx: i32) -> libsql::Result<libsql::Value> {
+        let idx = usize::try_from(idx).map_err(|_| libsql::Error::InvalidColumnIndex)?;
+        self.get_mut(idx)
+            .map(|value| std::mem::replace(value, libsql::Value::Null))
+            .ok_or(libsql::Error::InvalidColumnIndex)
+    }
+}
+
 fn map_row(
     env: &Env,
     column_names: &[std::ffi::CString],
-    row: &libsql::Row,
+    row: &mut (impl RowValues + ?Sized),
     safe_ints: bool,
     raw: bool,
     pluck: bool,
@@ -1567,30 +4283,36 @@ fn map_row(
     Ok(result)
 }
 
+/// Converts a libSQL value into a JavaScript value.
+///
+/// The value is taken by ownership so that blobs can be handed over to an
+/// external `Buffer` without copying: the `Vec<u8>` read out of the row becomes
+/// the backing store of the `Buffer` and is freed when the JavaScript garbage
+/// collector releases it.
 fn convert_value_to_js(
     env: &Env,
-    value: &libsql::Value,
+    value: libsql::Value,
     safe_ints: bool,
 ) -> Result<napi::JsUnknown> {
     match value {
         libsql::Value::Null => Ok(env.get_null()?.into_unknown()),
         libsql::Value::Integer(v) => {
             if safe_ints {
-                Ok(env.create_bigint_from_i64(*v)?.into_unknown()?)
+                Ok(env.create_bigint_from_i64(v)?.into_unknown()?)
             } else {
-                Ok(env.create_double(*v as f64)?.into_unknown())
+                Ok(env.create_double(v as f64)?.into_unknown())
             }
         }
-        libsql::Value::Real(v) => Ok(env.create_double(*v)?.into_unknown()),
-        libsql::Value::Text(v) => Ok(env.create_string(v)?.into_unknown()),
-        libsql::Value::Blob(v) => Ok(env.create_buffer_with_data(v.clone())?.into_unknown()),
+        libsql::Value::Real(v) => Ok(env.create_double(v)?.into_unknown()),
+        libsql::Value::Text(v) => Ok(env.create_string(&v)?.into_unknown()),
+        libsql::Value::Blob(v) => Ok(env.create_buffer_with_data(v)?.into_unknown()),
     }
 }
 
 fn map_row_object(
     env: &Env,
     column_names: &[std::ffi::CString],
-    row: &libsql::Row,
+    row: &mut (impl RowValues + ?Sized),
     safe_ints: bool,
     pluck: bool,
 ) -> Result<napi::JsUnknown> {
@@ -1598,11 +4320,11 @@ fn map_row_object(
 
     let result = if pluck {
         if column_count > 0 {
-            let value = match row.get_value(0) {
+            let value = match row.take_value(0) {
                 Ok(v) => v,
                 Err(e) => return Err(napi::Error::from_reason(e.to_string())),
             };
-            convert_value_to_js(env, &value, safe_ints)?
+            convert_value_to_js(env, value, safe_ints)?
         } else {
             env.get_null()?.into_unknown()
         }
@@ -1610,14 +4332,13 @@ fn map_row_object(
         let result = env.create_object()?;
         let result = unsafe { napi::JsObject::to_napi_value(env.raw(), result)? };
         // If not plucking, get all columns
-        for idx in 0..column_count {
-            let value = match row.get_value(idx as i32) {
+        for (idx, column_name) in column_names.iter().enumerate() {
+            let value = match row.take_value(idx as i32) {
                 Ok(v) => v,
                 Err(e) => return Err(napi::Error::from_reason(e.to_string())),
             };
 
-            let column_name = &column_names[idx];
-            let js_value = convert_value_to_js(env, &value, safe_ints)?;
+            let js_value = convert_value_to_js(env, value, safe_ints)?;
             unsafe {
                 napi::sys::napi_set_named_property(
                     env.raw(),
@@ -1636,13 +4357,13 @@ fn map_row_object(
 fn map_row_raw(
     env: &Env,
     column_names: &[std::ffi::CString],
-    row: &libsql::Row,
+    row: &mut (impl RowValues + ?Sized),
     safe_ints: bool,
     pluck: bool,
 ) -> Result<napi::JsUnknown> {
     if pluck {
-        let value = match row.get_value(0) {
-            Ok(v) => convert_value_to_js(env, &v, safe_ints)?,
+        let value = match row.take_value(0) {
+            Ok(v) => convert_value_to_js(env, v, safe_ints)?,
             Err(_) => env.get_null()?.into_unknown(),
         };
         return Ok(value);
@@ -1650,11 +4371,11 @@ fn map_row_raw(
     let column_count = column_names.len();
     let mut arr = env.create_array(column_count as u32)?;
     for idx in 0..column_count {
-        let value = match row.get_value(idx as i32) {
+        let value = match row.take_value(idx as i32) {
             Ok(v) => v,
             Err(e) => return Err(napi::Error::from_reason(e.to_string())),
         };
-        let js_value = convert_value_to_js(env, &value, safe_ints)?;
+        let js_value = convert_value_to_js(env, value, safe_ints)?;
         arr.set(idx as u32, js_value)?;
     }
     Ok(arr.coerce_to_object()?.into_unknown())
@@ -0,0 +1,274 @@
+//! Schema migrations.
+//!
+//! The migrations applied to a database are recorded in a table, in the order
+//! they were applied, along with a checksum of their SQL. Migrating checks that
+//! the recorded migrations are the first ones of the given list, unchanged, and
+//! then applies the others in a single transaction, so that a failing migration
+//! leaves the database as it was. The transaction begins with a write, which
+//! embedded replicas and remote databases run on the primary, where the table
+//! of applied migrations is up to date. Migrations that begin or end a
+//! transaction themselves would break out of it, so they are rejected.
+
+use crate::script;
+use crate::sql::quote_identifier;
+use libsql::{params, TransactionBehavior};
+
+/// A migration, identified by `id`, that runs the `up` SQL.
+pub struct Migration {
+    pub id: String,
+    pub up: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The migrations recorded in the database differ from the given ones.
+    Drift(String),
+    /// A migration begins or ends a transaction.
+    Transaction(String),
+    /// A statement failed, with the SQL of the migration that was running, if
+    /// any.
+    Sql(libsql::Error, Option<String>),
+}
+
+impl From<libsql::Error> for Error {
+    fn from(err: libsql::Error) -> Self {
+        Error::Sql(err, None)
+    }
+}
+
+/// Applies the migrations not yet recorded in `table`, creating the table if
+/// needed, and returns the ids of those applied.
+pub async fn migrate(
+    conn: &libsql::Connection,
+    table: &str,
+    migrations: &[Migration],
+) -> Result<Vec<String>, Error> {
+    let table = quote_identifier(table);
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .await?;
+    let result = apply(&tx, &table, migrations).await;
+    match result {
+        Ok(applied) => {
+            tx.commit().await?;
+            Ok(applied)
+        }
+        Err(err) => {
+            let _ = tx.rollback().await;
+            Err(err)
+        }
+    }
+}
+
+async fn apply(
+    conn: &libsql::Connection,
+    table: &str,
+    migrations: &[Migration],
+) -> Result<Vec<String>, Error> {
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {table} (\
+             id TEXT PRIMARY KEY, checksum TEXT NOT NULL, applied_at INTEGER NOT NULL)"
+        ),
+        (),
+    )
+    .await?;
+    let mut rows = conn
+        .query(
+            &format!("SELECT id, checksum FROM {table} ORDER BY rowid"),
+            (),
+        )
+        .await?;
+    let mut recorded = Vec::new();
+    while let Some(row) = rows.next().await? {
+        recorded.push((row.get::<String>(0)?, row.get::<String>(1)?));
+    }
+    drop(rows);
+    check_drift(&recorded, migrations).map_err(Error::Drift)?;
+
+    let mut applied = Vec::new();
+    for migration in &migrations[recorded.len()..] {
+        if let Some(statement) = transaction_statement(&migration.up) {
+            return Err(Error::Transaction(format!(
+                "Migration '{}' must not control transactions, as migrations run in one, but it runs `{statement}`",
+                migration.id
+            )));
+        }
+        conn.execute_batch(&migration.up)
+            .await
+            .map_err(|err| Error::Sql(err, Some(migration.up.clone())))?;
+        conn.execute(
+            &format!("INSERT INTO {table} (id, checksum, applied_at) VALUES (?1, ?2, unixepoch())"),
+            params![migration.id.as_str(), checksum(&migration.up)],
+        )
+        .await?;
+        applied.push(migration.id.clone());
+    }
+    Ok(applied)
+}
+
+/// Returns the first statement of `sql` that begins, commits or rolls back a
+/// transaction. Savepoints, and rolling back to them, stay within the
+/// transaction.
+fn transaction_statement(sql: &str) -> Option<&str> {
+    script::split(sql)
+        .into_iter()
+        .map(|range| &sql[range])
+        .find(|statement| {
+            let mut words = statement
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .filter(|word| !word.is_empty());
+            let keyword = |word: Option<&str>, keyword: &str| {
+                word.is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            };
+            match words.next() {
+                Some(word)
+                    if ["BEGIN", "COMMIT", "END"]
+                        .iter()
+                        .any(|k| keyword(Some(word), k)) =>
+                {
+                    true
+                }
+                Some(word) if keyword(Some(word), "ROLLBACK") => {
+                    let next = words.find(|word| !word.eq_ignore_ascii_case("TRANSACTION"));
+                    !keyword(next, "TO")
+                }
+                _ => false,
+            }
+        })
+}
+
+/// Checks that the `recorded` migrations, as pairs of id and checksum, are the
+/// first of `migrations`.
+fn check_drift(recorded: &[(String, String)], migrations: &[Migration]) -> Result<(), String> {
+    for (position, (id, sum)) in recorded.iter().enumerate() {
+        let Some(migration) = migrations.get(position) else {
+            return Err(format!(
+                "Migration '{id}' was applied to the database but is missing from the migrations"
+            ));
+        };
+        if migration.id != *id {
+            return Err(format!(
+                "Migration '{id}' was applied to the database where migration '{}' is expected",
+                migration.id
+            ));
+        }
+        if checksum(&migration.up) != *sum {
+            return Err(format!(
+                "Migration '{id}' was changed after it was applied to the database"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the 64-bit FNV-1a hash of `sql`, in hexadecimal.
+fn checksum(sql: &str) -> String {
+    let hash = sql.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(id: &str, up: &str) -> Migration {
+        Migration {
+            id: id.to_string(),
+            up: up.to_string(),
+        }
+    }
+
+    #[test]
+    fn checksums_are_stable() {
+        assert_eq!(checksum(""), "cbf29ce484222325");
+        assert_eq!(checksum("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn recorded_migrations_must_come_first_and_be_unchanged() {
+        let migrations = [
+            migration("1", "CREATE TABLE a(x)"),
+            migration("2", "CREATE TABLE b(x)"),
+        ];
+        let recorded = |id: &str, up: &str| (id.to_string(), checksum(up));
+        assert!(check_drift(&[], &migrations).is_ok());
+        assert!(check_drift(&[recorded("1", "CREATE TABLE a(x)")], &migrations).is_ok());
+        assert!(check_drift(&[recorded("2", "CREATE TABLE b(x)")], &migrations).is_err());
+        assert!(check_drift(&[recorded("1", "CREATE TABLE a(y)")], &migrations).is_err());
+        assert!(check_drift(
+            &[
+                recorded("1", "CREATE TABLE a(x)"),
+                recorded("2", "CREATE TABLE b(x)"),
+                recorded("3", "")
+            ],
+            &migrations
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn transaction_statements_are_found() {
+        assert_eq!(
+            transaction_statement("CREATE TABLE a(x); commit;"),
+            Some("commit;")
+        );
+        assert_eq!(
+            transaction_statement("-- setup\nBEGIN; CREATE TABLE a(x)"),
+            Some("BEGIN;")
+        );
+        assert_eq!(
+            transaction_statement("ROLLBACK TRANSACTION"),
+            Some("ROLLBACK TRANSACTION")
+        );
+        assert_eq!(transaction_statement("END"), Some("END"));
+        assert_eq!(
+            transaction_statement(
+                "SAVEPOINT s; ROLLBACK TRANSACTION TO s; RELEASE s; \
+                 CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM b; END;"
+            ),
+            None
+        );
+        assert_eq!(transaction_statement("SELECT 'COMMIT'"), None);
+    }
+
+    #[tokio::test]
+    async fn applies_pending_migrations_once() {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let mut migrations = vec![migration("1", "CREATE TABLE a(x)")];
+        assert_eq!(
+            migrate(&conn, "_migrations", &migrations).await.unwrap(),
+            ["1"]
+        );
+        assert!(migrate(&conn, "_migrations", &migrations)
+            .await
+            .unwrap()
+            .is_empty());
+
+        migrations.push(migration(
+            "2",
+            "INSERT INTO a VALUES (1); SELECT * FROM missing",
+        ));
+        assert!(matches!(
+            migrate(&conn, "_migrations", &migrations).await,
+            Err(Error::Sql(_, Some(_)))
+        ));
+        let mut rows = conn.query("SELECT count(*) FROM a", ()).await.unwrap();
+        let count: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(count, 0);
+        assert!(conn.is_autocommit());
+
+        migrations[1] = migration("2", "INSERT INTO a VALUES (1); COMMIT");
+        assert!(matches!(
+            migrate(&conn, "_migrations", &migrations).await,
+            Err(Error::Transaction(_))
+        ));
+        assert!(conn.is_autocommit());
+    }
+}
@@ -0,0 +1,94 @@
+//! Operations in flight on a connection.
+//!
+//! Asynchronous operations hold on to the connection while they run on the
+//! runtime, and iterators keep a statement stepping until they are finished.
+//! Closing a database gracefully stops new operations from starting and waits
+//! for those to complete, so that SQLite gets to close the connection, and
+//! checkpoint its write-ahead log, with nothing left running on it.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    running: usize,
+    stopped: bool,
+}
+
+/// The operations in flight on a connection.
+pub struct Operations {
+    state: watch::Sender<State>,
+}
+
+impl Default for Operations {
+    fn default() -> Self {
+        Self {
+            state: watch::Sender::new(State::default()),
+        }
+    }
+}
+
+/// An operation in flight, until dropped.
+pub struct Operation {
+    operations: Arc<Operations>,
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        self.operations
+            .state
+            .send_modify(|state| state.running -= 1);
+    }
+}
+
+impl Operations {
+    /// Starts an operation, unless operations have been stopped.
+    pub fn start(self: &Arc<Self>) -> Option<Operation> {
+        let started = self.state.send_if_modified(|state| {
+            if state.stopped {
+                return false;
+            }
+            state.running += 1;
+            true
+        });
+        started.then(|| Operation {
+            operations: self.clone(),
+        })
+    }
+
+    /// Stops new operations from starting.
+    pub fn stop(&self) {
+        self.state.send_modify(|state| state.stopped = true);
+    }
+
+    /// Waits until no operation is in flight.
+    pub async fn idle(&self) {
+        let mut state = self.state.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail.
+        let _ = state.wait_for(|state| state.running == 0).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_operations_in_flight() {
+        let operations = Arc::new(Operations::default());
+        let operation = operations.start().unwrap();
+        operations.stop();
+        assert!(operations.start().is_none());
+
+        let idle = tokio::spawn({
+            let operations = operations.clone();
+            async move { operations.idle().await }
+        });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!idle.is_finished());
+
+        drop(operation);
+        idle.await.unwrap();
+    }
+}
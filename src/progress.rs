@@ -0,0 +1,322 @@
+//! VM-step budgets and progress reporting for local connections, built on
+//! `sqlite3_progress_handler`.
+//!
+//! SQLite supports a single progress handler per connection, invoked every N
+//! virtual machine instructions. The handler installed here counts the
+//! instructions the connection executes and serves two consumers:
+//!
+//! - step budgets, registered per operation, which interrupt their target once
+//!   the connection has executed the allotted number of instructions while the
+//!   operation was in flight, and
+//! - an optional listener, notified roughly every N instructions.
+//!
+//! Budgets interrupt through the same [`Interruptible`] targets as query
+//! timeouts, so an exhausted budget stops only the statement it was registered
+//! for. The handler is installed lazily, the first time either consumer needs
+//! it, so connections that use neither pay nothing.
+
+use crate::query_timeout::Interruptible;
+use crate::raw::RawConnection;
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    os::raw::c_int,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+/// How many VM instructions pass between two handler invocations when no
+/// listener asks for a finer period. This bounds how far past its limit a
+/// budget may run.
+const DEFAULT_PERIOD: u64 = 100;
+
+/// The progress handler of one local connection.
+pub struct Progress {
+    raw: Arc<RawConnection>,
+    state: Arc<State>,
+    // The period the handler is installed with, or 0 if not installed yet.
+    installed_period: Mutex<u64>,
+}
+
+struct State {
+    // VM instructions executed since the handler was installed, counted at
+    // the granularity of the handler period.
+    steps: AtomicU64,
+    // The period the handler is currently invoked with.
+    period: AtomicU64,
+    // Number of entries in `budgets.active`, to skip the lock when it is empty.
+    active_budgets: AtomicUsize,
+    budgets: Mutex<Budgets>,
+    has_listener: AtomicBool,
+    listener: Mutex<Option<Listener>>,
+}
+
+struct Budgets {
+    next_id: u64,
+    active: HashMap<u64, Budget>,
+}
+
+struct Budget {
+    // The value of `State::steps` at which the budget is exhausted.
+    exhausted_at: u64,
+    target: Weak<dyn Interruptible>,
+    fired: Arc<AtomicBool>,
+}
+
+/// A function notified with the connection's running instruction count.
+pub type ProgressCallback = Box<dyn Fn(u64) + Send + Sync>;
+
+struct Listener {
+    every: u64,
+    next_at: u64,
+    callback: ProgressCallback,
+}
+
+impl Progress {
+    pub fn new(raw: Arc<RawConnection>) -> Self {
+        Self {
+            raw,
+            state: Arc::new(State {
+                steps: AtomicU64::new(0),
+                period: AtomicU64::new(DEFAULT_PERIOD),
+                active_budgets: AtomicUsize::new(0),
+                budgets: Mutex::new(Budgets {
+                    next_id: 0,
+                    active: HashMap::new(),
+                }),
+                has_listener: AtomicBool::new(false),
+                listener: Mutex::new(None),
+            }),
+            installed_period: Mutex::new(0),
+        }
+    }
+
+    /// Registers a budget of `max_steps` VM instructions for an operation
+    /// about to run against `target`, returning a guard that cancels the
+    /// budget when dropped.
+    ///
+    /// SQLite counts instructions per connection, so the budget is charged for
+    /// every instruction the connection executes while the operation is in
+    /// flight, including those of other statements interleaved with it.
+    pub fn register<T: Interruptible + 'static>(
+        &self,
+        target: &Arc<T>,
+        max_steps: u64,
+    ) -> StepBudgetGuard {
+        self.install();
+        let fired = Arc::new(AtomicBool::new(false));
+        let weak = Arc::downgrade(target);
+        let target: Weak<dyn Interruptible> = weak;
+        let mut budgets = self.state.budgets.lock().unwrap();
+        let id = budgets.next_id;
+        budgets.next_id += 1;
+        budgets.active.insert(
+            id,
+            Budget {
+                exhausted_at: self
+                    .state
+                    .steps
+                    .load(Ordering::SeqCst)
+                    .saturating_add(max_steps),
+                target,
+                fired: fired.clone(),
+            },
+        );
+        self.state.active_budgets.fetch_add(1, Ordering::SeqCst);
+        StepBudgetGuard {
+            id,
+            state: self.state.clone(),
+            max_steps,
+            fired,
+        }
+    }
+
+    /// Sets the listener notified with the connection's running instruction
+    /// count roughly every `every` VM instructions, or removes it.
+    pub fn set_listener(&self, listener: Option<(u64, ProgressCallback)>) {
+        let period = match &listener {
+            Some((every, _)) => (*every).clamp(1, DEFAULT_PERIOD),
+            None => DEFAULT_PERIOD,
+        };
+        let steps = self.state.steps.load(Ordering::SeqCst);
+        let listener = listener.map(|(every, callback)| Listener {
+            every: every.max(1),
+            next_at: steps.saturating_add(every.max(1)),
+            callback,
+        });
+        self.state
+            .has_listener
+            .store(listener.is_some(), Ordering::SeqCst);
+        *self.state.listener.lock().unwrap() = listener;
+        self.state.period.store(period, Ordering::SeqCst);
+        self.install();
+    }
+
+    /// Installs the handler with the current period, unless it already is.
+    fn install(&self) {
+        let period = self.state.period.load(Ordering::SeqCst);
+        let mut installed_period = self.installed_period.lock().unwrap();
+        if *installed_period == period {
+            return;
+        }
+        if *installed_period == 0 {
+            // Tie the state's lifetime to the connection the handler belongs to.
+            self.raw
+                .set_clientdata(c"libsql-js.progress", self.state.clone());
+        }
+        // SAFETY: the connection keeps a reference to the state until it is
+        // closed, after which the handler can no longer be invoked.
+        unsafe {
+            self.raw.set_progress_handler(
+                period as c_int,
+                on_progress,
+                Arc::as_ptr(&self.state) as *mut c_void,
+            );
+        }
+        *installed_period = period;
+    }
+}
+
+unsafe extern "C" fn on_progress(arg: *mut c_void) -> c_int {
+    let state = unsafe { &*(arg as *const State) };
+    state.tick();
+    0
+}
+
+impl State {
+    fn tick(&self) {
+        let period = self.period.load(Ordering::Relaxed);
+        let steps = self.steps.fetch_add(period, Ordering::SeqCst) + period;
+
+        if self.active_budgets.load(Ordering::SeqCst) > 0 {
+            let mut budgets = self.budgets.lock().unwrap();
+            budgets.active.retain(|_, budget| {
+                if steps < budget.exhausted_at {
+                    return true;
+                }
+                if let Some(target) = budget.target.upgrade() {
+                    budget.fired.store(true, Ordering::SeqCst);
+                    target.interrupt();
+                }
+                self.active_budgets.fetch_sub(1, Ordering::SeqCst);
+                false
+            });
+        }
+
+        if self.has_listener.load(Ordering::Relaxed) {
+            let mut listener = self.listener.lock().unwrap();
+            if let Some(listener) = listener.as_mut() {
+                if steps >= listener.next_at {
+                    listener.next_at = steps - steps % listener.every + listener.every;
+                    (listener.callback)(steps);
+                }
+            }
+        }
+    }
+}
+
+/// RAII handle for a registered step budget.
+pub struct StepBudgetGuard {
+    id: u64,
+    state: Arc<State>,
+    max_steps: u64,
+    fired: Arc<AtomicBool>,
+}
+
+impl StepBudgetGuard {
+    /// Returns whether the budget ran out and the operation was interrupted
+    /// because of it.
+    pub fn fired(&self) -> bool {
+        self.fired.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of VM instructions the operation was allowed.
+    pub fn max_steps(&self) -> u64 {
+        self.max_steps
+    }
+}
+
+impl Drop for StepBudgetGuard {
+    fn drop(&mut self) {
+        let mut budgets = self.state.budgets.lock().unwrap();
+        if budgets.active.remove(&self.id).is_some() {
+            self.state.active_budgets.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDLESS: &str =
+        "WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n+1 FROM r) SELECT * FROM r";
+
+    async fn test_conn() -> (libsql::Database, Arc<libsql::Connection>, Progress) {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let (conn, raw) = crate::raw::connect(&db).unwrap();
+        let progress = Progress::new(Arc::new(raw.unwrap()));
+        (db, Arc::new(conn), progress)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn exhausted_budget_interrupts_target() {
+        let (_db, conn, progress) = test_conn().await;
+
+        let guard = progress.register(&conn, 10_000);
+        let result = conn.execute_batch(ENDLESS).await;
+
+        assert!(result.is_err(), "query should have been interrupted");
+        assert!(guard.fired());
+        assert_eq!(guard.max_steps(), 10_000);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn dropped_budget_does_not_interrupt() {
+        let (_db, conn, progress) = test_conn().await;
+
+        let guard = progress.register(&conn, 10);
+        drop(guard);
+        conn.execute_batch("SELECT 1; SELECT 2; SELECT 3")
+            .await
+            .expect("no budget should be in effect");
+
+        let guard = progress.register(&conn, 1_000_000);
+        conn.execute_batch("SELECT 1").await.unwrap();
+        assert!(!guard.fired());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn listener_is_notified_periodically() {
+        let (_db, conn, progress) = test_conn().await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        {
+            let calls = calls.clone();
+            progress.set_listener(Some((
+                50,
+                Box::new(move |steps| calls.lock().unwrap().push(steps)),
+            )));
+        }
+
+        conn.execute_batch(
+            "WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n+1 FROM r WHERE n < 1000) SELECT sum(n) FROM r",
+        )
+        .await
+        .unwrap();
+
+        let calls = std::mem::take(&mut *calls.lock().unwrap());
+        assert!(calls.len() > 10, "expected periodic notifications");
+        assert!(calls.windows(2).all(|w| w[1] - w[0] >= 50));
+
+        progress.set_listener(None);
+        conn.execute_batch("SELECT 1").await.unwrap();
+    }
+}
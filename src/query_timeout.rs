@@ -1,7 +1,10 @@
 use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap},
-    sync::{Arc, Condvar, Mutex, OnceLock, Weak},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex, OnceLock, Weak,
+    },
     time::{Duration, Instant},
 };
 
@@ -40,6 +43,8 @@ pub struct QueryTimeoutManager {
 struct Inner {
     state: Mutex<State>,
     cv: Condvar,
+    /// Counters for every operation registered with this manager.
+    counters: TimeoutCounters,
 }
 
 struct State {
@@ -49,15 +54,75 @@ struct State {
     /// interrupted if it is still present here; a completed operation removes
     /// itself via its guard's `Drop`, leaving a stale heap entry that is
     /// discarded lazily when its deadline is reached.
-    active: HashMap<u64, Weak<dyn Interruptible>>,
+    active: HashMap<u64, Active>,
     next_id: u64,
     shutdown: bool,
 }
 
+/// An in-flight operation: what to interrupt, and the flag its guard reads to
+/// learn that the interrupt came from its deadline expiring.
+struct Active {
+    target: Weak<dyn Interruptible>,
+    fired: Arc<AtomicBool>,
+    counters: Option<Arc<TimeoutCounters>>,
+}
+
 #[derive(Clone)]
 struct Entry {
     id: u64,
     deadline: Instant,
+    counters: Option<Arc<TimeoutCounters>>,
+}
+
+/// Counters describing the work done by the wheel, kept for the whole process
+/// and, optionally, for the operations of a single database.
+#[derive(Default)]
+pub struct TimeoutCounters {
+    registered: AtomicU64,
+    fired: AtomicU64,
+    reaped: AtomicU64,
+    heap_size: AtomicU64,
+    active: AtomicU64,
+}
+
+/// A snapshot of [`TimeoutCounters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutStats {
+    /// Timeouts registered so far.
+    pub registered: u64,
+    /// Timeouts that expired and interrupted their operation.
+    pub fired: u64,
+    /// Heap entries of completed operations discarded at their deadline.
+    pub reaped: u64,
+    /// Deadlines currently in the heap, including stale ones.
+    pub heap_size: u64,
+    /// Operations currently in flight with a timeout.
+    pub active: u64,
+}
+
+impl TimeoutCounters {
+    pub fn snapshot(&self) -> TimeoutStats {
+        TimeoutStats {
+            registered: self.registered.load(Ordering::SeqCst),
+            fired: self.fired.load(Ordering::SeqCst),
+            reaped: self.reaped.load(Ordering::SeqCst),
+            heap_size: self.heap_size.load(Ordering::SeqCst),
+            active: self.active.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Applies `update` to the manager-wide counters and to those of the
+/// database the operation belongs to, if any.
+fn count(
+    inner: &Inner,
+    counters: Option<&Arc<TimeoutCounters>>,
+    update: impl Fn(&TimeoutCounters),
+) {
+    update(&inner.counters);
+    if let Some(counters) = counters {
+        update(counters);
+    }
 }
 
 impl PartialEq for Entry {
@@ -100,6 +165,7 @@ impl QueryTimeoutManager {
                 shutdown: false,
             }),
             cv: Condvar::new(),
+            counters: TimeoutCounters::default(),
         });
 
         let bg = Arc::downgrade(&inner);
@@ -124,33 +190,58 @@ impl QueryTimeoutManager {
         self.inner.cv.notify_one();
     }
 
+    /// Returns a snapshot of the counters of every operation registered with
+    /// this manager.
+    pub fn stats(&self) -> TimeoutStats {
+        self.inner.counters.snapshot()
+    }
+
     /// Register a timeout for an operation about to run against `target`. The
     /// returned guard cancels the timeout when dropped (i.e. when the operation
-    /// completes).
+    /// completes). The operation is also accounted for in `counters`, if given.
     pub fn register<T: Interruptible + 'static>(
         &self,
         target: &Arc<T>,
         timeout: Duration,
+        counters: Option<&Arc<TimeoutCounters>>,
     ) -> QueryTimeoutGuard {
         let mut state = self.inner.state.lock().unwrap();
 
         let id = state.next_id;
         state.next_id += 1;
 
-        let deadline = Instant::now()
+        let started = Instant::now();
+        let deadline = started
             .checked_add(timeout)
-            .unwrap_or_else(|| Instant::now() + Duration::from_secs(86400));
-        let entry = Entry { id, deadline };
+            .unwrap_or_else(|| started + Duration::from_secs(86400));
+        let entry = Entry {
+            id,
+            deadline,
+            counters: counters.cloned(),
+        };
 
         let is_new_earliest = state
             .heap
             .peek()
-            .map_or(true, |Reverse(existing)| entry.deadline < existing.deadline);
+            .is_none_or(|Reverse(existing)| entry.deadline < existing.deadline);
 
         let weak = Arc::downgrade(target);
         let target: Weak<dyn Interruptible> = weak;
-        state.active.insert(id, target);
+        let fired = Arc::new(AtomicBool::new(false));
+        state.active.insert(
+            id,
+            Active {
+                target,
+                fired: fired.clone(),
+                counters: counters.cloned(),
+            },
+        );
         state.heap.push(Reverse(entry));
+        count(&self.inner, counters, |c| {
+            c.registered.fetch_add(1, Ordering::SeqCst);
+            c.heap_size.fetch_add(1, Ordering::SeqCst);
+            c.active.fetch_add(1, Ordering::SeqCst);
+        });
         drop(state);
 
         // Only the soonest deadline dictates when the thread next wakes, so we
@@ -164,15 +255,22 @@ impl QueryTimeoutManager {
         QueryTimeoutGuard {
             op_id: id,
             inner: self.inner.clone(),
+            timeout,
+            started,
+            fired,
         }
     }
 
     fn deregister(inner: &Arc<Inner>, op_id: u64) {
         let mut state = inner.state.lock().unwrap();
-        state.active.remove(&op_id);
+        if let Some(active) = state.active.remove(&op_id) {
+            count(inner, active.counters.as_ref(), |c| {
+                c.active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
     }
 
-    fn process_expired_deadlines(state: &mut State) {
+    fn process_expired_deadlines(inner: &Inner, state: &mut State) {
         let now = Instant::now();
 
         while let Some(Reverse(entry)) = state.heap.peek() {
@@ -187,9 +285,24 @@ impl QueryTimeoutManager {
                 .0;
 
             // Interrupt only if the operation is still in flight; a completed
-            // operation will have removed itself from `active` already.
-            if let Some(target) = state.active.remove(&entry.id) {
-                if let Some(target) = target.upgrade() {
+            // operation will have removed itself from `active` already. The
+            // flag is raised first so that the operation sees it when the
+            // interrupt makes it fail.
+            let active = state.active.remove(&entry.id);
+            count(inner, entry.counters.as_ref(), |c| {
+                c.heap_size.fetch_sub(1, Ordering::SeqCst);
+                if active.is_some() {
+                    c.active.fetch_sub(1, Ordering::SeqCst);
+                } else {
+                    c.reaped.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+            if let Some(active) = active {
+                if let Some(target) = active.target.upgrade() {
+                    active.fired.store(true, Ordering::SeqCst);
+                    count(inner, entry.counters.as_ref(), |c| {
+                        c.fired.fetch_add(1, Ordering::SeqCst);
+                    });
                     target.interrupt();
                 }
             }
@@ -197,38 +310,36 @@ impl QueryTimeoutManager {
     }
 
     fn background_thread(weak: Weak<Inner>) {
-        loop {
-            let inner = match weak.upgrade() {
-                Some(inner) => inner,
-                None => return,
-            };
+        let inner = match weak.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
 
-            let mut state = inner.state.lock().unwrap();
+        let mut state = inner.state.lock().unwrap();
 
-            loop {
-                if state.shutdown {
-                    return;
-                }
+        loop {
+            if state.shutdown {
+                return;
+            }
 
-                match state.heap.peek().map(|Reverse(entry)| entry.deadline) {
-                    Some(deadline) => {
-                        let now = Instant::now();
-                        if deadline > now {
-                            let wait_for = deadline.saturating_duration_since(now);
-                            let (new_state, timeout_result) =
-                                inner.cv.wait_timeout(state, wait_for).unwrap();
-                            state = new_state;
-
-                            if !timeout_result.timed_out() {
-                                continue;
-                            }
+            match state.heap.peek().map(|Reverse(entry)| entry.deadline) {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline > now {
+                        let wait_for = deadline.saturating_duration_since(now);
+                        let (new_state, timeout_result) =
+                            inner.cv.wait_timeout(state, wait_for).unwrap();
+                        state = new_state;
+
+                        if !timeout_result.timed_out() {
+                            continue;
                         }
-
-                        Self::process_expired_deadlines(&mut state);
-                    }
-                    None => {
-                        state = inner.cv.wait(state).unwrap();
                     }
+
+                    Self::process_expired_deadlines(&inner, &mut state);
+                }
+                None => {
+                    state = inner.cv.wait(state).unwrap();
                 }
             }
         }
@@ -245,6 +356,27 @@ impl Drop for QueryTimeoutManager {
 pub struct QueryTimeoutGuard {
     op_id: u64,
     inner: Arc<Inner>,
+    timeout: Duration,
+    started: Instant,
+    fired: Arc<AtomicBool>,
+}
+
+impl QueryTimeoutGuard {
+    /// Returns whether the deadline expired and the wheel interrupted the
+    /// operation, as opposed to it being interrupted by someone else.
+    pub fn fired(&self) -> bool {
+        self.fired.load(Ordering::SeqCst)
+    }
+
+    /// Returns the timeout the operation was registered with.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the time since the operation was registered.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
 }
 
 impl Drop for QueryTimeoutGuard {
@@ -271,7 +403,7 @@ mod tests {
         let conn = test_conn().await;
         let mgr = QueryTimeoutManager::new();
 
-        let _guard = mgr.register(&conn, Duration::from_millis(200));
+        let _guard = mgr.register(&conn, Duration::from_millis(200), None);
 
         let fut = {
             let conn = conn.clone();
@@ -300,7 +432,7 @@ mod tests {
         );
         let mgr = QueryTimeoutManager::new();
 
-        let _guard = mgr.register(&stmt, Duration::from_millis(200));
+        let _guard = mgr.register(&stmt, Duration::from_millis(200), None);
 
         // Drain the (effectively infinite) result set; the statement-level
         // interrupt must abort it once the deadline expires.
@@ -333,7 +465,7 @@ mod tests {
         );
         let mgr = QueryTimeoutManager::new();
 
-        let _guard = mgr.register(&stmt, Duration::from_millis(200));
+        let _guard = mgr.register(&stmt, Duration::from_millis(200), None);
 
         let result = async {
             let mut rows = stmt.query(()).await?;
@@ -374,13 +506,35 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn guard_reports_whether_its_deadline_fired() {
+        let conn = test_conn().await;
+        let mgr = QueryTimeoutManager::new();
+
+        let slow = mgr.register(&conn, Duration::from_millis(200), None);
+        let fast = mgr.register(&conn, Duration::from_millis(60_000), None);
+
+        let result = conn
+            .execute_batch(
+                "WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n+1 FROM r) SELECT * FROM r",
+            )
+            .await;
+        assert!(result.is_err(), "query should have been interrupted");
+
+        assert!(slow.fired(), "the expired guard should report its timeout");
+        assert_eq!(slow.timeout(), Duration::from_millis(200));
+        assert!(slow.elapsed() >= Duration::from_millis(200));
+        assert!(!fast.fired(), "a pending guard should not report a timeout");
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     #[ntest::timeout(10000)]
     async fn guard_dropped_before_deadline_cancels_timeout() {
         let conn = test_conn().await;
         let mgr = QueryTimeoutManager::new();
 
-        let guard = mgr.register(&conn, Duration::from_millis(200));
+        let guard = mgr.register(&conn, Duration::from_millis(200), None);
         drop(guard);
 
         std::thread::sleep(Duration::from_millis(300));
@@ -398,11 +552,11 @@ mod tests {
         let conn = test_conn().await;
         let mgr = QueryTimeoutManager::new();
 
-        let guard = mgr.register(&conn, Duration::from_millis(200));
+        let guard = mgr.register(&conn, Duration::from_millis(200), None);
         drop(guard);
 
         // Register a second operation after the first one has been deregistered.
-        let _guard2 = mgr.register(&conn, Duration::from_millis(5000));
+        let _guard2 = mgr.register(&conn, Duration::from_millis(5000), None);
 
         std::thread::sleep(Duration::from_millis(300));
 
@@ -413,6 +567,44 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn stats_count_registered_fired_and_reaped_entries() {
+        let conn = test_conn().await;
+        let other = test_conn().await;
+        let mgr = QueryTimeoutManager::new();
+        let counters = Arc::new(TimeoutCounters::default());
+
+        let completed = mgr.register(&conn, Duration::from_millis(100), Some(&counters));
+        drop(completed);
+        let _pending = mgr.register(&conn, Duration::from_millis(60_000), Some(&counters));
+        let _expiring = mgr.register(&conn, Duration::from_millis(100), Some(&counters));
+        let _unrelated = mgr.register(&other, Duration::from_millis(60_000), None);
+
+        std::thread::sleep(Duration::from_millis(300));
+
+        assert_eq!(
+            counters.snapshot(),
+            TimeoutStats {
+                registered: 3,
+                fired: 1,
+                reaped: 1,
+                heap_size: 1,
+                active: 1,
+            }
+        );
+        assert_eq!(
+            mgr.stats(),
+            TimeoutStats {
+                registered: 4,
+                fired: 1,
+                reaped: 1,
+                heap_size: 2,
+                active: 2,
+            }
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     #[ntest::timeout(10000)]
     async fn one_wheel_serves_many_connections() {
@@ -420,12 +612,12 @@ mod tests {
 
         // A short-deadline operation on one connection must be interrupted...
         let slow_conn = test_conn().await;
-        let _slow_guard = mgr.register(&slow_conn, Duration::from_millis(200));
+        let _slow_guard = mgr.register(&slow_conn, Duration::from_millis(200), None);
 
         // ...while a long-deadline operation on a different connection,
         // registered through the same manager, is left untouched.
         let fast_conn = test_conn().await;
-        let _fast_guard = mgr.register(&fast_conn, Duration::from_millis(60_000));
+        let _fast_guard = mgr.register(&fast_conn, Duration::from_millis(60_000), None);
 
         let slow = {
             let slow_conn = slow_conn.clone();
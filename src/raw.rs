@@ -0,0 +1,404 @@
+//! Access to the SQLite connection handle behind a local `libsql::Connection`.
+//!
+//! The `libsql` crate does not expose the `sqlite3` pointer of its local
+//! connections, but a few features need to call into the SQLite C API
+//! directly. We capture the pointer at the moment the connection is opened:
+//! an auto-extension registered once per process is invoked by
+//! `sqlite3_open_v2` for every new connection, and records the handle if the
+//! opening thread asked for it. Remote connections never open a SQLite
+//! connection, so they have no handle.
+
+use libsql::ffi;
+use std::{
+    cell::Cell,
+    ffi::{c_void, CStr, CString},
+    os::raw::{c_char, c_int},
+    sync::{Arc, Once},
+};
+
+/// The raw SQLite handle of a local connection.
+///
+/// The handle is owned by the `libsql::Connection` it was captured from and is
+/// only valid for as long as that connection is alive; holders must keep the
+/// connection around alongside it.
+pub struct RawConnection {
+    handle: *mut ffi::sqlite3,
+}
+
+// SAFETY: libSQL opens connections in serialized threading mode, so the
+// SQLite API can be called on the handle from any thread.
+unsafe impl Send for RawConnection {}
+unsafe impl Sync for RawConnection {}
+
+impl RawConnection {
+    /// Returns the extended result code of the most recent failed API call on
+    /// this connection.
+    pub fn extended_errcode(&self) -> i32 {
+        unsafe { ffi::sqlite3_extended_errcode(self.handle) }
+    }
+
+    /// Returns the byte offset into the SQL text of the token that caused the
+    /// most recent error, if SQLite was able to locate one.
+    pub fn error_offset(&self) -> Option<usize> {
+        let offset = unsafe { ffi::sqlite3_error_offset(self.handle) };
+        usize::try_from(offset).ok()
+    }
+
+    /// Returns the message describing the most recent failed API call on this
+    /// connection.
+    pub fn errmsg(&self) -> String {
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.handle)) };
+        message.to_string_lossy().into_owned()
+    }
+
+    /// Returns whether the database attached as `schema` is read-only,
+    /// whether because of the open flags or of URI parameters such as
+    /// `mode=ro`.
+    pub fn is_readonly(&self, schema: &CStr) -> bool {
+        unsafe { ffi::sqlite3_db_readonly(self.handle, schema.as_ptr()) == 1 }
+    }
+
+    /// Returns whether the database attached as `schema` is in memory or
+    /// temporary, as SQLite gives such databases no file name.
+    pub fn is_memory(&self, schema: &CStr) -> bool {
+        let name = unsafe { ffi::sqlite3_db_filename(self.handle, schema.as_ptr()) };
+        name.is_null() || unsafe { *name == 0 }
+    }
+
+    /// Runs a checkpoint of the given mode on the write-ahead log of `schema`,
+    /// or of every attached database if `schema` is `None`. Returns the result
+    /// code together with the number of frames in the log and the number of
+    /// frames checkpointed, both -1 if the database is not in WAL mode.
+    pub fn wal_checkpoint(&self, schema: Option<&CStr>, mode: c_int) -> (c_int, c_int, c_int) {
+        let (mut log, mut checkpointed) = (-1, -1);
+        let rc = unsafe {
+            ffi::sqlite3_wal_checkpoint_v2(
+                self.handle,
+                schema.map_or(std::ptr::null(), CStr::as_ptr),
+                mode,
+                &mut log,
+                &mut checkpointed,
+            )
+        };
+        (rc, log, checkpointed)
+    }
+
+    /// Returns the number of frames in the write-ahead log, as of the last
+    /// transaction of this connection.
+    pub fn wal_frame_count(&self) -> Result<u32, c_int> {
+        let mut frames = 0;
+        match unsafe { ffi::libsql_wal_frame_count(self.handle, &mut frames) } {
+            ffi::SQLITE_OK => Ok(frames),
+            rc => Err(rc),
+        }
+    }
+
+    /// Reads frame number `frame_no` of the write-ahead log, counting from
+    /// one, into `frame`, which must hold the frame header and a page.
+    pub fn wal_frame(&self, frame_no: u32, frame: &mut [u8]) -> Result<(), c_int> {
+        let rc = unsafe {
+            ffi::libsql_wal_get_frame(
+                self.handle,
+                frame_no,
+                frame.as_mut_ptr() as *mut c_void,
+                frame.len() as u32,
+            )
+        };
+        match rc {
+            ffi::SQLITE_OK => Ok(()),
+            rc => Err(rc),
+        }
+    }
+
+    /// Makes SQLite checkpoint the write-ahead log automatically once it grows
+    /// past `frames` frames. This replaces any WAL hook.
+    pub fn set_wal_autocheckpoint(&self, frames: c_int) {
+        unsafe {
+            ffi::sqlite3_wal_autocheckpoint(self.handle, frames);
+        }
+    }
+
+    /// Returns the number of frames past which SQLite checkpoints the
+    /// write-ahead log automatically, or 0 if it does not.
+    pub fn wal_autocheckpoint(&self) -> Result<c_int, c_int> {
+        let stmt = self.prepare("PRAGMA wal_autocheckpoint")?;
+        match unsafe { ffi::sqlite3_step(stmt.handle) } {
+            ffi::SQLITE_ROW => Ok(unsafe { ffi::sqlite3_column_int(stmt.handle, 0) }),
+            rc => Err(rc),
+        }
+    }
+
+    /// Returns the page size of the main database.
+    pub fn page_size(&self) -> Result<u64, c_int> {
+        let stmt = self.prepare("PRAGMA page_size")?;
+        match unsafe { ffi::sqlite3_step(stmt.handle) } {
+            ffi::SQLITE_ROW => Ok(unsafe { ffi::sqlite3_column_int64(stmt.handle, 0) } as u64),
+            rc => Err(rc),
+        }
+    }
+
+    /// Installs `hook` as the WAL hook of the connection, to be invoked with
+    /// `arg` after every commit to a write-ahead log. This replaces automatic
+    /// checkpointing.
+    ///
+    /// # Safety
+    ///
+    /// `arg` must remain valid for as long as the hook is installed.
+    pub unsafe fn set_wal_hook(
+        &self,
+        hook: unsafe extern "C" fn(*mut c_void, *mut ffi::sqlite3, *const c_char, c_int) -> c_int,
+        arg: *mut c_void,
+    ) {
+        unsafe {
+            ffi::sqlite3_wal_hook(self.handle, Some(hook), arg);
+        }
+    }
+
+    /// Attaches `data` to the connection under `name`, replacing any data
+    /// previously attached under that name. SQLite holds the reference until
+    /// the connection is closed, which makes it suitable for state that C
+    /// callbacks registered on the connection point into.
+    pub fn set_clientdata<T: Send + Sync>(&self, name: &CStr, data: Arc<T>) {
+        unsafe extern "C" fn release<T>(data: *mut c_void) {
+            drop(unsafe { Arc::from_raw(data as *const T) });
+        }
+        unsafe {
+            ffi::sqlite3_set_clientdata(
+                self.handle,
+                name.as_ptr(),
+                Arc::into_raw(data) as *mut c_void,
+                Some(release::<T>),
+            );
+        }
+    }
+
+    /// Returns the data attached to the connection under `name`, if any.
+    ///
+    /// # Safety
+    ///
+    /// Any data attached under `name` must have been attached as an `Arc<T>`.
+    pub unsafe fn clientdata<T: Send + Sync>(&self, name: &CStr) -> Option<Arc<T>> {
+        let data = unsafe { ffi::sqlite3_get_clientdata(self.handle, name.as_ptr()) } as *const T;
+        if data.is_null() {
+            return None;
+        }
+        unsafe {
+            Arc::increment_strong_count(data);
+            Some(Arc::from_raw(data))
+        }
+    }
+
+    /// Releases the data attached to the connection under `name`, if any.
+    pub fn remove_clientdata(&self, name: &CStr) {
+        unsafe {
+            ffi::sqlite3_set_clientdata(self.handle, name.as_ptr(), std::ptr::null_mut(), None);
+        }
+    }
+
+    /// Installs `handler` as the progress handler of the connection, to be
+    /// invoked with `arg` roughly every `period` virtual machine instructions.
+    ///
+    /// # Safety
+    ///
+    /// `arg` must remain valid for as long as the handler is installed.
+    pub unsafe fn set_progress_handler(
+        &self,
+        period: c_int,
+        handler: unsafe extern "C" fn(*mut c_void) -> c_int,
+        arg: *mut c_void,
+    ) {
+        unsafe { ffi::sqlite3_progress_handler(self.handle, period, Some(handler), arg) }
+    }
+
+    /// Installs `handler` as the busy handler of the connection, to be invoked
+    /// with `arg` and the number of prior invocations for the same lock, or
+    /// removes the busy handler. This replaces any busy timeout.
+    ///
+    /// # Safety
+    ///
+    /// `arg` must remain valid for as long as the handler is installed.
+    pub unsafe fn set_busy_handler(
+        &self,
+        handler: Option<unsafe extern "C" fn(*mut c_void, c_int) -> c_int>,
+        arg: *mut c_void,
+    ) {
+        unsafe {
+            ffi::sqlite3_busy_handler(self.handle, handler, arg);
+        }
+    }
+
+    /// Returns whether the latest statement libSQL prepared on the connection
+    /// from `sql` makes no direct change to the database, read from that
+    /// statement rather than by preparing `sql` again. Returns `None` if the
+    /// connection has no such statement.
+    pub fn prepared_readonly(&self, sql: &str) -> Option<bool> {
+        unsafe {
+            // Statements are finalized under the mutex of the connection, so
+            // holding it keeps the statements listed valid.
+            let mutex = ffi::sqlite3_db_mutex(self.handle);
+            ffi::sqlite3_mutex_enter(mutex);
+            // SQLite lists the statements of a connection from the most
+            // recently prepared, each with the text it was prepared from up to
+            // the end of the statement.
+            let mut stmt = ffi::sqlite3_next_stmt(self.handle, std::ptr::null_mut());
+            let mut readonly = None;
+            while !stmt.is_null() {
+                let text = ffi::sqlite3_sql(stmt);
+                if !text.is_null() {
+                    let text = CStr::from_ptr(text).to_bytes();
+                    if !text.is_empty() && sql.as_bytes().starts_with(text) {
+                        readonly = Some(ffi::sqlite3_stmt_readonly(stmt) != 0);
+                        break;
+                    }
+                }
+                stmt = ffi::sqlite3_next_stmt(self.handle, stmt);
+            }
+            ffi::sqlite3_mutex_leave(mutex);
+            readonly
+        }
+    }
+
+    /// Prepares the first statement of `sql`, apart from the statements libSQL
+    /// prepares, so that its properties can be read.
+    pub fn prepare(&self, sql: &str) -> Result<RawStatement, c_int> {
+        let mut handle = std::ptr::null_mut();
+        let rc = unsafe {
+            ffi::sqlite3_prepare_v2(
+                self.handle,
+                sql.as_ptr().cast(),
+                c_int::try_from(sql.len()).map_err(|_| ffi::SQLITE_TOOBIG)?,
+                &mut handle,
+                std::ptr::null_mut(),
+            )
+        };
+        match rc {
+            ffi::SQLITE_OK => Ok(RawStatement { handle }),
+            rc => Err(rc),
+        }
+    }
+}
+
+/// A statement prepared on a `RawConnection`, finalized when dropped.
+///
+/// The handle is null if the SQL text held no statement.
+pub struct RawStatement {
+    handle: *mut ffi::sqlite3_stmt,
+}
+
+impl RawStatement {
+    /// Returns the index of the parameter named `name`, prefix included.
+    pub fn parameter_index(&self, name: &str) -> Option<c_int> {
+        let name = CString::new(name).ok()?;
+        let index = unsafe { ffi::sqlite3_bind_parameter_index(self.handle, name.as_ptr()) };
+        (index > 0).then_some(index)
+    }
+
+    /// Binds `value` to the parameter at `index`, starting at 1.
+    pub fn bind(&self, index: c_int, value: &libsql::Value) -> Result<(), c_int> {
+        use libsql::Value;
+        let rc = unsafe {
+            match value {
+                Value::Null => ffi::sqlite3_bind_null(self.handle, index),
+                Value::Integer(value) => ffi::sqlite3_bind_int64(self.handle, index, *value),
+                Value::Real(value) => ffi::sqlite3_bind_double(self.handle, index, *value),
+                Value::Text(value) => ffi::sqlite3_bind_text64(
+                    self.handle,
+                    index,
+                    value.as_ptr().cast(),
+                    value.len() as u64,
+                    ffi::SQLITE_TRANSIENT(),
+                    ffi::SQLITE_UTF8 as u8,
+                ),
+                Value::Blob(value) => ffi::sqlite3_bind_blob64(
+                    self.handle,
+                    index,
+                    value.as_ptr().cast(),
+                    value.len() as u64,
+                    ffi::SQLITE_TRANSIENT(),
+                ),
+            }
+        };
+        match rc {
+            ffi::SQLITE_OK => Ok(()),
+            rc => Err(rc),
+        }
+    }
+
+    /// Returns the SQL text of the statement with its bound parameters
+    /// expanded, or `None` if SQLite could not allocate it.
+    pub fn expanded_sql(&self) -> Option<String> {
+        unsafe {
+            let sql = ffi::sqlite3_expanded_sql(self.handle);
+            if sql.is_null() {
+                return None;
+            }
+            let expanded = CStr::from_ptr(sql).to_string_lossy().into_owned();
+            ffi::sqlite3_free(sql.cast());
+            Some(expanded)
+        }
+    }
+}
+
+impl Drop for RawStatement {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3_finalize(self.handle);
+        }
+    }
+}
+
+/// Returns the English description of the result code `rc`.
+pub fn errstr(rc: c_int) -> String {
+    let message = unsafe { CStr::from_ptr(ffi::sqlite3_errstr(rc)) };
+    message.to_string_lossy().into_owned()
+}
+
+thread_local! {
+    /// Whether a connection being opened on this thread should be captured.
+    static ARMED: Cell<bool> = const { Cell::new(false) };
+    /// The handle of the last connection opened on this thread while armed.
+    static CAPTURED: Cell<*mut ffi::sqlite3> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+unsafe extern "C" fn capture_handle(
+    db: *mut ffi::sqlite3,
+    _err_msg: *mut *const c_char,
+    _api: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    if ARMED.with(Cell::get) {
+        CAPTURED.with(|captured| captured.set(db));
+    }
+    ffi::SQLITE_OK
+}
+
+/// Connects to `db`, returning the raw handle of the new connection alongside
+/// it when the database is local.
+///
+/// `libsql::Database::connect` opens the SQLite connection synchronously on the
+/// calling thread, and it opens the connection it returns last, so the last
+/// handle captured while armed is the one backing the returned connection.
+pub fn connect(
+    db: &libsql::Database,
+) -> libsql::Result<(libsql::Connection, Option<RawConnection>)> {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        // Registering an auto-extension initializes SQLite, after which libSQL
+        // can no longer select the serialized threading mode. Opening (but not
+        // connecting to) an in-memory database runs libSQL's one-time setup
+        // first.
+        let _ = libsql::Database::open_in_memory();
+        unsafe {
+            ffi::sqlite3_auto_extension(Some(capture_handle));
+        }
+    });
+
+    CAPTURED.with(|captured| captured.set(std::ptr::null_mut()));
+    ARMED.with(|armed| armed.set(true));
+    let conn = db.connect();
+    ARMED.with(|armed| armed.set(false));
+    let handle = CAPTURED.with(|captured| captured.replace(std::ptr::null_mut()));
+
+    let conn = conn?;
+    let raw = (!handle.is_null()).then_some(RawConnection { handle });
+    Ok((conn, raw))
+}
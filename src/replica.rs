@@ -0,0 +1,327 @@
+//! State of embedded replicas that libSQL does not expose.
+//!
+//! libSQL does not report progress while it syncs an embedded replica, but it
+//! records how far the replica got next to the database file every time it
+//! commits a batch of frames: in `<path>-info` with the v2 sync protocol, and
+//! in `<path>-client_wal_index` with the v1 protocol. Reading that record
+//! before, during and after a sync tells how many frames have been applied.
+//!
+//! Offline replicas write locally and push their frames to the primary. The
+//! record then also tells which frames of the write-ahead log the primary
+//! already has, and the ones after it are the changes waiting to be pushed.
+//!
+//! Neither file is a documented interface of libSQL. Only the layouts of the
+//! versions this module knows are read, and a record in any other layout is
+//! treated as missing, so what is derived from it is best effort.
+
+use crate::raw::RawConnection;
+use std::path::Path;
+
+/// The version of the `-info` layout that is understood.
+const METADATA_VERSION: u64 = 0;
+
+/// The length of a `-client_wal_index` record: the log identifier, the last
+/// committed frame number and padding.
+const WAL_INDEX_LEN: usize = 16 + 8 + 8;
+
+/// The size of the header of a replicated frame, which is followed by a page.
+const FRAME_HEADER_SIZE: u64 = 24;
+
+/// The page size SQLite uses when a database does not tell otherwise.
+const DEFAULT_PAGE_SIZE: u64 = 4096;
+
+/// Returns the size of a replicated frame of a database with pages of
+/// `page_size` bytes.
+pub fn frame_size(page_size: u64) -> u64 {
+    FRAME_HEADER_SIZE + page_size
+}
+
+/// Reads the page size of the database at `path` from the header of its
+/// write-ahead log or, failing that, of the database file. A replica that has
+/// not been downloaded yet has the default page size.
+pub fn page_size(path: &str) -> u64 {
+    fn read_header(path: &str, len: usize) -> Option<Vec<u8>> {
+        use std::io::Read;
+        let mut header = vec![0; len];
+        std::fs::File::open(path)
+            .ok()?
+            .read_exact(&mut header)
+            .ok()?;
+        Some(header)
+    }
+    let valid =
+        |size: u64| (size.is_power_of_two() && (512..=65536).contains(&size)).then_some(size);
+    let from_wal = || {
+        let header = read_header(&format!("{path}-wal"), 32)?;
+        valid(u32::from_be_bytes(header[8..12].try_into().ok()?).into())
+    };
+    let from_database = || {
+        let header = read_header(path, 100)?;
+        // A page size of 65536 does not fit in the field and is stored as 1.
+        match u16::from_be_bytes(header[16..18].try_into().ok()?) {
+            1 => Some(65536),
+            size => valid(size.into()),
+        }
+    };
+    from_wal()
+        .or_else(from_database)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+}
+
+/// How far an embedded replica has synced, as recorded next to its database
+/// file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    /// The generation of the primary's log, with the v2 sync protocol.
+    pub generation: Option<u32>,
+    /// The identifier of the primary's log, with the v1 sync protocol.
+    pub log_id: Option<u128>,
+    /// The number of frames of the log the replica has applied.
+    pub frames: u64,
+}
+
+impl Position {
+    /// Reads the position of the replica at `path`. A replica that has never
+    /// synced has the default position.
+    pub fn read(path: &str) -> Self {
+        Self::read_v2(path)
+            .or_else(|| Self::read_v1(path))
+            .unwrap_or_default()
+    }
+
+    fn read_v2(path: &str) -> Option<Self> {
+        let contents = std::fs::read(format!("{path}-info")).ok()?;
+        let metadata: serde_json::Value = serde_json::from_slice(&contents).ok()?;
+        if metadata.get("version")?.as_u64()? != METADATA_VERSION {
+            return None;
+        }
+        Some(Self {
+            generation: Some(metadata.get("generation")?.as_u64()?.try_into().ok()?),
+            log_id: None,
+            frames: metadata.get("durable_frame_num")?.as_u64()?,
+        })
+    }
+
+    fn read_v1(path: &str) -> Option<Self> {
+        let path = Path::new(path);
+        let file_name = path.file_name()?.to_str()?;
+        let contents =
+            std::fs::read(path.with_file_name(format!("{file_name}-client_wal_index"))).ok()?;
+        if contents.len() != WAL_INDEX_LEN {
+            return None;
+        }
+        let log_id = u128::from_le_bytes(contents.get(0..16)?.try_into().ok()?);
+        let committed = u64::from_le_bytes(contents.get(16..24)?.try_into().ok()?);
+        Some(Self {
+            generation: None,
+            log_id: Some(log_id),
+            // Frame numbers start at zero, and the maximum marks a replica that
+            // has not committed any frame yet.
+            frames: committed.checked_add(1).unwrap_or(0),
+        })
+    }
+
+    /// Whether the replica has synced before.
+    pub fn is_known(&self) -> bool {
+        self.generation.is_some() || self.log_id.is_some()
+    }
+
+    /// Whether this position carries on from `start` without downloading the
+    /// database again: the same log with the v1 sync protocol, or the same or
+    /// a later generation with the v2 protocol, which moves from one
+    /// generation to the next by applying frames.
+    fn continues(&self, start: &Position) -> bool {
+        match (start.generation, self.generation) {
+            (Some(start), Some(generation)) => generation >= start,
+            _ => start.log_id.is_some() && self.log_id == start.log_id,
+        }
+    }
+
+    /// Returns the number of frames applied to get from `start` to this
+    /// position. Frames of a generation the replica moved on from, and frames
+    /// of a database downloaded again, are not counted.
+    pub fn frames_since(&self, start: &Position) -> u64 {
+        if self.continues(start) && self.generation == start.generation {
+            self.frames.saturating_sub(start.frames)
+        } else {
+            // The frames of a new generation or log are numbered from zero.
+            self.frames
+        }
+    }
+
+    /// Whether getting from `start` to this position downloaded the
+    /// database from scratch, either because the replica had never synced or
+    /// because the primary's log was replaced.
+    pub fn bootstrapped_from(&self, start: &Position) -> bool {
+        self.is_known() && !self.continues(start)
+    }
+}
+
+/// Local changes of an offline replica that have not been pushed yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pending {
+    /// The number of frames waiting to be pushed.
+    pub frames: u32,
+    /// The number of transactions those frames commit.
+    pub transactions: u32,
+}
+
+/// Returns the local changes of the offline replica at `path` that have not
+/// been pushed yet.
+pub fn pending_changes(raw: &RawConnection, path: &str) -> libsql::Result<Pending> {
+    let failure = |rc| libsql::Error::SqliteFailure(rc, raw.errmsg());
+    let pushed = u32::try_from(Position::read(path).frames).unwrap_or(u32::MAX);
+    let frames = raw.wal_frame_count().map_err(failure)?;
+    let page_size = raw.page_size().map_err(failure)?;
+    let mut pending = Pending::default();
+    let mut frame = vec![0; frame_size(page_size) as usize];
+    for frame_no in pushed.saturating_add(1)..=frames {
+        raw.wal_frame(frame_no, &mut frame).map_err(failure)?;
+        pending.frames += 1;
+        // The header of the last frame of a transaction holds the size of the
+        // database after the commit; the others hold zero.
+        if frame[4..8] != [0; 4] {
+            pending.transactions += 1;
+        }
+    }
+    Ok(pending)
+}
+
+/// A push rejected because the primary moved on independently of the
+/// replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// The primary has frames the replica does not know about.
+    Frame { local: u32, remote: u32 },
+    /// The primary is at an older generation than the replica.
+    Generation { local: u32, remote: u32 },
+}
+
+impl Conflict {
+    /// Recognizes a conflict in the message of a libSQL sync error, which
+    /// does not expose the underlying error otherwise.
+    pub fn parse(message: &str) -> Option<Self> {
+        fn numbers(message: &str, prefix: &str, first: &str, second: &str) -> Option<(u32, u32)> {
+            let rest = &message[message.find(prefix)? + prefix.len()..];
+            let (a, b) = rest.strip_prefix(first)?.split_once(", ")?;
+            let b = b.strip_prefix(second)?;
+            let end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            Some((a.parse().ok()?, b[..end].parse().ok()?))
+        }
+        if let Some((local, remote)) =
+            numbers(message, "server returned a conflict: ", "sent=", "got=")
+        {
+            return Some(Conflict::Frame { local, remote });
+        }
+        numbers(
+            message,
+            "server returned a lower generation than local: ",
+            "local=",
+            "remote=",
+        )
+        .map(|(local, remote)| Conflict::Generation { local, remote })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_is_read_from_replica_metadata() {
+        let dir = std::env::temp_dir().join(format!("libsql-js-replica-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replica.db");
+        let path = path.to_str().unwrap();
+
+        let start = Position::read(path);
+        assert_eq!(start, Position::default());
+        assert!(!start.is_known());
+
+        let mut index = 7u128.to_le_bytes().to_vec();
+        index.extend(u64::MAX.to_le_bytes());
+        index.extend([0; 8]);
+        std::fs::write(format!("{path}-client_wal_index"), &index).unwrap();
+        let empty = Position::read(path);
+        assert_eq!((empty.log_id, empty.frames), (Some(7), 0));
+        assert!(empty.bootstrapped_from(&start));
+
+        index[16..24].copy_from_slice(&9u64.to_le_bytes());
+        std::fs::write(format!("{path}-client_wal_index"), &index).unwrap();
+        let v1 = Position::read(path);
+        assert_eq!(v1.frames, 10);
+        assert_eq!(v1.frames_since(&empty), 10);
+        assert!(!v1.bootstrapped_from(&empty));
+
+        std::fs::write(
+            format!("{path}-info"),
+            r#"{"hash":1,"version":0,"durable_frame_num":5,"generation":2}"#,
+        )
+        .unwrap();
+        let v2 = Position::read(path);
+        assert_eq!((v2.generation, v2.log_id, v2.frames), (Some(2), None, 5));
+        assert_eq!(v2.frames_since(&v1), 5);
+        assert!(v2.bootstrapped_from(&v1));
+        let next = Position {
+            frames: 3,
+            generation: Some(3),
+            ..v2
+        };
+        assert_eq!(next.frames_since(&v2), 3);
+        assert!(!next.bootstrapped_from(&v2));
+
+        std::fs::write(
+            format!("{path}-info"),
+            r#"{"hash":1,"version":1,"durable_frame_num":5,"generation":2}"#,
+        )
+        .unwrap();
+        assert_eq!(Position::read(path), v1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn page_size_is_read_from_headers() {
+        let dir = std::env::temp_dir().join(format!("libsql-js-page-size-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replica.db");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(page_size(path), DEFAULT_PAGE_SIZE);
+
+        let mut header = vec![0; 100];
+        header[16..18].copy_from_slice(&1u16.to_be_bytes());
+        std::fs::write(path, &header).unwrap();
+        assert_eq!(page_size(path), 65536);
+
+        let mut wal = vec![0; 32];
+        wal[8..12].copy_from_slice(&8192u32.to_be_bytes());
+        std::fs::write(format!("{path}-wal"), &wal).unwrap();
+        assert_eq!(page_size(path), 8192);
+        assert_eq!(frame_size(page_size(path)), 24 + 8192);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn conflicts_are_recognized_in_sync_errors() {
+        assert_eq!(
+            Conflict::parse("sync error: server returned a conflict: sent=12, got=15"),
+            Some(Conflict::Frame {
+                local: 12,
+                remote: 15
+            })
+        );
+        assert_eq!(
+            Conflict::parse("server returned a lower generation than local: local=3, remote=2"),
+            Some(Conflict::Generation {
+                local: 3,
+                remote: 2
+            })
+        );
+        assert_eq!(
+            Conflict::parse("server returned a lower frame_no: sent=1, got=0"),
+            None
+        );
+    }
+}
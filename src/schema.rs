@@ -0,0 +1,342 @@
+//! Introspection of the schema of a database.
+//!
+//! The objects of a database are listed in its `sqlite_schema` table, and
+//! `PRAGMA table_list` tells plain tables from virtual and shadow ones. The
+//! details of each table come from the `table_xinfo`, `foreign_key_list`,
+//! `index_list` and `index_xinfo` pragmas, queried through their table-valued
+//! functions so that names are bound as parameters rather than quoted.
+
+use crate::sql::quote_identifier;
+use libsql::params;
+
+/// The objects of a database, in the order they were created.
+#[derive(Debug, Default)]
+pub struct Schema {
+    pub tables: Vec<Table>,
+    pub views: Vec<View>,
+    pub virtual_tables: Vec<View>,
+    pub indexes: Vec<Index>,
+    pub triggers: Vec<Trigger>,
+}
+
+/// An ordinary table.
+#[derive(Debug)]
+pub struct Table {
+    pub name: String,
+    pub sql: Option<String>,
+    pub strict: bool,
+    pub without_rowid: bool,
+    pub columns: Vec<Column>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub indexes: Vec<TableIndex>,
+}
+
+/// A view, or a virtual table.
+#[derive(Debug)]
+pub struct View {
+    pub name: String,
+    pub sql: Option<String>,
+    pub columns: Vec<Column>,
+}
+
+/// A column of a table or view.
+#[derive(Debug)]
+pub struct Column {
+    pub name: String,
+    /// The declared type, empty if the column has none.
+    pub declared_type: String,
+    pub not_null: bool,
+    /// The SQL text of the default value.
+    pub default_value: Option<String>,
+    /// The position of the column in the primary key, starting at 1, or 0 if
+    /// it is not part of it.
+    pub primary_key: u32,
+    /// Whether the column is a hidden column of a virtual table.
+    pub hidden: bool,
+    /// `"virtual"` or `"stored"` for generated columns.
+    pub generated: Option<&'static str>,
+}
+
+/// A foreign key constraint of a table.
+#[derive(Debug)]
+pub struct ForeignKey {
+    pub table: String,
+    pub from: Vec<String>,
+    /// The referenced columns, `None` for the primary key of the referenced
+    /// table.
+    pub to: Vec<Option<String>>,
+    pub on_update: String,
+    pub on_delete: String,
+    pub match_: String,
+}
+
+/// An index of a table.
+#[derive(Debug)]
+pub struct TableIndex {
+    pub name: String,
+    pub unique: bool,
+    /// `"c"` for indexes created with `CREATE INDEX`, `"u"` for `UNIQUE`
+    /// constraints and `"pk"` for `PRIMARY KEY` constraints.
+    pub origin: String,
+    pub partial: bool,
+    pub columns: Vec<IndexColumn>,
+}
+
+/// A key column of an index.
+#[derive(Debug)]
+pub struct IndexColumn {
+    /// The name of the indexed column, `None` for expressions.
+    pub name: Option<String>,
+    pub descending: bool,
+    pub collation: String,
+}
+
+/// An index, as listed in `sqlite_schema`.
+#[derive(Debug)]
+pub struct Index {
+    pub name: String,
+    pub table: String,
+    /// The `CREATE INDEX` statement.
+    pub sql: Option<String>,
+}
+
+/// A trigger.
+#[derive(Debug)]
+pub struct Trigger {
+    pub name: String,
+    pub table: String,
+    pub sql: Option<String>,
+}
+
+/// Reads the schema of the database attached to `conn` as `schema`.
+pub async fn read(conn: &libsql::Connection, schema: &str) -> libsql::Result<Schema> {
+    let sql = format!(
+        "SELECT s.type, s.name, s.tbl_name, s.sql, l.type, l.wr, l.strict \
+         FROM {}.sqlite_schema AS s \
+         LEFT JOIN pragma_table_list AS l ON l.schema = ?1 AND l.name = s.name \
+         WHERE s.name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+         ORDER BY s.rowid",
+        quote_identifier(schema)
+    );
+    let mut rows = conn.query(&sql, params![schema]).await?;
+    let mut objects = Vec::new();
+    while let Some(row) = rows.next().await? {
+        objects.push((
+            row.get::<String>(0)?,
+            row.get::<String>(1)?,
+            row.get::<String>(2)?,
+            row.get::<Option<String>>(3)?,
+            row.get::<Option<String>>(4)?,
+            row.get::<Option<bool>>(5)?.unwrap_or(false),
+            row.get::<Option<bool>>(6)?.unwrap_or(false),
+        ));
+    }
+
+    let mut result = Schema::default();
+    for (kind, name, table, sql, list_type, without_rowid, strict) in objects {
+        match (kind.as_str(), list_type.as_deref()) {
+            ("table", Some("table")) => {
+                let columns = columns(conn, schema, &name).await?;
+                let foreign_keys = foreign_keys(conn, schema, &name).await?;
+                let indexes = table_indexes(conn, schema, &name).await?;
+                result.tables.push(Table {
+                    name,
+                    sql,
+                    strict,
+                    without_rowid,
+                    columns,
+                    foreign_keys,
+                    indexes,
+                });
+            }
+            ("table", Some("virtual")) => {
+                let columns = columns(conn, schema, &name).await?;
+                result.virtual_tables.push(View { name, sql, columns });
+            }
+            ("view", _) => {
+                let columns = columns(conn, schema, &name).await?;
+                result.views.push(View { name, sql, columns });
+            }
+            ("index", _) => result.indexes.push(Index { name, table, sql }),
+            ("trigger", _) => result.triggers.push(Trigger { name, table, sql }),
+            // Shadow tables store the content of virtual tables.
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+async fn columns(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<Column>> {
+    let mut rows = conn
+        .query(
+            "SELECT name, type, \"notnull\", dflt_value, pk, hidden \
+             FROM pragma_table_xinfo(?1, ?2) ORDER BY cid",
+            params![table, schema],
+        )
+        .await?;
+    let mut columns = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let hidden = row.get::<i64>(5)?;
+        columns.push(Column {
+            name: row.get(0)?,
+            declared_type: row.get(1)?,
+            not_null: row.get(2)?,
+            default_value: row.get(3)?,
+            primary_key: row.get(4)?,
+            hidden: hidden == 1,
+            generated: match hidden {
+                2 => Some("virtual"),
+                3 => Some("stored"),
+                _ => None,
+            },
+        });
+    }
+    Ok(columns)
+}
+
+async fn foreign_keys(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<ForeignKey>> {
+    let mut rows = conn
+        .query(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete, \"match\" \
+             FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+            params![table, schema],
+        )
+        .await?;
+    let mut foreign_keys: Vec<(i64, ForeignKey)> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id = row.get::<i64>(0)?;
+        let from = row.get::<String>(2)?;
+        let to = row.get::<Option<String>>(3)?;
+        // A constraint on several columns spans several rows.
+        match foreign_keys.last_mut() {
+            Some((last, foreign_key)) if *last == id => {
+                foreign_key.from.push(from);
+                foreign_key.to.push(to);
+            }
+            _ => foreign_keys.push((
+                id,
+                ForeignKey {
+                    table: row.get(1)?,
+                    from: vec![from],
+                    to: vec![to],
+                    on_update: row.get(4)?,
+                    on_delete: row.get(5)?,
+                    match_: row.get(6)?,
+                },
+            )),
+        }
+    }
+    Ok(foreign_keys.into_iter().map(|(_, fk)| fk).collect())
+}
+
+async fn table_indexes(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<TableIndex>> {
+    let mut rows = conn
+        .query(
+            "SELECT name, \"unique\", origin, partial \
+             FROM pragma_index_list(?1, ?2) ORDER BY seq DESC",
+            params![table, schema],
+        )
+        .await?;
+    let mut indexes = Vec::new();
+    while let Some(row) = rows.next().await? {
+        indexes.push(TableIndex {
+            name: row.get(0)?,
+            unique: row.get(1)?,
+            origin: row.get(2)?,
+            partial: row.get(3)?,
+            columns: Vec::new(),
+        });
+    }
+    for index in &mut indexes {
+        let mut rows = conn
+            .query(
+                "SELECT name, \"desc\", coll FROM pragma_index_xinfo(?1, ?2) \
+                 WHERE key ORDER BY seqno",
+                params![index.name.as_str(), schema],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            index.columns.push(IndexColumn {
+                name: row.get(0)?,
+                descending: row.get(1)?,
+                collation: row.get(2)?,
+            });
+        }
+    }
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_tables_and_their_constraints() {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE parent(a, b, PRIMARY KEY (a, b)) WITHOUT ROWID;
+             CREATE TABLE child(
+                 id INTEGER PRIMARY KEY,
+                 a TEXT NOT NULL DEFAULT 'x',
+                 b INTEGER,
+                 c INTEGER AS (b * 2) STORED,
+                 FOREIGN KEY (a, b) REFERENCES parent ON DELETE CASCADE
+             ) STRICT;
+             CREATE INDEX child_b ON child(b DESC, lower(a)) WHERE b > 0;
+             CREATE VIEW children AS SELECT id, c FROM child;
+             CREATE TRIGGER child_insert AFTER INSERT ON child BEGIN SELECT 1; END;",
+        )
+        .await
+        .unwrap();
+
+        let schema = read(&conn, "main").await.unwrap();
+        let names = |tables: &[Table]| tables.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&schema.tables), ["parent", "child"]);
+        let parent = &schema.tables[0];
+        assert!(parent.without_rowid && !parent.strict);
+        assert_eq!(parent.indexes[0].origin, "pk");
+
+        let child = &schema.tables[1];
+        assert!(child.strict && !child.without_rowid);
+        let a = &child.columns[1];
+        assert!(a.not_null);
+        assert_eq!(a.default_value.as_deref(), Some("'x'"));
+        assert_eq!(child.columns[0].primary_key, 1);
+        assert_eq!(child.columns[3].generated, Some("stored"));
+        assert_eq!(child.foreign_keys[0].from, ["a", "b"]);
+        assert_eq!(child.foreign_keys[0].to, [None, None]);
+        assert_eq!(child.foreign_keys[0].on_delete, "CASCADE");
+        let index = &child.indexes[0];
+        assert!(index.partial && !index.unique);
+        assert_eq!(index.columns[0].name.as_deref(), Some("b"));
+        assert!(index.columns[0].descending);
+        assert_eq!(index.columns[1].name, None);
+
+        assert_eq!(schema.views[0].columns.len(), 2);
+        assert_eq!(schema.triggers[0].table, "child");
+        assert_eq!(
+            schema
+                .indexes
+                .iter()
+                .map(|i| i.name.as_str())
+                .collect::<Vec<_>>(),
+            ["child_b"]
+        );
+    }
+}
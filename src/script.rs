@@ -0,0 +1,203 @@
+//! Splitting SQL scripts into statements.
+//!
+//! A script is split after each semicolon that completes a statement, as the
+//! sqlite3 shell does, and the text that holds no statement is left out.
+//! Whether a semicolon completes a statement is told by the state machine of
+//! `sqlite3_complete()`, which knows about string literals, comments and the
+//! semicolons in the body of `CREATE TRIGGER` statements. It is ported here,
+//! as calling `sqlite3_complete()` on the text of each statement so far would
+//! take quadratic time on long statements.
+
+use std::ops::Range;
+
+/// Returns the byte ranges of the statements of `sql`, from their first token
+/// to their terminating semicolon, if any.
+pub fn split(sql: &str) -> Vec<Range<usize>> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for end in statement_ends(sql.as_bytes()) {
+        statements.extend(statement(sql, start..end));
+        start = end;
+    }
+    statements.extend(statement(sql, start..sql.trim_end().len().max(start)));
+    statements
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Semi,
+    Space,
+    Other,
+    Explain,
+    Create,
+    Temp,
+    Trigger,
+    End,
+}
+
+/// The transitions of the state machine of `sqlite3_complete()`, by state and
+/// token. The statement so far is complete in state 1.
+const TRANSITIONS: [[u8; 8]; 8] = [
+    // Semi, Space, Other, Explain, Create, Temp, Trigger, End
+    [1, 0, 2, 3, 4, 2, 2, 2], // 0: invalid
+    [1, 1, 2, 3, 4, 2, 2, 2], // 1: start
+    [1, 2, 2, 2, 2, 2, 2, 2], // 2: normal
+    [1, 3, 3, 2, 4, 2, 2, 2], // 3: explain
+    [1, 4, 2, 2, 2, 4, 5, 2], // 4: create
+    [6, 5, 5, 5, 5, 5, 5, 5], // 5: trigger
+    [6, 6, 5, 5, 5, 5, 5, 7], // 6: semicolon in a trigger
+    [1, 7, 5, 5, 5, 5, 5, 5], // 7: end of a trigger
+];
+
+/// Returns the offsets just after the semicolons of `sql` that complete a
+/// statement.
+fn statement_ends(sql: &[u8]) -> Vec<usize> {
+    // Text after a NUL character is cut short by SQLite, so it completes no
+    // statement.
+    let sql = &sql[..sql.iter().position(|&c| c == 0).unwrap_or(sql.len())];
+    let mut ends = Vec::new();
+    let mut state = 0;
+    let mut i = 0;
+    while i < sql.len() {
+        let rest = &sql[i..];
+        let (token, len) = match rest[0] {
+            b';' => (Token::Semi, 1),
+            b' ' | b'\r' | b'\t' | b'\n' | 0x0c => (Token::Space, 1),
+            b'/' if rest.starts_with(b"/*") => {
+                match rest[2..].windows(2).position(|close| close == b"*/") {
+                    Some(close) => (Token::Space, close + 4),
+                    None => break,
+                }
+            }
+            b'-' if rest.starts_with(b"--") => match rest.iter().position(|&c| c == b'\n') {
+                Some(newline) => (Token::Space, newline + 1),
+                None => break,
+            },
+            quote @ (b'[' | b'`' | b'"' | b'\'') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                match rest[1..].iter().position(|&c| c == close) {
+                    Some(end) => (Token::Other, end + 2),
+                    None => break,
+                }
+            }
+            c if is_id_char(c) => {
+                let len = rest
+                    .iter()
+                    .position(|&c| !is_id_char(c))
+                    .unwrap_or(rest.len());
+                (keyword(&rest[..len]), len)
+            }
+            _ => (Token::Other, 1),
+        };
+        state = TRANSITIONS[state as usize][token as usize];
+        i += len;
+        if matches!(token, Token::Semi) && state == 1 {
+            ends.push(i);
+        }
+    }
+    ends
+}
+
+/// Returns whether `c` can be part of an unquoted identifier.
+fn is_id_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+/// Returns the token of an unquoted identifier or keyword.
+fn keyword(word: &[u8]) -> Token {
+    [
+        ("create", Token::Create),
+        ("temp", Token::Temp),
+        ("temporary", Token::Temp),
+        ("trigger", Token::Trigger),
+        ("explain", Token::Explain),
+        ("end", Token::End),
+    ]
+    .into_iter()
+    .find(|(keyword, _)| word.eq_ignore_ascii_case(keyword.as_bytes()))
+    .map_or(Token::Other, |(_, token)| token)
+}
+
+/// Returns the range of the statement in `range`, without the whitespace and
+/// comments before it, unless there is no statement.
+fn statement(sql: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let start = skip_space_and_comments(sql, range.start, range.end);
+    if start == range.end || sql[start..range.end].starts_with(';') {
+        return None;
+    }
+    Some(start..range.end)
+}
+
+/// Returns the offset of the first token of `sql` between `start` and `end`.
+fn skip_space_and_comments(sql: &str, mut start: usize, end: usize) -> usize {
+    while start < end {
+        let rest = &sql[start..end];
+        if rest.starts_with("--") {
+            start += rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            start += comment.find("*/").map_or(rest.len(), |close| close + 4);
+        } else if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+            start += c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(sql: &str) -> Vec<&str> {
+        split(sql).into_iter().map(|range| &sql[range]).collect()
+    }
+
+    #[test]
+    fn splits_after_complete_statements() {
+        assert_eq!(
+            statements("SELECT 1; SELECT 'a;b';\n-- c;\nSELECT 2 /* ; */"),
+            ["SELECT 1;", "SELECT 'a;b';", "SELECT 2 /* ; */"]
+        );
+        assert_eq!(
+            statements("CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM b; END; SELECT 1;"),
+            [
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM b; END;",
+                "SELECT 1;"
+            ]
+        );
+    }
+
+    #[test]
+    fn completes_statements_as_sqlite() {
+        let scripts = [
+            "SELECT 1; SELECT 'a;b'; SELECT \"c;\", [d;], `e;`;",
+            "CREATE TEMP TRIGGER t AFTER INSERT ON a BEGIN SELECT 1; END; END;",
+            "create temporary trigger t after insert on a begin select 1; end;;",
+            "EXPLAIN CREATE TRIGGER t AFTER INSERT ON a BEGIN SELECT 1; END; SELECT 2;",
+            "CREATE TABLE end(x); SELECT 1 -- ;\n; SELECT /* ; */ 2;",
+            "SELECT 'unterminated; SELECT 2;",
+            "SELECT 1; SELECT 2\0; SELECT 3;",
+        ];
+        for script in scripts {
+            let mut expected = Vec::new();
+            let mut start = 0;
+            for (end, _) in script.match_indices(';') {
+                let complete = std::ffi::CString::new(&script[start..=end])
+                    .is_ok_and(|sql| unsafe { libsql::ffi::sqlite3_complete(sql.as_ptr()) != 0 });
+                if complete {
+                    expected.push(end + 1);
+                    start = end + 1;
+                }
+            }
+            assert_eq!(statement_ends(script.as_bytes()), expected, "{script}");
+        }
+    }
+
+    #[test]
+    fn skips_empty_statements() {
+        assert!(statements("").is_empty());
+        assert!(statements(" ;; -- done\n /* really */ ").is_empty());
+        assert_eq!(statements(";SELECT 1;;"), ["SELECT 1;"]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Write-ahead log maintenance for local connections: checkpoints run on
+//! demand with `sqlite3_wal_checkpoint_v2`, and commit notifications delivered
+//! by `sqlite3_wal_hook`.
+//!
+//! SQLite implements automatic checkpointing with the same hook, so a
+//! connection that is notified of commits no longer checkpoints on its own
+//! and is expected to call [`checkpoint`] on its own schedule.
+
+use crate::raw::RawConnection;
+use libsql::ffi;
+use std::{
+    ffi::{c_void, CStr, CString},
+    os::raw::{c_char, c_int},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// How much a checkpoint does, see `sqlite3_wal_checkpoint_v2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// Checkpoint as many frames as possible without waiting on readers or
+    /// writers.
+    Passive,
+    /// Wait for writers, then checkpoint every frame.
+    Full,
+    /// Like `Full`, then wait for readers so that the log restarts from the
+    /// beginning.
+    Restart,
+    /// Like `Restart`, then truncate the log file to zero bytes.
+    Truncate,
+}
+
+impl FromStr for CheckpointMode {
+    type Err = ();
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_ascii_lowercase().as_str() {
+            "passive" => Ok(CheckpointMode::Passive),
+            "full" => Ok(CheckpointMode::Full),
+            "restart" => Ok(CheckpointMode::Restart),
+            "truncate" => Ok(CheckpointMode::Truncate),
+            _ => Err(()),
+        }
+    }
+}
+
+impl CheckpointMode {
+    fn code(self) -> c_int {
+        match self {
+            CheckpointMode::Passive => ffi::SQLITE_CHECKPOINT_PASSIVE,
+            CheckpointMode::Full => ffi::SQLITE_CHECKPOINT_FULL,
+            CheckpointMode::Restart => ffi::SQLITE_CHECKPOINT_RESTART,
+            CheckpointMode::Truncate => ffi::SQLITE_CHECKPOINT_TRUNCATE,
+        }
+    }
+}
+
+/// The outcome of a checkpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Whether readers or writers kept the checkpoint from doing everything
+    /// its mode asks for.
+    pub busy: bool,
+    /// Number of frames in the log, or -1 if the database is not in WAL mode.
+    pub log_frames: i32,
+    /// Number of frames checkpointed, or -1 if the database is not in WAL
+    /// mode.
+    pub checkpointed_frames: i32,
+}
+
+/// Checkpoints the write-ahead log of `schema`, or of every attached database
+/// if `schema` is `None`.
+pub fn checkpoint(
+    raw: &RawConnection,
+    schema: Option<&str>,
+    mode: CheckpointMode,
+) -> libsql::Result<Checkpoint> {
+    let schema = schema
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| libsql::Error::Misuse("Schema name must not contain NUL bytes".into()))?;
+    let (rc, log_frames, checkpointed_frames) = raw.wal_checkpoint(schema.as_deref(), mode.code());
+    match rc {
+        ffi::SQLITE_OK | ffi::SQLITE_BUSY => Ok(Checkpoint {
+            busy: rc == ffi::SQLITE_BUSY,
+            log_frames,
+            checkpointed_frames,
+        }),
+        _ => Err(libsql::Error::SqliteFailure(
+            raw.extended_errcode(),
+            raw.errmsg(),
+        )),
+    }
+}
+
+/// A function notified with the schema and the number of frames in its log
+/// after every commit to a write-ahead log.
+pub type WalCallback = Box<dyn Fn(&str, u32) + Send + Sync>;
+
+const CLIENTDATA: &CStr = c"libsql-js.wal";
+
+/// The state a WAL hook points into.
+struct WalHook {
+    callback: WalCallback,
+    /// The automatic checkpoint threshold in effect before the first hook was
+    /// installed, restored when the hook is removed.
+    autocheckpoint: c_int,
+}
+
+/// Installs `callback` to be notified of commits, which turns automatic
+/// checkpointing off, or removes it and turns automatic checkpointing back on
+/// with the threshold the connection had before.
+pub fn set_wal_hook(raw: &RawConnection, callback: Option<WalCallback>) -> libsql::Result<()> {
+    // SAFETY: only this module attaches data under `CLIENTDATA`.
+    let installed = unsafe { raw.clientdata::<WalHook>(CLIENTDATA) };
+    match callback {
+        Some(callback) => {
+            let autocheckpoint = match installed {
+                Some(hook) => hook.autocheckpoint,
+                None => raw
+                    .wal_autocheckpoint()
+                    .map_err(|rc| libsql::Error::SqliteFailure(rc, crate::raw::errstr(rc)))?,
+            };
+            let hook = Arc::new(WalHook {
+                callback,
+                autocheckpoint,
+            });
+            // SAFETY: the connection keeps a reference to the hook state until
+            // it is replaced or the connection is closed, after which SQLite no
+            // longer invokes the hook.
+            unsafe {
+                raw.set_wal_hook(on_wal, Arc::as_ptr(&hook) as *mut c_void);
+            }
+            raw.set_clientdata(CLIENTDATA, hook);
+        }
+        None => {
+            if let Some(hook) = installed {
+                raw.set_wal_autocheckpoint(hook.autocheckpoint);
+                raw.remove_clientdata(CLIENTDATA);
+            }
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn on_wal(
+    arg: *mut c_void,
+    _db: *mut ffi::sqlite3,
+    schema: *const c_char,
+    pages: c_int,
+) -> c_int {
+    let hook = unsafe { &*(arg as *const WalHook) };
+    let schema = unsafe { CStr::from_ptr(schema) }.to_string_lossy();
+    (hook.callback)(&schema, pages.max(0) as u32);
+    ffi::SQLITE_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(10000)]
+    async fn hook_reports_commits_and_checkpoint_empties_the_log() {
+        let path = std::env::temp_dir().join(format!("libsql-js-wal-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = libsql::Builder::new_local(&path).build().await.unwrap();
+        let (conn, raw) = crate::raw::connect(&db).unwrap();
+        let raw = raw.unwrap();
+
+        let not_wal = checkpoint(&raw, None, CheckpointMode::Passive).unwrap();
+        assert_eq!((not_wal.log_frames, not_wal.checkpointed_frames), (-1, -1));
+
+        conn.query("PRAGMA journal_mode = WAL", ()).await.unwrap();
+        conn.query("PRAGMA wal_autocheckpoint = 50", ())
+            .await
+            .unwrap();
+        let commits = Arc::new(Mutex::new(Vec::new()));
+        {
+            let commits = commits.clone();
+            set_wal_hook(
+                &raw,
+                Some(Box::new(move |schema, pages| {
+                    commits.lock().unwrap().push((schema.to_string(), pages));
+                })),
+            )
+            .unwrap();
+        }
+        assert_eq!(raw.wal_autocheckpoint(), Ok(0));
+        conn.execute_batch("CREATE TABLE t(x); INSERT INTO t VALUES (1)")
+            .await
+            .unwrap();
+        let commits = std::mem::take(&mut *commits.lock().unwrap());
+        assert_eq!(commits.len(), 2);
+        assert!(commits
+            .iter()
+            .all(|(schema, pages)| schema == "main" && *pages > 0));
+
+        let result = checkpoint(&raw, Some("main"), CheckpointMode::Truncate).unwrap();
+        assert!(!result.busy);
+        assert_eq!(result.log_frames, 0);
+
+        let err = checkpoint(&raw, Some("missing"), CheckpointMode::Passive).unwrap_err();
+        assert!(
+            matches!(err, libsql::Error::SqliteFailure(_, message) if message.contains("missing"))
+        );
+
+        set_wal_hook(&raw, None).unwrap();
+        assert_eq!(raw.wal_autocheckpoint(), Ok(50));
+        drop((conn, raw, db));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}